@@ -11,7 +11,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     let cnfs: Vec<(String,CNF)>  = files.map(|file| {
         let file_path = file.unwrap().path();
         let file_path_str = file_path.to_str().unwrap();
-        (file_path_str.to_string(),CNF::from(parse_dimacs_cnf(file_path_str)))
+        (file_path_str.to_string(),CNF::from(parse_dimacs_cnf(file_path_str).unwrap()))
     }).collect();
 
 
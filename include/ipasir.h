@@ -0,0 +1,75 @@
+/*
+ * IPASIR interface of sat-solver, see src/ipasir.rs
+ * Link against the static or dynamic library built by cargo (libsat_solver.a or libsat_solver.so)
+ * Variables are positive integers and literals non-zero integers, as in DIMACS
+ * The solver is not incremental: every ipasir_solve hands the formula added so far to a fresh DPLL
+ * solver, with the assumptions as unit clauses, and nothing learned carries over to the next call
+ */
+
+#ifndef IPASIR_H
+#define IPASIR_H
+
+/* Generated by cbindgen from src/ipasir.rs, do not edit */
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Name and version of the solver
+ */
+const char *ipasir_signature(void);
+
+/*
+ New solver, released with ipasir_release
+ */
+void *ipasir_init(void);
+
+/*
+ Frees the solver
+ */
+void ipasir_release(void *s);
+
+/*
+ Adds lit to the clause being built, 0 ends the clause and adds it to the formula
+ */
+void ipasir_add(void *s, int32_t lit_or_zero);
+
+/*
+ Assumes lit for the next call to ipasir_solve only
+ */
+void ipasir_assume(void *s, int32_t lit);
+
+/*
+ 10 if satisfiable, 20 if unsatisfiable, 0 if interrupted by the terminate callback
+ Solves the formula from scratch with a fresh DPLL solver, no state is kept from earlier calls
+ */
+int ipasir_solve(void *s);
+
+/*
+ After a satisfiable solve: lit if it is true in the model, -lit if it is false, 0 if either value works
+ */
+int32_t ipasir_val(void *s, int32_t lit);
+
+/*
+ After an unsatisfiable solve: 1 if assumption lit was used to prove unsatisfiability, else 0
+ */
+int ipasir_failed(void *s, int32_t lit);
+
+/*
+ Polled during ipasir_solve, which gives up once it returns non-zero. NULL removes the callback
+ */
+void ipasir_set_terminate(void *s, void *data, int (*terminate)(void *data));
+
+/*
+ Does nothing: learned clauses stay inside the solver of each call, so the callback is never called
+ */
+void ipasir_set_learn(void *s, void *data, int max_length, void (*learn)(void *data, int32_t *clause));
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* IPASIR_H */
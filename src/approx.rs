@@ -0,0 +1,300 @@
+//Approximate model counting and near-uniform sampling by hashing with random XOR constraints
+//ApproxMC (Chakraborty, Meel, Vardi - Algorithmic Improvements in Approximate Counting for Probabilistic
+//Inference, 2016) and UniGen (Chakraborty, Fremont, Meel, Seshia, Vardi - Balancing Scalability and
+//Uniformity in SAT Witness Generator, 2014)
+//Random XORs over the sampling set split its models into cells of about equal size, and a cell is
+//small enough to enumerate completely. Samples are drawn uniformly from a whole cell, so they do not
+//follow the solver's preference for True.
+use num_bigint::BigUint;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{
+    definitions::{Assignments, RefLiteral, Xor, XorRef, CNF},
+    enumerate::Enumerator,
+    SolverBuilder,
+};
+
+pub struct ApproxMC<B> {
+    builder: B,
+    epsilon: f64,
+    delta: f64,
+    projection: Option<Vec<RefLiteral>>,
+    seed: Option<u64>,
+}
+
+impl<B: SolverBuilder + Clone> ApproxMC<B> {
+    pub fn new(builder: B) -> Self {
+        ApproxMC { builder, epsilon: 0.8, delta: 0.2, projection: None, seed: None }
+    }
+
+    //The estimate is within a factor 1 + epsilon of the count with probability at least 1 - delta
+    pub fn epsilon(mut self, epsilon: f64) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    pub fn delta(mut self, delta: f64) -> Self {
+        self.delta = delta;
+        self
+    }
+
+    //Count the assignments of these variables that extend to a model
+    pub fn projection<I: IntoIterator<Item = RefLiteral>>(mut self, variables: I) -> Self {
+        self.projection = Some(variables.into_iter().collect());
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    //None if a solver limit was hit
+    pub fn count(&self, cnf: &CNF) -> Option<BigUint> {
+        let variables = sampling_set(cnf, &self.projection);
+        let mut rng = rng(self.seed);
+        let epsilon = self.epsilon;
+        let threshold = 1 + (9.84 * (1.0 + epsilon / (1.0 + epsilon)) * (1.0 + 1.0 / epsilon).powi(2)).ceil() as usize;
+
+        let models = bounded(&self.builder, cnf.clone(), &variables, threshold)?;
+        if models.len() < threshold {
+            return Some(BigUint::from(models.len()));
+        }
+
+        //The median of the rounds' estimates is the count
+        let rounds = (17.0 * (3.0 / self.delta).log2()).ceil() as usize;
+        let mut estimates = vec![];
+        for _ in 0..rounds {
+            let hash = |hashed| add_xor(hashed, &variables, &mut rng);
+            estimates.push(round(&self.builder, cnf, &variables, threshold, hash)?);
+        }
+        estimates.sort();
+        estimates.get(estimates.len() / 2).cloned()
+    }
+}
+
+pub struct UniGen<B> {
+    builder: B,
+    kappa: f64,
+    projection: Option<Vec<RefLiteral>>,
+    seed: Option<u64>,
+}
+
+impl<B: SolverBuilder + Clone> UniGen<B> {
+    pub fn new(builder: B) -> Self {
+        UniGen { builder, kappa: 0.638, projection: None, seed: None }
+    }
+
+    //Tolerance of the cell sizes in (0, 1): smaller values are closer to uniform and slower
+    pub fn kappa(mut self, kappa: f64) -> Self {
+        self.kappa = kappa;
+        self
+    }
+
+    //Sample assignments of these variables, each extending to a model
+    pub fn projection<I: IntoIterator<Item = RefLiteral>>(mut self, variables: I) -> Self {
+        self.projection = Some(variables.into_iter().collect());
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    //Estimates the count once to choose the number of XORs for the samples, None if a solver limit
+    //was hit
+    pub fn sampler(self, cnf: CNF) -> Option<Sampler<B>> {
+        let variables = sampling_set(&cnf, &self.projection);
+        let mut rng = rng(self.seed);
+        let pivot = (3.0 * 0.5f64.exp() * (1.0 + 1.0 / self.kappa).powi(2)).ceil();
+        let high = 1 + ((1.0 + self.kappa) * pivot).ceil() as usize;
+        let low = (pivot / (1.0 + self.kappa)).floor() as usize;
+
+        let models = bounded(&self.builder, cnf.clone(), &variables, high + 1)?;
+        if models.len() <= high {
+            return Some(Sampler { builder: self.builder, cnf, variables, rng, low, high, xors: None, models });
+        }
+
+        //A rough estimate suffices, as the sampler tries several numbers of XORs around it
+        let count = ApproxMC::new(self.builder.clone())
+            .delta(0.8)
+            .projection(variables.clone())
+            .seed(rng.gen())
+            .count(&cnf)?;
+        let q = (count.bits() as f64 + 1.8f64.log2() - pivot.log2()).ceil().max(0.0) as usize;
+        Some(Sampler { builder: self.builder, cnf, variables, rng, low, high, xors: Some(q), models: vec![] })
+    }
+}
+
+pub struct Sampler<B> {
+    builder: B,
+    cnf: CNF,
+    variables: Vec<RefLiteral>,
+    rng: StdRng,
+    low: usize,
+    high: usize,
+    //Largest number of XORs tried per sample, None when every model is already in models
+    xors: Option<usize>,
+    models: Vec<Assignments>,
+}
+
+impl<B: SolverBuilder + Clone> Sampler<B> {
+    //An assignment of the sampling set extending to a model, None if no cell of a suitable size was
+    //found, the formula is UNSAT or a solver limit was hit
+    pub fn sample(&mut self) -> Option<Assignments> {
+        let q = match self.xors {
+            None => return self.models.choose(&mut self.rng).cloned(),
+            Some(q) => q,
+        };
+        for i in q.saturating_sub(3)..=q {
+            let hashed = (0..i).fold(self.cnf.clone(), |cnf, _| add_xor(cnf, &self.variables, &mut self.rng));
+            let cell = bounded(&self.builder, hashed, &self.variables, self.high + 1)?;
+            if (self.low..=self.high).contains(&cell.len()) {
+                return cell.choose(&mut self.rng).cloned();
+            }
+        }
+        None
+    }
+}
+
+fn rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+//Sorted by name so that a seed reproduces the same XORs
+fn sampling_set(cnf: &CNF, projection: &Option<Vec<RefLiteral>>) -> Vec<RefLiteral> {
+    let mut variables: Vec<RefLiteral> = match projection {
+        Some(variables) => variables.clone(),
+        None => cnf.variables().into_iter().collect(),
+    };
+    variables.sort_by(|a, b| a.name().cmp(b.name()));
+    variables.dedup();
+    variables
+}
+
+//Adds XORs until a cell has fewer models than the threshold, or one for each variable, and scales the
+//cell up by the number of XORs. Dependent XORs split the models less than that scaling assumes, up to
+//a cell still at the threshold after all of them, so the estimate is capped at the 2^|S| assignments.
+//None if a solver limit was hit
+fn round<B: SolverBuilder + Clone>(
+    builder: &B,
+    cnf: &CNF,
+    variables: &[RefLiteral],
+    threshold: usize,
+    mut hash: impl FnMut(CNF) -> CNF,
+) -> Option<BigUint> {
+    let mut hashed = cnf.clone();
+    let mut cell = threshold;
+    let mut m = 0;
+    while cell >= threshold && m < variables.len() {
+        hashed = hash(hashed);
+        m += 1;
+        cell = bounded(builder, hashed.clone(), variables, threshold)?.len();
+    }
+    Some((BigUint::from(cell) << m).min(BigUint::from(1u32) << variables.len()))
+}
+
+//Each variable with probability 1/2 and a random parity
+fn add_xor(cnf: CNF, variables: &[RefLiteral], rng: &mut StdRng) -> CNF {
+    let mut literals: Vec<RefLiteral> = variables.iter().filter(|_| rng.gen_bool(0.5)).cloned().collect();
+    let parity = rng.gen_bool(0.5);
+    let xor: XorRef = match literals.pop() {
+        Some(last) if parity => Xor::new(literals.iter().map(|v| v.identity()).chain([last.identity()])),
+        Some(last) => Xor::new(literals.iter().map(|v| v.identity()).chain([last.not()])),
+        //The empty XOR is false, or trivially true and not added
+        None if parity => Xor::new([]),
+        None => return cnf,
+    };
+    cnf.add_xor(xor)
+}
+
+//Up to limit projected models, None if a solver limit ended the enumeration first
+fn bounded<B: SolverBuilder + Clone>(
+    builder: &B,
+    cnf: CNF,
+    variables: &[RefLiteral],
+    limit: usize,
+) -> Option<Vec<Assignments>> {
+    let mut models = Enumerator::new(builder.clone()).projection(variables.to_vec()).limit(limit).models(cnf);
+    let found: Vec<Assignments> = models.by_ref().collect();
+    (found.len() == limit || models.exhausted()).then_some(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        count::count,
+        definitions::{CNFValue, Literal, LiteralValue},
+        dimacs::read_dimacs_cnf,
+        sdpll::DPLLSolverBuilder,
+    };
+
+    //Models of 1 v 2 v 3 over 1..n
+    fn clause(n: usize) -> CNF {
+        let mut dimacs = format!("p cnf {} {}\n1 2 3 0\n", n, n - 2);
+        for v in 4..=n {
+            dimacs.push_str(&format!("{} -{} 0\n", v, v));
+        }
+        CNF::from(read_dimacs_cnf(dimacs.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn approximate_count() {
+        let cnf = clause(8);
+        let exact = count(&cnf);
+        assert_eq!(exact, BigUint::from(7u32 << 5));
+        let estimate = ApproxMC::new(DPLLSolverBuilder::new()).epsilon(1.0).delta(0.8).seed(1).count(&cnf).unwrap();
+        assert!(estimate.clone() * 2u32 >= exact && estimate <= exact.clone() * 2u32, "{}", estimate);
+
+        //Below the threshold the count is exact
+        let small = clause(5);
+        assert_eq!(ApproxMC::new(DPLLSolverBuilder::new()).seed(1).count(&small), Some(BigUint::from(28u32)));
+        let projection = ["1", "2"].map(|v| Literal::new(v.to_string()));
+        let projected = ApproxMC::new(DPLLSolverBuilder::new()).projection(projection).seed(1).count(&small);
+        assert_eq!(projected, Some(BigUint::from(4u32)));
+        let unsat = CNF::from(read_dimacs_cnf("p cnf 1 2\n1 0\n-1 0\n".as_bytes()).unwrap());
+        assert_eq!(ApproxMC::new(DPLLSolverBuilder::new()).count(&unsat), Some(BigUint::from(0u32)));
+    }
+
+    #[test]
+    fn dependent_xors() {
+        //Trivially true XORs leave all 112 models of the 7 variables in the cell, which would estimate
+        //73 << 7 without the cap
+        let cnf = clause(7);
+        let variables = sampling_set(&cnf, &None);
+        let estimate = round(&DPLLSolverBuilder::new(), &cnf, &variables, 73, |hashed| hashed);
+        assert_eq!(estimate, Some(BigUint::from(128u32)));
+
+        //Repeating the XOR 4 ^ 5 = true halves the cell only once, so 56 models estimate 56 << 1, and
+        //below 56 the cell stays at the threshold after all 7 XORs
+        let xor = || Xor::new(["4", "5"].map(|v| Literal::new(v.to_string()).identity()));
+        let estimate = round(&DPLLSolverBuilder::new(), &cnf, &variables, 57, |hashed| hashed.add_xor(xor()));
+        assert_eq!(estimate, Some(BigUint::from(112u32)));
+        let estimate = round(&DPLLSolverBuilder::new(), &cnf, &variables, 20, |hashed| hashed.add_xor(xor()));
+        assert_eq!(estimate, Some(BigUint::from(128u32)));
+    }
+
+    #[test]
+    fn samples() {
+        let cnf = clause(7);
+        let mut sampler = UniGen::new(DPLLSolverBuilder::new()).seed(2).sampler(cnf.clone()).unwrap();
+        let samples: Vec<Assignments> = (0..60).filter_map(|_| sampler.sample()).collect();
+        assert!(samples.len() >= 45);
+        for sample in samples.iter() {
+            assert_eq!(sample.len(), 7);
+            assert_eq!(cnf.clone().evaluate(sample), CNFValue::SAT);
+        }
+
+        //A uniform sample sets each of 4..7 in about half of the samples, where the solver prefers True
+        for v in 4..=7 {
+            let variable = Literal::new(v.to_string());
+            let ones = samples.iter().filter(|s| s.get(&variable) == LiteralValue::True).count();
+            assert!(ones * 4 >= samples.len() && ones * 4 <= samples.len() * 3, "{} {}", v, ones);
+        }
+    }
+}
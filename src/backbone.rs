@@ -0,0 +1,182 @@
+//Backbone of a satisfiable formula: the literals true in every model
+//Iterative algorithm with model-based filtering (Janota, Lynce & Marques-Silva, Algorithms for Computing
+//Backbones of Propositional Formulae, 2015): a candidate literal is tested by solving with its
+//complement as an assumption, and every model found removes the candidates it falsifies
+//Assumptions are unit clauses of a fresh solver, together with the backbone literals found so far
+use std::cell::Cell;
+
+use crate::{
+    definitions::{Assignments, LiteralValue, RefLiteral, Satisfiability, SignedLiteral, CNF},
+    verify::verify_model,
+    SolverBuilder,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackboneResult {
+    //Sorted by variable name
+    Backbone(Vec<SignedLiteral>),
+    Unsatisfiable,
+    //A limit of the underlying solver was reached
+    Unknown,
+}
+
+pub struct BackboneExtractor<B> {
+    builder: B,
+    filtering: bool,
+    calls: Cell<usize>,
+}
+
+impl<B: SolverBuilder + Clone> BackboneExtractor<B> {
+    pub fn new(builder: B) -> Self {
+        BackboneExtractor { builder, filtering: true, calls: Cell::new(0) }
+    }
+
+    //Drop candidates falsified by the models of SAT calls, and candidates whose variable can be flipped
+    //in the first model
+    pub fn filtering(mut self, filtering: bool) -> Self {
+        self.filtering = filtering;
+        self
+    }
+
+    //Number of SAT calls made by the last computation
+    pub fn solver_calls(&self) -> usize {
+        self.calls.get()
+    }
+
+    pub fn backbone(&self, cnf: &CNF) -> BackboneResult {
+        self.calls.set(0);
+        let model = match self.sat(cnf, &[]) {
+            Some(Some(model)) => model,
+            Some(None) => return BackboneResult::Unsatisfiable,
+            None => return BackboneResult::Unknown,
+        };
+
+        //Variables the solver left unassigned take either value in some model
+        let mut variables: Vec<RefLiteral> = cnf.variables().into_iter().collect();
+        variables.sort_by(|a, b| a.name().cmp(b.name()));
+        let mut candidates: Vec<SignedLiteral> = variables
+            .iter()
+            .filter_map(|v| match model.get(v) {
+                LiteralValue::True => Some(v.identity()),
+                LiteralValue::False => Some(v.not()),
+                LiteralValue::Unassigned => None,
+            })
+            .collect();
+        if self.filtering {
+            let complete = candidates.iter().fold(Assignments::new(), |mut m, l| {
+                m.satisfy(l);
+                m
+            });
+            candidates.retain(|l| {
+                let mut flipped = complete.clone();
+                flipped.satisfy(&l.complement());
+                verify_model(cnf, &flipped).is_err()
+            });
+        }
+
+        let mut backbone: Vec<SignedLiteral> = vec![];
+        candidates.reverse();
+        while let Some(l) = candidates.pop() {
+            let assumptions: Vec<SignedLiteral> = backbone.iter().cloned().chain([l.complement()]).collect();
+            match self.sat(cnf, &assumptions) {
+                Some(None) => backbone.push(l),
+                Some(Some(model)) if self.filtering => candidates.retain(|c| c.evaluate(&model) == LiteralValue::True),
+                Some(Some(_)) => {}
+                None => return BackboneResult::Unknown,
+            }
+        }
+        BackboneResult::Backbone(backbone)
+    }
+
+    //Some(None) when UNSAT, None when the solver gave up
+    fn sat(&self, cnf: &CNF, assumptions: &[SignedLiteral]) -> Option<Option<Assignments>> {
+        self.calls.set(self.calls.get() + 1);
+        let cnf = assumptions
+            .iter()
+            .fold(cnf.clone(), |cnf, l| cnf.add_clause([l.clone()].into_iter().collect()));
+        let mut solver = self.builder.clone().build(cnf);
+        match solver.solve() {
+            Satisfiability::SAT => Some(Some(solver.model().unwrap_or_default())),
+            Satisfiability::UNSAT => Some(None),
+            Satisfiability::Unknown => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        definitions::{CNFValue, Literal},
+        dimacs::read_dimacs_cnf,
+        sdpll::DPLLSolverBuilder,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    //Literals true in every model, None if UNSAT
+    fn brute_force(cnf: &CNF) -> Option<Vec<SignedLiteral>> {
+        let mut variables: Vec<RefLiteral> = cnf.variables().into_iter().collect();
+        variables.sort_by(|a, b| a.name().cmp(b.name()));
+        let models: Vec<Assignments> = (0..1u32 << variables.len())
+            .map(|bits| {
+                let mut m = Assignments::new();
+                for (i, v) in variables.iter().enumerate() {
+                    m.assign(v.clone(), if bits & (1 << i) != 0 { LiteralValue::True } else { LiteralValue::False });
+                }
+                m
+            })
+            .filter(|m| cnf.clone().evaluate(m) == CNFValue::SAT)
+            .collect();
+        if models.is_empty() {
+            return None;
+        }
+        Some(
+            variables
+                .iter()
+                .flat_map(|v| [v.identity(), v.not()])
+                .filter(|l| models.iter().all(|m| l.evaluate(m) == LiteralValue::True))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn random_against_brute_force() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let (mut filtered_calls, mut plain_calls) = (0, 0);
+        for _ in 0..30 {
+            let num_clauses = rng.gen_range(5..25);
+            let mut dimacs = format!("p cnf 8 {}\n", num_clauses);
+            for _ in 0..num_clauses {
+                let len = rng.gen_range(1..=3);
+                let clause: Vec<String> = (0..len)
+                    .map(|_| {
+                        let var: i32 = rng.gen_range(1..=8);
+                        (if rng.gen_bool(0.5) { var } else { -var }).to_string()
+                    })
+                    .collect();
+                dimacs.push_str(&format!("{} 0\n", clause.join(" ")));
+            }
+            let cnf = CNF::from(read_dimacs_cnf(dimacs.as_bytes()).unwrap());
+            let expected = match brute_force(&cnf) {
+                Some(backbone) => BackboneResult::Backbone(backbone),
+                None => BackboneResult::Unsatisfiable,
+            };
+            let filtered = BackboneExtractor::new(DPLLSolverBuilder::new());
+            let plain = BackboneExtractor::new(DPLLSolverBuilder::new()).filtering(false);
+            assert_eq!(filtered.backbone(&cnf), expected, "{}", dimacs);
+            assert_eq!(plain.backbone(&cnf), expected, "{}", dimacs);
+            filtered_calls += filtered.solver_calls();
+            plain_calls += plain.solver_calls();
+        }
+        assert!(filtered_calls < plain_calls);
+    }
+
+    #[test]
+    fn forced_options() {
+        //1 forces 2 and 3, 4 is free
+        let cnf = CNF::from(read_dimacs_cnf("p cnf 4 4\n1 0\n-1 2 0\n-2 3 0\n4 -3 -4 0\n".as_bytes()).unwrap());
+        let backbone = BackboneExtractor::new(DPLLSolverBuilder::new()).backbone(&cnf);
+        let expected = ["1", "2", "3"].map(|v| Literal::new(v.to_string()).identity());
+        assert_eq!(backbone, BackboneResult::Backbone(expected.to_vec()));
+    }
+}
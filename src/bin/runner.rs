@@ -0,0 +1,461 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use sat_solver::{
+    config::SolverConfig,
+    definitions::{Satisfiability, CNF},
+    dimacs::{parse_dimacs_cnf, DimacsCnf},
+    limits::Limits,
+    statistics::Statistics,
+    verify::verify_dimacs_model,
+    SolverBuilder,
+};
+use serde_json::{json, Value};
+
+const USAGE: &str = "Usage: runner [OPTIONS] <DIR>
+
+Runs every solver configuration on every .cnf file in DIR and reports solve counts and PAR-2 scores.
+The expected answer of an instance is read from a sidecar file <instance>.expected containing SAT or
+UNSAT, or else inferred from its name (uf* is SAT, uuf* is UNSAT).
+
+Options:
+  --solver <SPEC>        Solver configuration, may be repeated (default: dpll)
+                         SPEC is <dpll|pdpll>[:<par-factor>][:<mom|random>][:share][:steal][:det], e.g. pdpll:2:random,
+                         dpll takes :neg or :randpol for the value tried first, or walksat, probsat, portfolio[:<threads>] or cube[:<threads>]
+  --timeout <SECONDS>    Per-instance time limit (default: 60)
+  --seed <N>             Seed for random choices, recorded per run (default: drawn at random per run)
+  --csv <FILE>           Write one row per run as CSV
+  --json <FILE>          Write one object per run as JSON
+  --cactus <FILE>        Write cactus plot data (config, solver, solved, seconds) as CSV
+  -h, --help             Print this help
+
+Exits with 1 if any answer contradicts the expected one or any model does not satisfy its formula.";
+
+#[derive(Debug)]
+struct Options {
+    dir: PathBuf,
+    configs: Vec<SolverConfig>,
+    timeout: Duration,
+    seed: Option<u64>,
+    csv: Option<PathBuf>,
+    json: Option<PathBuf>,
+    cactus: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Solved,
+    Timeout,
+    //Answer contradicts the expected one
+    Wrong,
+    //SAT with a model that does not satisfy the formula
+    InvalidModel,
+}
+
+struct Run {
+    instance: String,
+    //Index of the configuration in Options::configs, the same configuration may be given twice
+    config: usize,
+    solver: String,
+    answer: Satisfiability,
+    expected: Option<Satisfiability>,
+    status: Status,
+    seconds: f64,
+    statistics: Statistics,
+}
+
+fn parse_value<T: std::str::FromStr>(option: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("missing value for {}", option))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", option, value))
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Option<Options>, String> {
+    let mut dir = None;
+    let mut options = Options {
+        dir: PathBuf::new(),
+        configs: vec![],
+        timeout: Duration::from_secs(60),
+        seed: None,
+        csv: None,
+        json: None,
+        cactus: None,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--solver" => {
+                let spec: String = parse_value(&arg, args.next())?;
+                options.configs.push(spec.parse()?);
+            }
+            "--timeout" => {
+                let seconds: f64 = parse_value(&arg, args.next())?;
+                if !(seconds > 0.0 && seconds.is_finite()) {
+                    return Err(format!("invalid value for {}: {}", arg, seconds));
+                }
+                options.timeout = Duration::from_secs_f64(seconds);
+            }
+            "--seed" => options.seed = Some(parse_value(&arg, args.next())?),
+            "--csv" => options.csv = Some(parse_value(&arg, args.next())?),
+            "--json" => options.json = Some(parse_value(&arg, args.next())?),
+            "--cactus" => options.cactus = Some(parse_value(&arg, args.next())?),
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+            _ => {
+                if dir.is_some() {
+                    return Err(format!("unexpected argument: {}", arg));
+                }
+                dir = Some(PathBuf::from(arg));
+            }
+        }
+    }
+    options.dir = dir.ok_or("missing benchmark directory")?;
+    if options.configs.is_empty() {
+        options.configs.push("dpll".parse()?);
+    }
+    Ok(Some(options))
+}
+
+fn instances(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|e| e == "cnf") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+//Sidecar <instance>.expected first, then SATLIB naming
+fn expected(path: &Path) -> Option<Satisfiability> {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".expected");
+    if let Ok(contents) = fs::read_to_string(&sidecar) {
+        let answer = contents.split_whitespace().find(|token| *token != "s");
+        return match answer {
+            Some("SAT" | "SATISFIABLE") => Some(Satisfiability::SAT),
+            Some("UNSAT" | "UNSATISFIABLE") => Some(Satisfiability::UNSAT),
+            _ => None,
+        };
+    }
+
+    let name = path.file_name()?.to_str()?;
+    if name.starts_with("uuf") {
+        Some(Satisfiability::UNSAT)
+    } else if name.starts_with("uf") {
+        Some(Satisfiability::SAT)
+    } else {
+        None
+    }
+}
+
+fn run(instance: &str, dimacs: &DimacsCnf, expected: Option<Satisfiability>, index: usize, config: SolverConfig) -> Run {
+    let solver_name = config.to_string();
+    let mut solver = config.build(CNF::from(dimacs.clone()));
+    let start = Instant::now();
+    let answer = solver.solve();
+    let seconds = start.elapsed().as_secs_f64();
+
+    let status = match answer {
+        Satisfiability::Unknown => Status::Timeout,
+        _ if expected.is_some_and(|e| e != answer) => Status::Wrong,
+        Satisfiability::SAT => match solver.model() {
+            Some(model) if verify_dimacs_model(dimacs, &model).is_ok() => Status::Solved,
+            _ => Status::InvalidModel,
+        },
+        Satisfiability::UNSAT => Status::Solved,
+    };
+    Run {
+        instance: instance.to_string(),
+        config: index,
+        solver: solver_name,
+        answer,
+        expected,
+        status,
+        seconds,
+        statistics: solver.statistics(),
+    }
+}
+
+fn answer_name(answer: Option<Satisfiability>) -> &'static str {
+    match answer {
+        Some(Satisfiability::SAT) => "SAT",
+        Some(Satisfiability::UNSAT) => "UNSAT",
+        Some(Satisfiability::Unknown) => "UNKNOWN",
+        None => "",
+    }
+}
+
+fn status_name(status: Status) -> &'static str {
+    match status {
+        Status::Solved => "solved",
+        Status::Timeout => "timeout",
+        Status::Wrong => "wrong",
+        Status::InvalidModel => "invalid-model",
+    }
+}
+
+//Runs that are not solved correctly count twice the timeout
+fn par2(runs: &[&Run], timeout: Duration) -> f64 {
+    runs.iter()
+        .map(|run| match run.status {
+            Status::Solved => run.seconds,
+            _ => 2.0 * timeout.as_secs_f64(),
+        })
+        .sum()
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_csv<W: Write>(out: &mut W, runs: &[Run]) -> io::Result<()> {
+    writeln!(out, "instance,config,solver,answer,expected,status,seconds,decisions,propagations,conflicts,seed")?;
+    for run in runs {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{:.6},{},{},{},{}",
+            csv_field(&run.instance),
+            run.config,
+            csv_field(&run.solver),
+            answer_name(Some(run.answer)),
+            answer_name(run.expected),
+            status_name(run.status),
+            run.seconds,
+            run.statistics.decisions,
+            run.statistics.propagations,
+            run.statistics.conflicts,
+            run.statistics.seed.map_or(String::new(), |seed| seed.to_string())
+        )?;
+    }
+    Ok(())
+}
+
+fn write_json<W: Write>(out: &mut W, runs: &[Run]) -> io::Result<()> {
+    let runs: Vec<Value> = runs
+        .iter()
+        .map(|run| {
+            json!({
+                "instance": run.instance,
+                "config": run.config,
+                "solver": run.solver,
+                "answer": answer_name(Some(run.answer)),
+                "expected": run.expected.map(|expected| answer_name(Some(expected))),
+                "status": status_name(run.status),
+                "seconds": run.seconds,
+                "decisions": run.statistics.decisions,
+                "propagations": run.statistics.propagations,
+                "conflicts": run.statistics.conflicts,
+                "seed": run.statistics.seed,
+            })
+        })
+        .collect();
+    serde_json::to_writer_pretty(&mut *out, &runs)?;
+    writeln!(out)
+}
+
+//Cactus plot: for each solver, the n-th fastest solved run against n
+fn write_cactus<W: Write>(out: &mut W, runs: &[Run], configs: &[SolverConfig]) -> io::Result<()> {
+    writeln!(out, "config,solver,solved,seconds")?;
+    for (index, config) in configs.iter().enumerate() {
+        let mut times: Vec<f64> = runs
+            .iter()
+            .filter(|run| run.config == index && run.status == Status::Solved)
+            .map(|run| run.seconds)
+            .collect();
+        times.sort_by(f64::total_cmp);
+        for (i, seconds) in times.iter().enumerate() {
+            writeln!(out, "{},{},{},{:.6}", index, csv_field(&config.to_string()), i + 1, seconds)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_file<F: FnOnce(&mut BufWriter<File>) -> io::Result<()>>(path: &Path, write: F) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write(&mut out)?;
+    out.flush()
+}
+
+fn write_summary<W: Write>(out: &mut W, runs: &[Run], options: &Options) -> io::Result<()> {
+    writeln!(
+        out,
+        "{:>6} {:<20} {:>7} {:>5} {:>6} {:>8} {:>6} {:>12}",
+        "config", "solver", "solved", "sat", "unsat", "timeout", "wrong", "PAR-2"
+    )?;
+    for (index, config) in options.configs.iter().enumerate() {
+        let solver = config.to_string();
+        let runs: Vec<&Run> = runs.iter().filter(|run| run.config == index).collect();
+        let count = |f: &dyn Fn(&Run) -> bool| runs.iter().filter(|run| f(run)).count();
+        writeln!(
+            out,
+            "{:>6} {:<20} {:>7} {:>5} {:>6} {:>8} {:>6} {:>12.3}",
+            index,
+            solver,
+            count(&|run| run.status == Status::Solved),
+            count(&|run| run.status == Status::Solved && run.answer == Satisfiability::SAT),
+            count(&|run| run.status == Status::Solved && run.answer == Satisfiability::UNSAT),
+            count(&|run| run.status == Status::Timeout),
+            count(&|run| matches!(run.status, Status::Wrong | Status::InvalidModel)),
+            par2(&runs, options.timeout)
+        )?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    pretty_env_logger::init();
+
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            return ExitCode::from(1);
+        }
+    };
+
+    let files = match instances(&options.dir) {
+        Ok(files) => files,
+        Err(error) => {
+            eprintln!("error: {}: {}", options.dir.display(), error);
+            return ExitCode::from(1);
+        }
+    };
+
+    let mut runs = vec![];
+    for file in files.iter() {
+        let instance = file.file_name().unwrap().to_string_lossy().to_string();
+        let dimacs = match parse_dimacs_cnf(&file.to_string_lossy()) {
+            Ok(dimacs) => dimacs,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                return ExitCode::from(1);
+            }
+        };
+        let expected = expected(file);
+        for (index, config) in options.configs.iter().enumerate() {
+            let mut config = config.clone().limits(Limits::new().time(options.timeout));
+            if let Some(seed) = options.seed {
+                config = config.seed(seed);
+            }
+            let run = run(&instance, &dimacs, expected, index, config);
+            println!(
+                "{:<30} {:<20} {:<8} {:>10.3}s {}",
+                run.instance,
+                run.solver,
+                answer_name(Some(run.answer)),
+                run.seconds,
+                status_name(run.status)
+            );
+            runs.push(run);
+        }
+    }
+
+    let result = (|| -> io::Result<()> {
+        println!();
+        write_summary(&mut io::stdout().lock(), &runs, &options)?;
+        if let Some(ref path) = options.csv {
+            write_file(path, |out| write_csv(out, &runs))?;
+        }
+        if let Some(ref path) = options.json {
+            write_file(path, |out| write_json(out, &runs))?;
+        }
+        if let Some(ref path) = options.cactus {
+            write_file(path, |out| write_cactus(out, &runs, &options.configs))?;
+        }
+        Ok(())
+    })();
+    if let Err(error) = result {
+        eprintln!("error: {}", error);
+        return ExitCode::from(1);
+    }
+
+    if runs
+        .iter()
+        .any(|run| matches!(run.status, Status::Wrong | Status::InvalidModel))
+    {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Both runs of the first configuration time out, the second one solves one of its two
+    fn runs() -> (Vec<Run>, Options) {
+        let options = parse_args(["--solver", "dpll", "--solver", "dpll", "--timeout", "10", "dir"].map(String::from).into_iter())
+            .unwrap()
+            .unwrap();
+        let run = |instance: &str, config: usize, answer: Satisfiability, status: Status| Run {
+            instance: instance.to_string(),
+            config,
+            solver: "dpll".to_string(),
+            answer,
+            expected: Some(Satisfiability::SAT),
+            status,
+            seconds: 1.5,
+            statistics: Statistics { decisions: 3, seed: Some(7), ..Statistics::default() },
+        };
+        let runs = vec![
+            run("uf20-01.cnf", 0, Satisfiability::Unknown, Status::Timeout),
+            run("uf20-01.cnf", 1, Satisfiability::SAT, Status::Solved),
+            run("uf\"75\".cnf", 0, Satisfiability::Unknown, Status::Timeout),
+            run("uf\"75\".cnf", 1, Satisfiability::Unknown, Status::Timeout),
+        ];
+        (runs, options)
+    }
+
+    #[test]
+    fn summary_per_configuration() {
+        let (runs, options) = runs();
+        let mut out = vec![];
+        write_summary(&mut out, &runs, &options).unwrap();
+        let rows: Vec<Vec<String>> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|line| line.split_whitespace().map(String::from).collect())
+            .collect();
+        assert_eq!(rows, [
+            ["0", "dpll", "0", "0", "0", "2", "0", "40.000"],
+            ["1", "dpll", "1", "1", "0", "1", "0", "21.500"],
+        ]);
+    }
+
+    #[test]
+    fn json_output() {
+        let (runs, _) = runs();
+        let mut out = vec![];
+        write_json(&mut out, &runs).unwrap();
+        let json: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 4);
+        assert_eq!(json[1]["config"], 1);
+        assert_eq!(json[1]["answer"], "SAT");
+        assert_eq!(json[1]["expected"], "SAT");
+        assert_eq!(json[1]["status"], "solved");
+        assert_eq!(json[1]["seconds"], 1.5);
+        assert_eq!(json[1]["decisions"], 3);
+        assert_eq!(json[1]["seed"], 7);
+        assert_eq!(json[2]["instance"], "uf\"75\".cnf");
+        assert_eq!(json[2]["status"], "timeout");
+    }
+}
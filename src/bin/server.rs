@@ -0,0 +1,75 @@
+use std::{process::ExitCode, time::Duration};
+
+use sat_solver::service::{Service, ServiceOptions};
+
+const USAGE: &str = "Usage: server [OPTIONS]
+
+Solves the formulas submitted over HTTP on a pool of solver threads until it is killed. See src/service.rs
+for the API, for example:
+  curl --data-binary @benchmarks/uf20-01.cnf 'localhost:7080/jobs?solver=pdpll:2&time_limit=10'
+  curl localhost:7080/jobs/1/events
+
+Options:
+  --listen <ADDR>          Address to listen on, host:port (default: 127.0.0.1:7080)
+  --threads <N>            Number of jobs solved at the same time (default: number of CPUs)
+  --queue <N>              Number of jobs waiting for a thread before submissions are refused (default: 64)
+  --connections <N>        Number of connections open at the same time, including event streams
+                           (default: 256)
+  --time-limit <SECONDS>   Time limit of every job, jobs may ask for less (default: 300)
+  -h, --help               Print this help";
+
+fn parse_value<T: std::str::FromStr>(option: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("missing value for {}", option))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", option, value))
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Option<(String, ServiceOptions)>, String> {
+    let mut address = "127.0.0.1:7080".to_string();
+    let mut options = ServiceOptions::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--listen" => address = parse_value(&arg, args.next())?,
+            "--threads" => options = options.threads(parse_value(&arg, args.next())?),
+            "--queue" => options = options.queue(parse_value(&arg, args.next())?),
+            "--connections" => options = options.connections(parse_value(&arg, args.next())?),
+            "--time-limit" => {
+                let seconds: f64 = parse_value(&arg, args.next())?;
+                if !(seconds >= 0.0 && seconds.is_finite()) {
+                    return Err(format!("invalid value for {}: {}", arg, seconds));
+                }
+                options = options.time(Duration::from_secs_f64(seconds));
+            }
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    Ok(Some((address, options)))
+}
+
+fn main() -> ExitCode {
+    pretty_env_logger::init();
+
+    let (address, options) = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            return ExitCode::from(1);
+        }
+    };
+
+    let result = Service::bind(&address, options).and_then(|service| {
+        println!("c listening on {}", service.local_addr()?);
+        service.serve()
+    });
+    if let Err(error) = result {
+        eprintln!("error: {}: {}", address, error);
+        return ExitCode::from(1);
+    }
+    ExitCode::SUCCESS
+}
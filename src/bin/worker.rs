@@ -0,0 +1,50 @@
+use std::process::ExitCode;
+
+use sat_solver::distributed::{Endpoint, WorkerListener};
+
+const USAGE: &str = "Usage: worker [OPTIONS]
+
+Conquers the cubes sent by sat-solver --workers until it is killed, with the solver configuration of each
+coordinator. Coordinators may connect at the same time.
+
+Options:
+  --listen <ADDR>        Address to listen on, host:port or unix:<path> (default: 127.0.0.1:7070)
+  -h, --help             Print this help";
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Option<Endpoint>, String> {
+    let mut endpoint = "127.0.0.1:7070".parse()?;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--listen" => endpoint = args.next().ok_or("missing value for --listen")?.parse()?,
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    Ok(Some(endpoint))
+}
+
+fn main() -> ExitCode {
+    pretty_env_logger::init();
+
+    let endpoint = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(endpoint)) => endpoint,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            return ExitCode::from(1);
+        }
+    };
+
+    let result = WorkerListener::bind(&endpoint).and_then(|listener| {
+        println!("c listening on {}", listener.endpoint()?);
+        listener.serve()
+    });
+    if let Err(error) = result {
+        eprintln!("error: {}: {}", endpoint, error);
+        return ExitCode::from(1);
+    }
+    ExitCode::SUCCESS
+}
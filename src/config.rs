@@ -0,0 +1,268 @@
+//Named solver configurations, written as <solver>[:<option>]... e.g. "dpll", "pdpll:4", "pdpll:2:random"
+//Options are a parallel factor (pdpll only), a decision heuristic (mom, random, dpll and pdpll only), the
+//value tried first at a decision (neg for False, randpol for a random one, True by default, dpll only),
+//learned clause sharing between the parallel branches (share, pdpll only) and work stealing between one
+//thread per available core instead of the parallel factor (steal, pdpll only), or a deterministic search
+//that gives the same answer on every run with the same seed and threads (det, pdpll only), with one
+//thread per available core if also given steal and 8 threads otherwise
+//"walksat" and "probsat" are the local search solvers, which never answer UNSAT
+//"portfolio[:<threads>]" runs diverse solvers in parallel, one per available core by default
+//"cube[:<threads>]" splits the formula into cubes and conquers them in parallel with dpll, taking the
+//heuristic and polarity options of dpll
+use std::{fmt, str::FromStr};
+
+use crate::{
+    cube::CubeAndConquerSolverBuilder,
+    definitions::{Heuristic, Polarity, CNF},
+    limits::Limits,
+    local_search::{ProbSATSolverBuilder, WalkSATSolverBuilder},
+    pdpll::PDPLLSolverBuilder,
+    portfolio::PortfolioSolverBuilder,
+    sdpll::DPLLSolverBuilder,
+    sharing::Sharing,
+    Solver, SolverBuilder,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum SolverKind {
+    DPLL,
+    PDPLL(usize),
+    WalkSAT,
+    ProbSAT,
+    Portfolio(usize),
+    CubeAndConquer(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolverConfig {
+    kind: SolverKind,
+    heuristic: Heuristic,
+    polarity: Polarity,
+    limits: Limits,
+    seed: Option<u64>,
+    sharing: bool,
+    stealing: Option<usize>,
+    deterministic: bool,
+}
+
+impl SolverConfig {
+    pub fn new(kind: SolverKind) -> Self {
+        SolverConfig {
+            kind,
+            heuristic: Heuristic::MOM,
+            polarity: Polarity::Positive,
+            limits: Limits::new(),
+            seed: None,
+            sharing: false,
+            stealing: None,
+            deterministic: false,
+        }
+    }
+
+    pub fn heuristic(mut self, heuristic: Heuristic) -> Self {
+        self.heuristic = heuristic;
+        self
+    }
+
+    //Only used by dpll and cube
+    pub fn polarity(mut self, polarity: Polarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    //Only used by pdpll
+    pub fn sharing(mut self, sharing: bool) -> Self {
+        self.sharing = sharing;
+        self
+    }
+
+    //Only used by pdpll
+    pub fn stealing(mut self, threads: usize) -> Self {
+        self.stealing = Some(threads);
+        self
+    }
+
+    //Only used by pdpll
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    pub fn kind(&self) -> SolverKind {
+        self.kind
+    }
+}
+
+impl SolverBuilder for SolverConfig {
+    fn build(self, formula: CNF) -> Box<dyn Solver> {
+        match self.kind {
+            SolverKind::DPLL => {
+                let builder =
+                    DPLLSolverBuilder::new().heuristic(self.heuristic).polarity(self.polarity).limits(self.limits);
+                match self.seed {
+                    Some(seed) => builder.seed(seed).build(formula),
+                    None => builder.build(formula),
+                }
+            }
+            SolverKind::PDPLL(par_factor) => {
+                let mut builder = PDPLLSolverBuilder::new(par_factor).heuristic(self.heuristic).limits(self.limits);
+                if self.sharing {
+                    builder = builder.sharing(Sharing::new());
+                }
+                if let Some(threads) = self.stealing {
+                    builder = builder.stealing(threads);
+                }
+                builder = builder.deterministic(self.deterministic);
+                match self.seed {
+                    Some(seed) => builder.seed(seed).build(formula),
+                    None => builder.build(formula),
+                }
+            }
+            SolverKind::WalkSAT => {
+                let builder = WalkSATSolverBuilder::new().limits(self.limits);
+                match self.seed {
+                    Some(seed) => builder.seed(seed).build(formula),
+                    None => builder.build(formula),
+                }
+            }
+            SolverKind::ProbSAT => {
+                let builder = ProbSATSolverBuilder::new().limits(self.limits);
+                match self.seed {
+                    Some(seed) => builder.seed(seed).build(formula),
+                    None => builder.build(formula),
+                }
+            }
+            SolverKind::Portfolio(threads) => {
+                let seed = self.seed.unwrap_or_else(rand::random);
+                PortfolioSolverBuilder::diverse(threads, seed).limits(self.limits).build(formula)
+            }
+            SolverKind::CubeAndConquer(threads) => {
+                let conquer = SolverConfig::new(SolverKind::DPLL).heuristic(self.heuristic).polarity(self.polarity);
+                let builder = CubeAndConquerSolverBuilder::new(conquer).threads(threads).limits(self.limits);
+                match self.seed {
+                    Some(seed) => builder.seed(seed).build(formula),
+                    None => builder.build(formula),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for SolverConfig {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut parts = spec.split(':');
+        let mut config = match parts.next() {
+            Some("dpll") => SolverConfig::new(SolverKind::DPLL),
+            Some("pdpll") => SolverConfig::new(SolverKind::PDPLL(4)),
+            Some("walksat") => SolverConfig::new(SolverKind::WalkSAT),
+            Some("probsat") => SolverConfig::new(SolverKind::ProbSAT),
+            Some("portfolio") => {
+                let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+                SolverConfig::new(SolverKind::Portfolio(threads))
+            }
+            Some("cube") => {
+                let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+                SolverConfig::new(SolverKind::CubeAndConquer(threads))
+            }
+            _ => return Err(format!("unknown solver: {}", spec)),
+        };
+        for option in parts {
+            match (option, config.kind) {
+                ("mom", SolverKind::DPLL | SolverKind::PDPLL(_) | SolverKind::CubeAndConquer(_)) => {
+                    config.heuristic = Heuristic::MOM
+                }
+                ("random", SolverKind::DPLL | SolverKind::PDPLL(_) | SolverKind::CubeAndConquer(_)) => {
+                    config.heuristic = Heuristic::Random
+                }
+                ("neg", SolverKind::DPLL | SolverKind::CubeAndConquer(_)) => config.polarity = Polarity::Negative,
+                ("randpol", SolverKind::DPLL | SolverKind::CubeAndConquer(_)) => config.polarity = Polarity::Random,
+                (_, SolverKind::PDPLL(_)) if option.parse::<usize>().is_ok() => {
+                    config.kind = SolverKind::PDPLL(option.parse().unwrap())
+                }
+                ("share", SolverKind::PDPLL(_)) => config.sharing = true,
+                ("det", SolverKind::PDPLL(_)) => config.deterministic = true,
+                ("steal", SolverKind::PDPLL(_)) => {
+                    config.stealing = Some(std::thread::available_parallelism().map_or(1, |n| n.get()))
+                }
+                (_, SolverKind::Portfolio(_)) if option.parse::<usize>().is_ok_and(|threads| threads > 0) => {
+                    config.kind = SolverKind::Portfolio(option.parse().unwrap())
+                }
+                (_, SolverKind::CubeAndConquer(_)) if option.parse::<usize>().is_ok_and(|threads| threads > 0) => {
+                    config.kind = SolverKind::CubeAndConquer(option.parse().unwrap())
+                }
+                _ => return Err(format!("invalid option '{}' in solver configuration {}", option, spec)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+//Inverse of from_str, limits and seed are not part of the name
+impl fmt::Display for SolverConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            SolverKind::DPLL => write!(f, "dpll")?,
+            SolverKind::PDPLL(par_factor) => write!(f, "pdpll:{}", par_factor)?,
+            SolverKind::WalkSAT => write!(f, "walksat")?,
+            SolverKind::ProbSAT => write!(f, "probsat")?,
+            SolverKind::Portfolio(threads) => write!(f, "portfolio:{}", threads)?,
+            SolverKind::CubeAndConquer(threads) => write!(f, "cube:{}", threads)?,
+        }
+        if self.heuristic == Heuristic::Random {
+            write!(f, ":random")?;
+        }
+        match self.polarity {
+            Polarity::Positive => {}
+            Polarity::Negative => write!(f, ":neg")?,
+            Polarity::Random => write!(f, ":randpol")?,
+        }
+        if self.sharing {
+            write!(f, ":share")?;
+        }
+        if self.stealing.is_some() {
+            write!(f, ":steal")?;
+        }
+        if self.deterministic {
+            write!(f, ":det")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_display() {
+        let specs = ["dpll", "dpll:random", "pdpll:4", "pdpll:2:random", "pdpll:4:random:share", "pdpll:4:steal"];
+        let more = ["pdpll:2:share:det", "pdpll:4:steal:det", "walksat", "probsat", "portfolio:8", "cube:2", "cube:4:random"];
+        let polarities = ["dpll:neg", "dpll:random:randpol", "cube:2:neg"];
+        for spec in specs.into_iter().chain(more).chain(polarities) {
+            assert_eq!(spec.parse::<SolverConfig>().unwrap().to_string(), spec);
+        }
+        assert_eq!("pdpll".parse::<SolverConfig>().unwrap().kind(), SolverKind::PDPLL(4));
+        assert_eq!("pdpll:mom".parse::<SolverConfig>().unwrap().to_string(), "pdpll:4");
+        assert!("dpll:4".parse::<SolverConfig>().is_err());
+        assert!("cdcl".parse::<SolverConfig>().is_err());
+        assert!("walksat:random".parse::<SolverConfig>().is_err());
+        assert!("pdpll:neg".parse::<SolverConfig>().is_err());
+        assert!("dpll:share".parse::<SolverConfig>().is_err());
+        assert!("walksat:steal".parse::<SolverConfig>().is_err());
+        assert!("portfolio:0".parse::<SolverConfig>().is_err());
+        assert!("cube:0".parse::<SolverConfig>().is_err());
+    }
+}
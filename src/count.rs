@@ -0,0 +1,314 @@
+//Exact model counting (#SAT): DPLL branching on both values, with unit propagation, component
+//decomposition and component caching (Thurley, sharpSAT - Counting Models with Advanced Component
+//Caching and Implicit BCP, 2006)
+//Counts are over the variables of the formula: every variable that disappears without being assigned
+//doubles the count
+use std::{cell::RefCell, collections::HashMap};
+
+use num_bigint::BigUint;
+
+use crate::definitions::{Assignments, CNFValue, LiteralValue, RefLiteral, SignedLiteral, CNF};
+
+pub struct ModelCounter {
+    components: bool,
+    caching: bool,
+    cache: RefCell<HashMap<String, BigUint>>,
+}
+
+impl ModelCounter {
+    pub fn new() -> Self {
+        ModelCounter {
+            components: true,
+            caching: true,
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn components(mut self, components: bool) -> Self {
+        self.components = components;
+        self
+    }
+
+    pub fn caching(mut self, caching: bool) -> Self {
+        self.caching = caching;
+        self
+    }
+
+    //Number of assignments of the formula's variables that satisfy it
+    pub fn count(&self, cnf: &CNF) -> BigUint {
+        self.cache.borrow_mut().clear();
+        let variables = cnf.variables().len();
+        match cnf.clone().evaluate(&Assignments::new()) {
+            CNFValue::UNSAT => BigUint::from(0u8),
+            CNFValue::SAT => BigUint::from(1u8) << variables,
+            CNFValue::Formula(f) => {
+                let freed = variables - f.variables().len();
+                self.count_formula(f) << freed
+            }
+        }
+    }
+
+    //Models over the variables of a simplified formula
+    fn count_formula(&self, mut f: CNF) -> BigUint {
+        let variables = f.variables().len();
+
+        //Unit propagation: forced values do not change the count
+        let mut forced = 0;
+        while let Some(unit) = unit(&f) {
+            forced += 1;
+            match f.evaluate(&Assignments::new().satisfy(&unit).to_owned()) {
+                CNFValue::UNSAT => return BigUint::from(0u8),
+                CNFValue::SAT => return BigUint::from(1u8) << (variables - forced),
+                CNFValue::Formula(g) => f = g,
+            }
+        }
+
+        let freed = variables - forced - f.variables().len();
+        let components = if self.components { split(f) } else { vec![f] };
+        let mut count = BigUint::from(1u8) << freed;
+        for component in components {
+            count *= self.count_component(component);
+            if count == BigUint::from(0u8) {
+                break;
+            }
+        }
+        count
+    }
+
+    fn count_component(&self, c: CNF) -> BigUint {
+        let key = self.caching.then(|| key(&c));
+        if let Some(count) = key
+            .as_ref()
+            .and_then(|key| self.cache.borrow().get(key).cloned())
+        {
+            return count;
+        }
+
+        let variables = c.variables().len();
+        let mut occurrences: HashMap<RefLiteral, usize> = HashMap::new();
+        for l in c.iter_literals() {
+            *occurrences.entry(l.literal()).or_insert(0) += 1;
+        }
+        let (variable, _) = occurrences
+            .into_iter()
+            .max_by(|(a, m), (b, n)| m.cmp(n).then_with(|| b.name().cmp(a.name())))
+            .unwrap();
+
+        let mut count = BigUint::from(0u8);
+        for value in [LiteralValue::True, LiteralValue::False] {
+            match c.clone().evaluate(
+                &Assignments::new()
+                    .assign(variable.clone(), value)
+                    .to_owned(),
+            ) {
+                CNFValue::UNSAT => {}
+                CNFValue::SAT => count += BigUint::from(1u8) << (variables - 1),
+                CNFValue::Formula(g) => {
+                    let freed = variables - 1 - g.variables().len();
+                    count += self.count_formula(g) << freed;
+                }
+            }
+        }
+
+        if let Some(key) = key {
+            self.cache.borrow_mut().insert(key, count.clone());
+        }
+        count
+    }
+}
+
+impl Default for ModelCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn count(cnf: &CNF) -> BigUint {
+    ModelCounter::new().count(cnf)
+}
+
+fn unit(f: &CNF) -> Option<SignedLiteral> {
+    f.clauses()
+        .find_map(|c| c.is_unit_clause())
+        .or_else(|| f.xors().find_map(|x| x.is_unit()))
+}
+
+//Connected components of the variable interaction graph, each with its clauses and XORs
+fn split(f: CNF) -> Vec<CNF> {
+    let mut index: HashMap<RefLiteral, usize> = HashMap::new();
+    let mut parent: Vec<usize> = vec![];
+    fn find(parent: &mut [usize], i: usize) -> usize {
+        let mut root = i;
+        while parent[root] != root {
+            root = parent[root];
+        }
+        let mut i = i;
+        while parent[i] != root {
+            let next = parent[i];
+            parent[i] = root;
+            i = next;
+        }
+        root
+    }
+
+    let constraints: Vec<Vec<RefLiteral>> = f
+        .clauses()
+        .map(|c| c.signed_literal().map(|l| l.literal()).collect())
+        .chain(
+            f.xors()
+                .map(|x| x.signed_literal().map(|l| l.literal()).collect()),
+        )
+        .collect();
+    for variables in constraints.iter() {
+        let ids: Vec<usize> = variables
+            .iter()
+            .map(|v| {
+                *index.entry(v.clone()).or_insert_with(|| {
+                    parent.push(parent.len());
+                    parent.len() - 1
+                })
+            })
+            .collect();
+        for id in ids.iter().skip(1) {
+            let (a, b) = (find(&mut parent, ids[0]), find(&mut parent, *id));
+            parent[a] = b;
+        }
+    }
+
+    let mut components: HashMap<usize, CNF> = HashMap::new();
+    let mut order = vec![];
+    let mut component = |variables: &Vec<RefLiteral>| {
+        let root = find(&mut parent, index[&variables[0]]);
+        components.entry(root).or_insert_with(|| {
+            order.push(root);
+            CNF::new()
+        });
+        root
+    };
+    let clause_roots: Vec<usize> = constraints
+        .iter()
+        .take(f.clauses().count())
+        .map(&mut component)
+        .collect();
+    let xor_roots: Vec<usize> = constraints
+        .iter()
+        .skip(f.clauses().count())
+        .map(&mut component)
+        .collect();
+
+    for (c, root) in f.clauses().zip(clause_roots) {
+        let cnf = components.remove(&root).unwrap();
+        components.insert(root, cnf.add_clause(c.clone()));
+    }
+    for (x, root) in f.xors().zip(xor_roots) {
+        let cnf = components.remove(&root).unwrap();
+        components.insert(root, cnf.add_xor(x.clone()));
+    }
+    order
+        .into_iter()
+        .map(|root| components.remove(&root).unwrap())
+        .collect()
+}
+
+//Canonical text of a component, independent of clause and literal order
+fn key(c: &CNF) -> String {
+    let mut constraints: Vec<String> = c
+        .clauses()
+        .map(|clause| {
+            let mut literals: Vec<String> = clause
+                .signed_literal()
+                .map(|l| match l {
+                    SignedLiteral::Id(v) => v.name().to_string(),
+                    SignedLiteral::Not(v) => format!("-{}", v.name()),
+                })
+                .collect();
+            literals.sort();
+            literals.join(" ")
+        })
+        .chain(c.xors().map(|xor| {
+            let mut literals: Vec<String> = xor
+                .signed_literal()
+                .map(|l| l.literal().name().to_string())
+                .collect();
+            literals.sort();
+            format!("x{} {}", xor.parity() as u8, literals.join(" "))
+        }))
+        .collect();
+    constraints.sort();
+    constraints.join("|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{definitions::Literal, dimacs::read_dimacs_cnf};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn brute_force(cnf: &CNF) -> BigUint {
+        let variables: Vec<RefLiteral> = cnf.variables().into_iter().collect();
+        let count = (0..1u32 << variables.len())
+            .filter(|bits| {
+                let mut m = Assignments::new();
+                for (i, v) in variables.iter().enumerate() {
+                    let value = if bits & (1 << i) != 0 {
+                        LiteralValue::True
+                    } else {
+                        LiteralValue::False
+                    };
+                    m.assign(v.clone(), value);
+                }
+                cnf.clone().evaluate(&m) == CNFValue::SAT
+            })
+            .count();
+        BigUint::from(count)
+    }
+
+    #[test]
+    fn random_against_brute_force() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..30 {
+            let num_clauses = rng.gen_range(5..30);
+            let num_xors = rng.gen_range(0..3);
+            let mut dimacs = format!("p cnf 10 {}\n", num_clauses + num_xors);
+            let literal = |rng: &mut StdRng| {
+                let var: i32 = rng.gen_range(1..=10);
+                if rng.gen_bool(0.5) {
+                    var
+                } else {
+                    -var
+                }
+            };
+            for _ in 0..num_clauses {
+                let len = rng.gen_range(1..=3);
+                let clause: Vec<String> = (0..len).map(|_| literal(&mut rng).to_string()).collect();
+                dimacs.push_str(&format!("{} 0\n", clause.join(" ")));
+            }
+            for _ in 0..num_xors {
+                let xor: Vec<String> = (0..3).map(|_| literal(&mut rng).to_string()).collect();
+                dimacs.push_str(&format!("x{} 0\n", xor.join(" ")));
+            }
+            let cnf = CNF::from(read_dimacs_cnf(dimacs.as_bytes()).unwrap());
+            let expected = brute_force(&cnf);
+            for components in [false, true] {
+                for caching in [false, true] {
+                    let counter = ModelCounter::new().components(components).caching(caching);
+                    assert_eq!(counter.count(&cnf), expected, "{}", dimacs);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn independent_components() {
+        //(x_i v y_i) for i in 1..=100 has 3^100 models, beyond any machine integer
+        let cnf = (0..100).fold(CNF::new(), |cnf, i| {
+            let x = Literal::new(format!("x{}", i));
+            let y = Literal::new(format!("y{}", i));
+            cnf.add_clause([x.identity(), y.identity()].into_iter().collect())
+        });
+        assert_eq!(count(&cnf), BigUint::from(3u8).pow(100));
+        assert_eq!(count(&CNF::new()), BigUint::from(1u8));
+        let unsat = CNF::from(read_dimacs_cnf("p cnf 1 2\n1 0\n-1 0\n".as_bytes()).unwrap());
+        assert_eq!(count(&unsat), BigUint::from(0u8));
+    }
+}
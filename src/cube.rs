@@ -0,0 +1,487 @@
+//Cube-and-conquer (Heule, Kullmann, Wieringa & Biere, Cube and Conquer: Guiding CDCL SAT Solvers by
+//Lookaheads, 2011)
+//The cuber splits the formula with a lookahead solver: every candidate variable is propagated both ways,
+//a literal whose propagation fails forces its complement, and the variable whose two propagations shrink
+//the most clauses is branched on. The leaves of this tree are the cubes, partial assignments that cover
+//the search space, which the conquer phase solves in parallel
+//None of the solvers is incremental, so cubes are not solved under assumptions of one solver as in the
+//paper: each cube gets a fresh solver of the conquer configuration with the cube literals as unit
+//clauses, as for the assumptions of backbone.rs and ipasir.rs. Nothing learned on one cube carries over
+//to the next
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+use crate::{
+    config::SolverConfig,
+    definitions::{Assignments, CNFValue, RefLiteral, Satisfiability, SignedLiteral, CNF},
+    limits::Limits,
+    statistics::Statistics,
+    Solver, SolverBuilder,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cutoff {
+    //Cubes have at most this many decisions
+    Depth(usize),
+    //Stop splitting once at most this many variables are left in the simplified formula
+    Variables(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cuber {
+    cutoff: Cutoff,
+    candidates: usize,
+}
+
+//Leaves of the lookahead tree, each a list of decisions
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cubes {
+    pub cubes: Vec<Vec<SignedLiteral>>,
+    //Leaves where both values of a variable failed, their decisions are UNSAT with the formula
+    pub refuted: Vec<Vec<SignedLiteral>>,
+}
+
+impl Cuber {
+    pub fn new() -> Self {
+        Cuber { cutoff: Cutoff::Depth(8), candidates: 32 }
+    }
+
+    pub fn cutoff(mut self, cutoff: Cutoff) -> Self {
+        self.cutoff = cutoff;
+        self
+    }
+
+    //Variables looked ahead at each node, the most frequent ones of the simplified formula
+    pub fn candidates(mut self, candidates: usize) -> Self {
+        self.candidates = candidates;
+        self
+    }
+
+    pub fn cubes(&self, cnf: &CNF) -> Cubes {
+        let mut cubes = Cubes::default();
+        match propagate(cnf, None) {
+            None => cubes.refuted.push(vec![]),
+            Some(CNFValue::Formula(f)) => self.split(f, &mut vec![], &mut cubes),
+            Some(_) => cubes.cubes.push(vec![]),
+        }
+        cubes
+    }
+
+    fn split(&self, mut f: CNF, cube: &mut Vec<SignedLiteral>, cubes: &mut Cubes) {
+        let done = match self.cutoff {
+            Cutoff::Depth(depth) => cube.len() >= depth,
+            Cutoff::Variables(variables) => f.variables().len() <= variables,
+        };
+        if done {
+            cubes.cubes.push(cube.clone());
+            return;
+        }
+
+        //Lookahead, restarted whenever a failed literal simplifies the formula
+        let (variable, branches) = 'lookahead: loop {
+            let lengths: HashMap<usize, usize> = f.clauses().map(|c| (c.id(), c.signed_literal().count())).collect();
+            let mut best: Option<(f64, RefLiteral, [CNF; 2])> = None;
+            for v in self.candidate_variables(&f) {
+                let positive = propagate(&f, Some(v.identity()));
+                let negative = propagate(&f, Some(v.not()));
+                match (positive, negative) {
+                    (None, None) => {
+                        cubes.refuted.push(cube.clone());
+                        return;
+                    }
+                    (Some(CNFValue::Formula(g)), None) | (None, Some(CNFValue::Formula(g))) => {
+                        debug!("Failed literal on {:?}", v);
+                        f = g;
+                        continue 'lookahead;
+                    }
+                    (Some(CNFValue::Formula(p)), Some(CNFValue::Formula(n))) => {
+                        let (a, b) = (reduction(&lengths, &p), reduction(&lengths, &n));
+                        //Favours balanced splits, as in march
+                        let score = 1024.0 * a * b + a + b;
+                        if best.as_ref().is_none_or(|(s, _, _)| score > *s) {
+                            best = Some((score, v, [p, n]));
+                        }
+                    }
+                    //A model was found, the conquering solver will find it again
+                    _ => {
+                        cubes.cubes.push(cube.clone());
+                        return;
+                    }
+                }
+            }
+            match best {
+                Some((_, variable, branches)) => break (variable, branches),
+                None => {
+                    cubes.cubes.push(cube.clone());
+                    return;
+                }
+            }
+        };
+
+        for (l, g) in [variable.identity(), variable.not()].into_iter().zip(branches) {
+            cube.push(l);
+            self.split(g, cube, cubes);
+            cube.pop();
+        }
+    }
+
+    fn candidate_variables(&self, f: &CNF) -> Vec<RefLiteral> {
+        let mut occurrences: HashMap<RefLiteral, usize> = HashMap::new();
+        for l in f.iter_literals() {
+            *occurrences.entry(l.literal()).or_insert(0) += 1;
+        }
+        let mut variables: Vec<(RefLiteral, usize)> = occurrences.into_iter().collect();
+        variables.sort_by(|(a, m), (b, n)| n.cmp(m).then_with(|| a.name().cmp(b.name())));
+        variables.into_iter().take(self.candidates.max(1)).map(|(v, _)| v).collect()
+    }
+}
+
+impl Default for Cuber {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//Unit propagation after assigning l, None on a conflict
+fn propagate(f: &CNF, l: Option<SignedLiteral>) -> Option<CNFValue> {
+    let mut m = Assignments::new();
+    if let Some(l) = l {
+        m.satisfy(&l);
+    }
+    let mut f = match f.clone().evaluate(&m) {
+        CNFValue::Formula(f) => f,
+        CNFValue::UNSAT => return None,
+        CNFValue::SAT => return Some(CNFValue::SAT),
+    };
+    loop {
+        let unit = f.clauses().find_map(|c| c.is_unit_clause()).or_else(|| f.xors().find_map(|x| x.is_unit()));
+        let Some(unit) = unit else {
+            return Some(CNFValue::Formula(f));
+        };
+        match f.evaluate(&Assignments::new().satisfy(&unit).to_owned()) {
+            CNFValue::Formula(g) => f = g,
+            CNFValue::UNSAT => return None,
+            CNFValue::SAT => return Some(CNFValue::SAT),
+        }
+    }
+}
+
+//Clauses shortened by a lookahead, weighted by how short they became
+fn reduction(lengths: &HashMap<usize, usize>, g: &CNF) -> f64 {
+    g.clauses()
+        .filter_map(|c| {
+            let len = c.signed_literal().count();
+            (lengths.get(&c.id()).is_some_and(|before| len < *before)).then(|| 0.2f64.powi(len as i32 - 2))
+        })
+        .sum()
+}
+
+//The formula followed by one "a" line per cube, variables keep the DIMACS numbers of the input as names
+//InvalidInput if a variable is not named by a positive number
+pub fn write_icnf<W: Write>(out: &mut W, cnf: &CNF, cubes: &[Vec<SignedLiteral>]) -> io::Result<()> {
+    let dimacs = |l: &SignedLiteral| -> io::Result<String> {
+        let name = l.literal().name().to_string();
+        let var: i32 = match name.parse() {
+            Ok(var) if var > 0 => var,
+            _ => {
+                let error = format!("iCNF needs DIMACS variable numbers, found {}", name);
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, error));
+            }
+        };
+        match l {
+            SignedLiteral::Id(_) => Ok(var.to_string()),
+            SignedLiteral::Not(_) => Ok((-var).to_string()),
+        }
+    };
+    writeln!(out, "p inccnf")?;
+    for clause in cnf.clauses() {
+        let literals: Vec<String> = clause.signed_literal().map(dimacs).collect::<io::Result<_>>()?;
+        writeln!(out, "{} 0", literals.join(" "))?;
+    }
+    for xor in cnf.xors() {
+        let mut literals: Vec<String> = xor.signed_literal().map(dimacs).collect::<io::Result<_>>()?;
+        //XOR literals are stored positively with the negations folded into the parity
+        if !xor.parity() {
+            if let Some(first) = literals.first_mut() {
+                *first = format!("-{}", first);
+            }
+        }
+        writeln!(out, "x{} 0", literals.join(" "))?;
+    }
+    for cube in cubes {
+        let literals: Vec<String> = cube.iter().map(dimacs).collect::<io::Result<_>>()?;
+        if literals.is_empty() {
+            writeln!(out, "a 0")?;
+        } else {
+            writeln!(out, "a {} 0", literals.join(" "))?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubeAndConquerSolverBuilder {
+    config: SolverConfig,
+    cuber: Cuber,
+    threads: usize,
+    limits: Limits,
+    seed: Option<u64>,
+}
+
+impl CubeAndConquerSolverBuilder {
+    //Cubes are conquered by solvers of this configuration, with the limits of the cube and conquer solver
+    pub fn new(config: SolverConfig) -> Self {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        CubeAndConquerSolverBuilder { config, cuber: Cuber::new(), threads, limits: Limits::new(), seed: None }
+    }
+
+    pub fn cuber(mut self, cuber: Cuber) -> Self {
+        self.cuber = cuber;
+        self
+    }
+
+    //Cubes solved at the same time, one per available core by default
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    //Shared by all cubes: each solver gets what is left when it starts
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    //The solver of the n-th cube is seeded with seed + n, replacing the seed of the configuration.
+    //Drawn from entropy if not set
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+impl SolverBuilder for CubeAndConquerSolverBuilder {
+    fn build(self, formula: CNF) -> Box<dyn Solver> {
+        Box::new(CubeAndConquerSolver {
+            formula,
+            config: self.config,
+            cuber: self.cuber,
+            threads: self.threads,
+            limits: self.limits,
+            seed: self.seed.unwrap_or_else(rand::random),
+            model: None,
+            core: None,
+            statistics: Statistics::default(),
+        })
+    }
+}
+
+pub struct CubeAndConquerSolver {
+    formula: CNF,
+    config: SolverConfig,
+    cuber: Cuber,
+    threads: usize,
+    limits: Limits,
+    seed: u64,
+    model: Option<Assignments>,
+    core: Option<CNF>,
+    //Summed over all cubes, with the wall-clock time of cubing and conquering
+    statistics: Statistics,
+}
+
+struct Conquered {
+    satisfiability: Satisfiability,
+    model: Option<Assignments>,
+    core: Option<CNF>,
+    statistics: Statistics,
+}
+
+impl Solver for CubeAndConquerSolver {
+    fn solve(&mut self) -> Satisfiability {
+        let start = Instant::now();
+        let cubes = self.cuber.cubes(&self.formula);
+        debug!("Cube and conquer: {} cubes, {} refuted", cubes.cubes.len(), cubes.refuted.len());
+
+        //Cubes refuted by the lookahead are solved too, their refutations are part of the core
+        let work: Vec<&Vec<SignedLiteral>> = cubes.cubes.iter().chain(cubes.refuted.iter()).collect();
+        let next = AtomicUsize::new(0);
+        let found = Arc::new(AtomicBool::new(false));
+        let total = Mutex::new(Statistics::default());
+        let results: Mutex<Vec<Conquered>> = Mutex::new(vec![]);
+        thread::scope(|scope| {
+            for _ in 0..self.threads.max(1) {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= work.len() || found.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let conflicts = total.lock().unwrap().conflicts;
+                    let limits = self.limits.remaining(start.elapsed(), conflicts).terminate(found.clone());
+                    let cnf = work[i]
+                        .iter()
+                        .fold(self.formula.clone(), |cnf, l| cnf.add_clause([l.clone()].into_iter().collect()));
+                    let config = self.config.clone().limits(limits).seed(self.seed.wrapping_add(i as u64));
+                    let mut solver = config.build(cnf);
+                    let satisfiability = solver.solve();
+                    if satisfiability == Satisfiability::SAT {
+                        found.store(true, Ordering::Relaxed);
+                    }
+                    let conquered = Conquered {
+                        satisfiability,
+                        model: solver.model(),
+                        core: solver.core(),
+                        statistics: solver.statistics(),
+                    };
+                    add(&mut total.lock().unwrap(), &conquered.statistics);
+                    results.lock().unwrap().push(conquered);
+                });
+            }
+        });
+
+        self.statistics = total.into_inner().unwrap();
+        self.statistics.elapsed = start.elapsed();
+        self.statistics.seed = Some(self.seed);
+        let results = results.into_inner().unwrap();
+        (self.model, self.core) = (None, None);
+        if let Some(sat) = results.iter().find(|r| r.satisfiability == Satisfiability::SAT) {
+            self.model = sat.model.clone();
+            return Satisfiability::SAT;
+        }
+        if results.len() < work.len() || results.iter().any(|r| r.satisfiability != Satisfiability::UNSAT) {
+            return Satisfiability::Unknown;
+        }
+
+        //Union of the input clauses of the cube cores, without the cube literals
+        let cores: Option<Vec<CNF>> = results.into_iter().map(|r| r.core).collect();
+        self.core = cores.map(|cores| {
+            let ids: HashSet<usize> = cores.iter().flat_map(|core| core.clauses().map(|c| c.id())).collect();
+            self.formula.subset(&ids, cores.iter().any(|core| core.xors().next().is_some()))
+        });
+        Satisfiability::UNSAT
+    }
+
+    fn model(&self) -> Option<Assignments> {
+        self.model.clone()
+    }
+
+    fn core(&self) -> Option<CNF> {
+        self.core.clone()
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.statistics.clone()
+    }
+}
+
+fn add(total: &mut Statistics, statistics: &Statistics) {
+    total.decisions += statistics.decisions;
+    total.propagations += statistics.propagations;
+    total.conflicts += statistics.conflicts;
+    total.elapsed = Duration::ZERO;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::SolverKind,
+        definitions::LiteralValue,
+        dimacs::read_dimacs_cnf,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    crate::tests::sat_tests!(CubeAndConquerSolverBuilder::new(SolverConfig::new(SolverKind::DPLL))
+        .cuber(Cuber::new().cutoff(Cutoff::Depth(2)))
+        .threads(2));
+
+    fn random_cnf(rng: &mut StdRng, variables: i32, clauses: usize) -> CNF {
+        let mut dimacs = format!("p cnf {} {}\n", variables, clauses);
+        for _ in 0..clauses {
+            let clause: Vec<String> = (0..3)
+                .map(|_| {
+                    let var = rng.gen_range(1..=variables);
+                    (if rng.gen_bool(0.5) { var } else { -var }).to_string()
+                })
+                .collect();
+            dimacs.push_str(&format!("{} 0\n", clause.join(" ")));
+        }
+        CNF::from(read_dimacs_cnf(dimacs.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn cubes_partition_models() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..20 {
+            let cnf = random_cnf(&mut rng, 10, 40);
+            let cubes = Cuber::new().cutoff(Cutoff::Depth(3)).cubes(&cnf);
+            assert!(cubes.cubes.iter().chain(cubes.refuted.iter()).all(|cube| cube.len() <= 3));
+
+            //Every model satisfies exactly one cube and no refuted cube
+            let holds = |cube: &Vec<SignedLiteral>, m: &Assignments| {
+                cube.iter().all(|l| l.evaluate(m) == LiteralValue::True)
+            };
+            for bits in 0..1u32 << 10 {
+                let mut m = Assignments::new();
+                for var in 0..10 {
+                    let value = if bits & (1 << var) != 0 { LiteralValue::True } else { LiteralValue::False };
+                    m.assign(Literal::new((var + 1).to_string()), value);
+                }
+                if cnf.clone().evaluate(&m) == CNFValue::SAT {
+                    assert_eq!(cubes.cubes.iter().filter(|cube| holds(cube, &m)).count(), 1);
+                    assert!(!cubes.refuted.iter().any(|cube| holds(cube, &m)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn variables_cutoff() {
+        let cnf = CNF::from(read_dimacs_cnf(include_str!("../benchmarks/uf75-01.cnf").as_bytes()).unwrap());
+        let cubes = Cuber::new().cutoff(Cutoff::Variables(60)).cubes(&cnf);
+        let shallow = Cuber::new().cutoff(Cutoff::Variables(70)).cubes(&cnf);
+        let leaves = |cubes: &Cubes| cubes.cubes.len() + cubes.refuted.len();
+        assert!(leaves(&shallow) > 1 && leaves(&shallow) < leaves(&cubes));
+
+        let mut solver = CubeAndConquerSolverBuilder::new(SolverConfig::new(SolverKind::DPLL))
+            .cuber(Cuber::new().cutoff(Cutoff::Variables(60)))
+            .build(cnf.clone());
+        assert_eq!(solver.solve(), Satisfiability::SAT);
+        assert_eq!(cnf.evaluate(&solver.model().unwrap()), CNFValue::SAT);
+    }
+
+    #[test]
+    fn unsat_core_covers_refuted_cubes() {
+        let cnf = CNF::from(read_dimacs_cnf(include_str!("../benchmarks/uuf50-01.cnf").as_bytes()).unwrap());
+        let mut solver = CubeAndConquerSolverBuilder::new(SolverConfig::new(SolverKind::DPLL)).build(cnf.clone());
+        assert_eq!(solver.solve(), Satisfiability::UNSAT);
+        let core = solver.core().unwrap();
+        assert!(core.clauses().count() <= cnf.clauses().count());
+        assert_eq!(SolverConfig::new(SolverKind::DPLL).build(core).solve(), Satisfiability::UNSAT);
+    }
+
+    #[test]
+    fn icnf_output() {
+        let cnf = CNF::from(read_dimacs_cnf("p cnf 3 2\n-2 0\nx-3 0\n".as_bytes()).unwrap());
+        let one = Literal::new("1".to_string());
+        let three = Literal::new("3".to_string());
+        let mut out = vec![];
+        write_icnf(&mut out, &cnf, &[vec![one.identity(), three.not()], vec![]]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "p inccnf\n-2 0\nx-3 0\na 1 -3 0\na 0\n");
+
+        let named = CNF::new().add_clause([Literal::new("x".to_string()).identity()].into_iter().collect());
+        let error = write_icnf(&mut vec![], &named, &[]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        let error = write_icnf(&mut vec![], &cnf, &[vec![Literal::new("0".to_string()).identity()]]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
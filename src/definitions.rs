@@ -1,7 +1,10 @@
 //CNF Definitions
-use std::{collections::{HashMap, HashSet}, vec, sync::Arc, cell::Ref};
-
-use log::debug;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::BuildHasherDefault,
+    sync::Arc,
+    vec,
+};
 
 use crate::dimacs::DimacsCnf;
 
@@ -10,6 +13,7 @@ use crate::dimacs::DimacsCnf;
 pub struct Literal(String);
 
 impl Literal {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(name: String) -> RefLiteral {
         RefLiteral::new(name)
     }
@@ -23,6 +27,10 @@ impl RefLiteral {
         RefLiteral(Arc::new(Literal(name)))
     }
 
+    pub fn name(&self) -> &str {
+        &self.0 .0
+    }
+
     pub fn identity(&self) -> SignedLiteral {
         SignedLiteral::Id(self.clone())
     }
@@ -32,6 +40,38 @@ impl RefLiteral {
     }
 }
 
+//Source of fresh auxiliary literals, named <prefix><index>
+#[derive(Debug, Clone)]
+pub struct LiteralPool {
+    prefix: String,
+    next: usize,
+}
+
+impl LiteralPool {
+    pub fn new(prefix: &str) -> LiteralPool {
+        LiteralPool { prefix: prefix.to_string(), next: 0 }
+    }
+
+    pub fn fresh(&mut self) -> RefLiteral {
+        self.next += 1;
+        RefLiteral::new(format!("{}{}", self.prefix, self.next))
+    }
+
+    pub fn len(&self) -> usize {
+        self.next
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.next == 0
+    }
+}
+
+impl Default for LiteralPool {
+    fn default() -> Self {
+        Self::new("__aux")
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Clone, Hash)]
 pub enum SignedLiteral {
     Id(RefLiteral),
@@ -57,8 +97,8 @@ impl SignedLiteral {
         let value = assignments
             .0
             .get(&self.literal())
-            .unwrap_or(&LiteralValue::Unassigned)
-            .clone();
+            .copied()
+            .unwrap_or(LiteralValue::Unassigned);
 
         match self {
             SignedLiteral::Id(_) => value,
@@ -97,10 +137,28 @@ impl Assignments {
         self
     }
 
+    //Assign the underlying literal so that the signed literal is true
+    pub fn satisfy(&mut self, literal: &SignedLiteral) -> &mut Self {
+        let value = match literal {
+            SignedLiteral::Id(_) => LiteralValue::True,
+            SignedLiteral::Not(_) => LiteralValue::False,
+        };
+        self.assign(literal.literal(), value)
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = (&RefLiteral, &LiteralValue)> {
         self.0.iter()
     }
 
+    pub fn extend(&mut self, other: Assignments) -> &mut Self {
+        self.0.extend(other.0);
+        self
+    }
+
+    pub fn get(&self, literal: &RefLiteral) -> LiteralValue {
+        self.0.get(literal).copied().unwrap_or(LiteralValue::Unassigned)
+    }
+
     pub fn len(&self) -> usize {
         self.0.len()
     }
@@ -110,9 +168,28 @@ impl Assignments {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl Default for Assignments {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//Literals of a constraint, hashed with fixed keys so that iteration orders, and the decisions that depend
+//on them, are the same on every run
+type LiteralSet = HashSet<SignedLiteral, BuildHasherDefault<DefaultHasher>>;
+
+#[derive(Debug, Clone)]
 pub struct Clause {
-    literals: HashSet<SignedLiteral>,
+    literals: LiteralSet,
+    //Input clause this one was simplified from, None for input clauses
+    origin: Option<Arc<Clause>>,
+}
+
+//Clauses are equal if their literals are, wherever they came from
+impl PartialEq for Clause {
+    fn eq(&self, other: &Self) -> bool {
+        self.literals == other.literals
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -127,28 +204,20 @@ pub struct ClauseRef(Arc<Clause>);
 
 impl ClauseValue {
     pub fn is_true(&self) -> bool {
-        match self {
-            ClauseValue::True => true,
-            _ => false,
-        }
+        matches!(self, ClauseValue::True)
     }
 
     pub fn is_false(&self) -> bool {
-        match self {
-            ClauseValue::False => true,
-            _ => false,
-        }
+        matches!(self, ClauseValue::False)
     }
 
     pub fn is_clause(&self) -> bool {
-        match self {
-            ClauseValue::Clause(_) => true,
-            _ => false,
-        }
+        matches!(self, ClauseValue::Clause(_))
     }
 }
 
 impl Clause {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> ClauseRef {
         ClauseRef::new()
     }
@@ -161,7 +230,7 @@ pub struct ClauseBuilder {
 impl ClauseBuilder {
     pub fn new() -> ClauseBuilder {
         ClauseBuilder {
-            clause: Clause{ literals: HashSet::new() },
+            clause: Clause{ literals: LiteralSet::default(), origin: None },
         }
     }
 
@@ -175,9 +244,28 @@ impl ClauseBuilder {
     }
 }
 
+impl Default for ClauseBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ClauseRef {
     pub fn new() -> ClauseRef {
-        ClauseRef(Arc::new(Clause{ literals: HashSet::new() }))
+        ClauseRef(Arc::new(Clause{ literals: LiteralSet::default(), origin: None }))
+    }
+
+    //Input clause this clause was simplified from by evaluate, itself for input clauses
+    pub fn origin(&self) -> ClauseRef {
+        match self.0.origin {
+            Some(ref origin) => ClauseRef(origin.clone()),
+            None => self.clone(),
+        }
+    }
+
+    //Identity of the origin, shared by every simplification of an input clause
+    pub(crate) fn id(&self) -> usize {
+        Arc::as_ptr(&self.origin().0) as usize
     }
 
     pub fn signed_literal(&self) -> impl Iterator<Item = &SignedLiteral> {
@@ -185,6 +273,11 @@ impl ClauseRef {
     }
 
     pub fn evaluate(self, assignments: &Assignments) -> ClauseValue {
+        // The empty clause has no literal that can be true
+        if self.0.literals.is_empty() {
+            return ClauseValue::False;
+        }
+
         let mut mark_for_removal = vec![];
         for literal in self.0.literals.iter() {
             match literal.evaluate(assignments) {
@@ -204,8 +297,9 @@ impl ClauseRef {
 
         if !mark_for_removal.is_empty() {
             let mut deep_clone = (*self.0).clone();
+            deep_clone.origin = Some(self.origin().0);
             for literal in mark_for_removal.iter() {
-                deep_clone.literals.remove(&literal);
+                deep_clone.literals.remove(literal);
             }
 
             if deep_clone.literals.is_empty() {
@@ -231,9 +325,122 @@ impl ClauseRef {
     }
 }
 
+impl FromIterator<SignedLiteral> for ClauseRef {
+    fn from_iter<I: IntoIterator<Item = SignedLiteral>>(literals: I) -> Self {
+        literals.into_iter().fold(ClauseBuilder::new(), |c, l| c.add_literal(l)).build()
+    }
+}
+
+impl Default for ClauseRef {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//XOR constraint: the number of true literals is odd iff parity is true
+//Literals are stored positively, negations are folded into the parity
+#[derive(Debug, Clone, PartialEq)]
+pub struct Xor {
+    literals: LiteralSet,
+    parity: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct XorRef(Arc<Xor>);
+
+#[derive(Debug, PartialEq)]
+pub enum XorValue {
+    True,
+    False,
+    Xor(XorRef),
+}
+
+impl Xor {
+    //l_1 ^ l_2 ^ ... ^ l_n = true, as in CryptoMiniSat's DIMACS x-lines
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<I: IntoIterator<Item = SignedLiteral>>(literals: I) -> XorRef {
+        let mut xor = Xor { literals: LiteralSet::default(), parity: true };
+        for literal in literals {
+            if let SignedLiteral::Not(_) = literal {
+                xor.parity = !xor.parity;
+            }
+            // x ^ x = 0
+            let literal = literal.literal().identity();
+            if !xor.literals.remove(&literal) {
+                xor.literals.insert(literal);
+            }
+        }
+        XorRef(Arc::new(xor))
+    }
+}
+
+impl XorRef {
+    pub fn signed_literal(&self) -> impl Iterator<Item = &SignedLiteral> {
+        self.0.literals.iter()
+    }
+
+    pub fn parity(&self) -> bool {
+        self.0.parity
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.literals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.literals.is_empty()
+    }
+
+    pub fn evaluate(self, assignments: &Assignments) -> XorValue {
+        let mut assigned = vec![];
+        let mut parity = self.0.parity;
+        for literal in self.0.literals.iter() {
+            match literal.evaluate(assignments) {
+                LiteralValue::True => {
+                    parity = !parity;
+                    assigned.push(literal.clone());
+                }
+                LiteralValue::False => assigned.push(literal.clone()),
+                LiteralValue::Unassigned => {}
+            }
+        }
+
+        if assigned.len() == self.0.literals.len() {
+            if parity { XorValue::False } else { XorValue::True }
+        } else if assigned.is_empty() {
+            XorValue::Xor(self)
+        } else {
+            let mut deep_clone = (*self.0).clone();
+            for literal in assigned.iter() {
+                deep_clone.literals.remove(literal);
+            }
+            deep_clone.parity = parity;
+            XorValue::Xor(XorRef(Arc::new(deep_clone)))
+        }
+    }
+
+    //A single remaining literal must take the parity as its value
+    pub fn is_unit(&self) -> Option<SignedLiteral> {
+        if self.0.literals.len() == 1 {
+            let literal = self.0.literals.iter().next().unwrap().literal();
+            Some(if self.0.parity { literal.identity() } else { literal.not() })
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct CNF {
     clauses: Vec<ClauseRef>,
+    xors: Vec<XorRef>,
+}
+
+//Constraint of the input falsified when a formula evaluates to UNSAT
+#[derive(Debug, PartialEq, Clone)]
+pub enum Conflict {
+    Clause(ClauseRef),
+    Xor,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -258,12 +465,19 @@ impl CNFValue {
             _ => self,
         }
     }
+
+    pub fn evaluate_traced(self, assignments: &Assignments) -> Result<CNFValue, Conflict> {
+        match self {
+            CNFValue::Formula(f) => f.evaluate_traced(assignments),
+            _ => Ok(self),
+        }
+    }
 }
 
 
 impl CNF {
     pub fn new() -> CNF {
-        CNF { clauses: Vec::new() }
+        CNF { clauses: Vec::new(), xors: Vec::new() }
     }
 
     //Insert in a sorted manner
@@ -278,13 +492,40 @@ impl CNF {
         self.clauses.iter()
     }
 
-    pub fn evaluate(self, assignments: &Assignments) -> CNFValue{
+    pub fn add_xor(mut self, xor: XorRef) -> Self {
+        self.xors.push(xor);
+        self
+    }
+
+    pub fn xors(&self) -> impl Iterator<Item = &XorRef> {
+        self.xors.iter()
+    }
+
+    pub fn evaluate(self, assignments: &Assignments) -> CNFValue {
+        self.evaluate_traced(assignments).unwrap_or(CNFValue::UNSAT)
+    }
+
+    //As evaluate, but an UNSAT formula reports the falsified input constraint instead
+    pub fn evaluate_traced(self, assignments: &Assignments) -> Result<CNFValue, Conflict> {
+        let mut xors = vec![];
+        for xor in self.xors.into_iter() {
+            match xor.evaluate(assignments) {
+                XorValue::False => {
+                    return Err(Conflict::Xor);
+                }
+                XorValue::Xor(x) => {
+                    xors.push(x);
+                }
+                XorValue::True => {}
+            }
+        }
+
         let mut clauses = vec![];        
         for clause in self.clauses.into_iter() {
-            match clause.evaluate(assignments) {
+            match clause.clone().evaluate(assignments) {
                 // CNF F is false if there is C in F st C is false
                 ClauseValue::False => {
-                    return CNFValue::UNSAT;
+                    return Err(Conflict::Clause(clause.origin()));
                 }
                 // Otherwise CNF F is unassigned
                 ClauseValue::Clause(c) => {
@@ -294,15 +535,30 @@ impl CNF {
                 ClauseValue::True => {}
             }
         }
-        if clauses.is_empty() {
-            CNFValue::SAT
+        if clauses.is_empty() && xors.is_empty() {
+            Ok(CNFValue::SAT)
         } else {
-            CNFValue::Formula(CNF { clauses })
+            Ok(CNFValue::Formula(CNF { clauses, xors }))
+        }
+    }
+
+    //Subformula of the clauses with the given ids, in their order in this formula
+    pub(crate) fn subset(&self, ids: &HashSet<usize>, xors: bool) -> CNF {
+        CNF {
+            clauses: self.clauses.iter().filter(|c| ids.contains(&c.id())).cloned().collect(),
+            xors: if xors { self.xors.clone() } else { vec![] },
         }
     }
 
     pub fn iter_literals(&self) -> impl Iterator<Item = &SignedLiteral> {
-        self.clauses.iter().flat_map(|c| c.signed_literal())
+        self.clauses
+            .iter()
+            .flat_map(|c| c.signed_literal())
+            .chain(self.xors.iter().flat_map(|x| x.signed_literal()))
+    }
+
+    pub fn variables(&self) -> HashSet<RefLiteral> {
+        self.iter_literals().map(|l| l.literal()).collect()
     }
 
     pub fn pure_literals(&self) -> HashSet<SignedLiteral> {
@@ -321,6 +577,11 @@ impl CNF {
                 }
             }
         });
+        //Both values of a variable in an XOR constraint matter
+        self.xors.iter().flat_map(|x| x.signed_literal()).for_each(|l| {
+            pure_literals.remove(l);
+            pure_literals.remove(&l.complement());
+        });
         pure_literals
     }
 
@@ -356,7 +617,7 @@ impl CNF {
                 let count = literal_counts.get(&literal).unwrap_or(&0usize);
                 (literal,*count)
             }).collect();
-            literal_counts.sort_by(|a,b| b.1.cmp(&a.1));
+            literal_counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
             literal_counts.into_iter().take(max_literals).map(|(literal,_)| literal).collect()
         }
         else {
@@ -366,6 +627,12 @@ impl CNF {
 
 }
 
+impl Default for CNF {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl From<DimacsCnf> for CNF {
     fn from(dimacs_cnf: DimacsCnf) -> Self {
         let mut cnf = CNF::new();
@@ -381,14 +648,57 @@ impl From<DimacsCnf> for CNF {
             }
             cnf = cnf.add_clause(c.build());
         }
+        for xor in dimacs_cnf.xors() {
+            let literals = xor.iter().map(|literal| {
+                let l = Literal::new(literal.abs().to_string());
+                if *literal > 0 { l.identity() } else { l.not() }
+            });
+            cnf = cnf.add_xor(Xor::new(literals));
+        }
         cnf
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Satisfiability {
     SAT,
     UNSAT,
+    //A limit was reached before the search completed
+    Unknown,
+}
+
+//Outcome of a search: SAT carries the assignments made on the way to the satisfying leaf
+#[derive(Debug, PartialEq, Clone)]
+pub enum SearchResult {
+    SAT(Assignments),
+    UNSAT,
+    Unknown,
+}
+
+impl SearchResult {
+    pub fn satisfiability(&self) -> Satisfiability {
+        match self {
+            SearchResult::SAT(_) => Satisfiability::SAT,
+            SearchResult::UNSAT => Satisfiability::UNSAT,
+            SearchResult::Unknown => Satisfiability::Unknown,
+        }
+    }
+}
+
+//Variable selection for decisions
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Heuristic {
+    //Most occurrences in clauses of minimum length
+    MOM,
+    Random,
+}
+
+//Value tried first for a decision variable
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Polarity {
+    Positive,
+    Negative,
+    Random,
 }
 
 #[cfg(test)]
@@ -1,12 +1,17 @@
 //DIMACS CNF parser
 //https://www.cs.ubc.ca/~hoos/SATLIB/Benchmarks/SAT/satformat.ps
+//XOR constraints as in CryptoMiniSat: "x1 -2 3 0" is x1 ^ ~x2 ^ x3 = true, counted in the header's clauses
 use std::fs::File;
 use std::io::{self, BufRead};
 
+use crate::definitions::{Literal, SignedLiteral};
+
+#[derive(Debug, Clone)]
 pub struct DimacsCnf {
     num_vars: usize,
     num_clauses: usize,
     clauses: Vec<Vec<i32>>,
+    xors: Vec<Vec<i32>>,
 }
 
 pub struct DimacsCnfBuilder(DimacsCnf);
@@ -16,29 +21,62 @@ impl DimacsCnfBuilder {
         DimacsCnfBuilder(DimacsCnf::new())
     }
 
-    fn parse_header(&mut self, header: &str) {
+    fn parse_header(&mut self, header: &str) -> Result<(), String> {
         let parts: Vec<&str> = header.split_whitespace().collect();
-        if parts.len() >= 4 && parts[0] == "p" && parts[1] == "cnf" {
-            self.0.num_vars = parts[2].parse().unwrap();
-            self.0.num_clauses = parts[3].parse().unwrap();
-        } else {
-            panic!("Invalid DIMACS CNF header");
+        let counts = match parts.as_slice() {
+            ["p", "cnf", num_vars, num_clauses, ..] => num_vars.parse().ok().zip(num_clauses.parse().ok()),
+            _ => None,
+        };
+        let (num_vars, num_clauses) = counts.ok_or_else(|| format!("invalid DIMACS CNF header: {}", header))?;
+        self.0.num_vars = num_vars;
+        self.0.num_clauses = num_clauses;
+        Ok(())
+    }
+
+    //A non-zero integer within the variables declared by the header, or 0 where allowed
+    fn parse_literal(&self, token: &str) -> Result<i32, String> {
+        match token.parse::<i32>() {
+            Ok(lit) if lit.unsigned_abs() as usize <= self.0.num_vars => Ok(lit),
+            _ => Err(format!("invalid literal: {}", token)),
         }
     }
 
-    fn parse_clause(&mut self, clause_line: &str) {
+    //A lone 0 is the empty clause, which makes the formula unsatisfiable
+    fn parse_clause(&mut self, clause_line: &str) -> Result<(), String> {
         let clause: Vec<i32> = clause_line
             .split_whitespace()
-            .flat_map(|s| s.parse::<i32>())
-            .collect();
-        if !clause.is_empty() {
-            self.0.clauses.push(clause);
+            .map(|s| self.parse_literal(s))
+            .collect::<Result<_, _>>()?;
+        self.0.clauses.push(clause);
+        Ok(())
+    }
+
+    fn parse_xor(&mut self, xor_line: &str) -> Result<(), String> {
+        let literals: Vec<i32> = xor_line
+            .split_whitespace()
+            .map(|s| self.parse_literal(s))
+            .collect::<Result<_, _>>()?;
+        match literals.split_last() {
+            Some((0, xor)) if !xor.contains(&0) => {
+                self.0.xors.push(xor.to_vec());
+                Ok(())
+            }
+            _ => Err(format!("XOR line must end with a single 0: x{}", xor_line)),
         }
     }
 
-    pub fn build(self) -> DimacsCnf {
-        assert_eq!(self.0.clauses.len(), self.0.num_clauses);
-        self.0
+    pub fn build(self) -> Result<DimacsCnf, String> {
+        let found = self.0.clauses.len() + self.0.xors.len();
+        if found != self.0.num_clauses {
+            return Err(format!("header declares {} clauses, found {}", self.0.num_clauses, found));
+        }
+        Ok(self.0)
+    }
+}
+
+impl Default for DimacsCnfBuilder {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -48,6 +86,7 @@ impl DimacsCnf {
             num_vars: 0,
             num_clauses: 0,
             clauses: Vec::new(),
+            xors: Vec::new(),
         }
     }
 
@@ -55,6 +94,10 @@ impl DimacsCnf {
         self.clauses.iter()
     }
 
+    pub fn xors(&self) -> impl Iterator<Item = &Vec<i32>> {
+        self.xors.iter()
+    }
+
     pub fn num_vars(&self) -> usize {
         self.num_vars
     }
@@ -64,54 +107,124 @@ impl DimacsCnf {
     }
 }
 
-pub fn parse_dimacs_cnf(file_path: &str) -> DimacsCnf {
-    let mut dimacs_cnf = DimacsCnfBuilder::new();
+//Literal of variable |lit|, named by its number as in CNF::from(DimacsCnf)
+pub(crate) fn dimacs_literal(lit: i32) -> SignedLiteral {
+    let variable = Literal::new(lit.unsigned_abs().to_string());
+    if lit > 0 {
+        variable.identity()
+    } else {
+        variable.not()
+    }
+}
+
+pub fn parse_dimacs_cnf(file_path: &str) -> Result<DimacsCnf, String> {
+    let file = File::open(file_path).map_err(|error| format!("{}: {}", file_path, error))?;
+    read_dimacs_cnf(io::BufReader::new(file)).map_err(|error| format!("{}: {}", file_path, error))
+}
 
-    if let Ok(file) = File::open(file_path) {
-        let reader = io::BufReader::new(file);
-        let mut current_clause_line = String::new();
-
-        for line in reader.lines() {
-            let line = line.unwrap();
-            let line = line.trim();
-
-            if line.is_empty() || line.starts_with("c") {
-                // Skip comments and empty lines
-                continue;
-            } else if line.starts_with("p cnf") {
-                // Parse header
-                dimacs_cnf.parse_header(line);
-            } else {
-                // Concatenate lines and split at each occurrence of 0
-                let parts = line.split_whitespace();
-                let mut is_first = true;
-
-                for part in parts {
-                    if part == "0" {
-                        // If 0 is encountered, parse the clause and reset the current_clause_line
-                        dimacs_cnf.parse_clause(&current_clause_line);
-                        current_clause_line.clear();
-                        is_first = true;
-                    } else if is_first {
-                        // If it's the first part, it might start with a negative sign
-                        current_clause_line.push_str(part);
-                        is_first = false;
-                    } else {
-                        // Otherwise, insert a space before adding the part to the current_clause_line
-                        current_clause_line.push(' ');
-                        current_clause_line.push_str(part);
-                    }
+//Errors on unreadable input, a malformed header, clause or XOR line, a literal outside the variables of
+//the header, or a clause count that does not match the header
+pub fn read_dimacs_cnf<R: BufRead>(reader: R) -> Result<DimacsCnf, String> {
+    let mut dimacs_cnf = DimacsCnfBuilder::new();
+    let mut current_clause_line = String::new();
+
+    for line in reader.lines() {
+        let line = line.map_err(|error| error.to_string())?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with("c") {
+            // Skip comments and empty lines
+            continue;
+        } else if line.starts_with('%') {
+            // SATLIB files end with a '%' line followed by a stray 0
+            break;
+        } else if line.starts_with('p') {
+            // Parse header
+            dimacs_cnf.parse_header(line)?;
+        } else if let Some(xor_line) = line.strip_prefix('x') {
+            // XOR constraints are always on a single line
+            dimacs_cnf.parse_xor(xor_line)?;
+        } else {
+            // Concatenate lines and split at each occurrence of 0
+            let parts = line.split_whitespace();
+            let mut is_first = true;
+
+            for part in parts {
+                if part == "0" {
+                    // If 0 is encountered, parse the clause and reset the current_clause_line
+                    dimacs_cnf.parse_clause(&current_clause_line)?;
+                    current_clause_line.clear();
+                    is_first = true;
+                } else if is_first {
+                    // If it's the first part, it might start with a negative sign
+                    current_clause_line.push_str(part);
+                    is_first = false;
+                } else {
+                    // Otherwise, insert a space before adding the part to the current_clause_line
+                    current_clause_line.push(' ');
+                    current_clause_line.push_str(part);
                 }
             }
         }
+    }
 
-        // Check if there's a remaining clause in current_clause_line
-        if !current_clause_line.is_empty() {
-            dimacs_cnf.parse_clause(&current_clause_line);
-        }
-    } else {
-        panic!("Failed to open file: {}", file_path);
+    // Check if there's a remaining clause in current_clause_line
+    if !current_clause_line.is_empty() {
+        dimacs_cnf.parse_clause(&current_clause_line)?;
     }
 
     dimacs_cnf.build()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_xor_lines() {
+        let dimacs = "c xor example\np cnf 3 3\n1 2 0\nx1 -2 3 0\nx 2 3 0\n";
+        let cnf = read_dimacs_cnf(dimacs.as_bytes()).unwrap();
+        assert_eq!(cnf.num_vars(), 3);
+        assert_eq!(cnf.clauses().collect::<Vec<_>>(), vec![&vec![1, 2]]);
+        assert_eq!(cnf.xors().collect::<Vec<_>>(), vec![&vec![1, -2, 3], &vec![2, 3]]);
+    }
+
+    #[test]
+    fn empty_clause() {
+        use crate::{
+            definitions::{Satisfiability, CNF},
+            sdpll::DPLLSolverBuilder,
+            SolverBuilder,
+        };
+        let cnf = read_dimacs_cnf("p cnf 1 2\n1 0\n0\n".as_bytes()).unwrap();
+        assert_eq!(cnf.clauses().collect::<Vec<_>>(), vec![&vec![1], &vec![]]);
+        assert_eq!(DPLLSolverBuilder::new().build(CNF::from(cnf)).solve(), Satisfiability::UNSAT);
+
+        //The stray 0 after the '%' line of SATLIB files is not one
+        let satlib = read_dimacs_cnf("p cnf 1 1\n1 0\n%\n0\n".as_bytes()).unwrap();
+        assert_eq!(satlib.clauses().count(), 1);
+    }
+
+    #[test]
+    fn malformed_input() {
+        let malformed: [&[u8]; 13] = [
+            b"p cnf x 1\n1 0\n",
+            b"p dnf 1 1\n1 0\n",
+            b"p cnf 2 2\n1 0\n",
+            b"p cnf 1 1\n1 0\n\xff\n",
+            b"p cnf 2 1\nx1 y 2 0\n",
+            b"p cnf 2 1\nx1 2\n",
+            b"p cnf 2 1\nx1 0 2 0\n",
+            b"p cnf 2 1\n1 foo 2 0\n",
+            b"p cnf 2 1\n1 -0x 0\n",
+            b"p cnf 2 1\n1 3 0\n",
+            b"p cnf 2 1\n-3 0\n",
+            b"p cnf 2 1\nx1 -3 0\n",
+            b"1 2 0\np cnf 2 1\n",
+        ];
+        for dimacs in malformed {
+            assert!(read_dimacs_cnf(dimacs).is_err(), "{:?}", dimacs);
+        }
+        assert!(parse_dimacs_cnf("benchmarks/missing.cnf").unwrap_err().starts_with("benchmarks/missing.cnf: "));
+    }
+}
@@ -0,0 +1,1022 @@
+//Distributed cube-and-conquer: the coordinator splits the formula with the lookahead cuber and hands the
+//cubes out to worker processes over TCP or Unix sockets, one cube at a time per worker
+//A worker that cannot be reached, disconnects or crashes loses its cube to the next idle worker, the
+//answer is Unknown if no worker is left while cubes remain
+//Protocol: frames of a 4-byte big-endian length followed by that many bytes of UTF-8 text. Literals are
+//written as their name, negated ones with a leading '-'. Whitespace, '%' and a leading '-' in names are
+//written as '%' and the two hex digits of each of their bytes
+//  coordinator -> worker: "formula <solver configuration>" followed by a "c <literals>" line per clause and
+//                         a "x <parity 0|1> <literals>" line per XOR, once per connection
+//                         "cube <job> <seed> <time limit in ms|-> <conflict limit|->" followed by a line
+//                         with the literals of the cube
+//                         "cancel" to give up on the current cube
+//  worker -> coordinator: "result <job> <SAT|UNSAT|UNKNOWN> <decisions> <propagations> <conflicts>" followed
+//                         by "model <literals>" on SAT or "core <xors 0|1> <clause indices>" on UNSAT, clauses
+//                         numbered in the order of the formula frame
+//                         "alive" every HEARTBEAT while a cube is solved
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+#[cfg(unix)]
+use std::{
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+};
+
+use log::debug;
+
+use crate::{
+    config::SolverConfig,
+    cube::Cuber,
+    definitions::{Assignments, ClauseRef, Literal, LiteralValue, Satisfiability, SignedLiteral, Xor, XorRef, CNF},
+    limits::Limits,
+    statistics::Statistics,
+    Solver, SolverBuilder,
+};
+
+//The formula frame is the largest, about the size of the DIMACS file
+const MAX_FRAME: usize = 256 << 20;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+//How long past the time limit of a cube the coordinator waits for its answer before it gives up on the worker
+const ANSWER_GRACE: Duration = Duration::from_secs(5);
+//Workers solving a cube send an alive frame this often, the coordinator gives up on a worker that sends
+//nothing for SILENCE, with or without a time limit
+const HEARTBEAT: Duration = Duration::from_secs(1);
+const SILENCE: Duration = Duration::from_secs(5);
+//Coordinators a worker serves at the same time, further connections are closed right away
+const MAX_COORDINATORS: usize = 64;
+//How often the coordinator checks its limits while cubes are out
+const POLL: Duration = Duration::from_millis(50);
+
+//Address of a worker, host:port or unix:<path>
+#[derive(Debug, Clone, PartialEq)]
+pub enum Endpoint {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        #[cfg(unix)]
+        if let Some(path) = address.strip_prefix("unix:") {
+            return Ok(Endpoint::Unix(PathBuf::from(path)));
+        }
+        match address.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(Endpoint::Tcp(address.to_string())),
+            _ => Err(format!("invalid worker address: {}", address)),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Endpoint::Tcp(address) => write!(f, "{}", address),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+enum Connection {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Connection {
+    fn connect(endpoint: &Endpoint) -> io::Result<Self> {
+        match endpoint {
+            Endpoint::Tcp(address) => {
+                let mut last = io::Error::new(io::ErrorKind::NotFound, "address did not resolve");
+                for address in address.to_socket_addrs()? {
+                    match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+                        Ok(stream) => {
+                            //Frames are small and answered one at a time
+                            stream.set_nodelay(true)?;
+                            return Ok(Connection::Tcp(stream));
+                        }
+                        Err(error) => last = error,
+                    }
+                }
+                Err(last)
+            }
+            #[cfg(unix)]
+            Endpoint::Unix(path) => UnixStream::connect(path).map(Connection::Unix),
+        }
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Connection::Tcp(stream) => stream.try_clone().map(Connection::Tcp),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.try_clone().map(Connection::Unix),
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Connection::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn write_frame<W: Write>(out: &mut W, frame: &str) -> io::Result<()> {
+    let mut bytes = (frame.len() as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(frame.as_bytes());
+    out.write_all(&bytes)?;
+    out.flush()
+}
+
+//None if the other side closed the connection between two frames
+fn read_frame<R: Read>(input: &mut R) -> io::Result<Option<String>> {
+    let mut length = [0; 4];
+    match input.read_exact(&mut length) {
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME {
+        return Err(invalid("frame too long"));
+    }
+    //Grows with the bytes received rather than the announced length
+    let mut frame = vec![];
+    input.take(length as u64).read_to_end(&mut frame)?;
+    if frame.len() < length {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated frame"));
+    }
+    String::from_utf8(frame).map(Some).map_err(|_| invalid("frame is not UTF-8"))
+}
+
+//Answer of a worker for one cube
+#[derive(Debug, Clone, PartialEq)]
+struct Conquered {
+    job: usize,
+    satisfiability: Satisfiability,
+    statistics: Statistics,
+    model: Option<Assignments>,
+    //Whether the core has XORs, and the indices of its clauses in the formula frame
+    core: Option<(bool, Vec<usize>)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Message {
+    Formula { config: SolverConfig, clauses: Vec<ClauseRef>, xors: Vec<XorRef> },
+    Cube { job: usize, seed: u64, time: Option<Duration>, conflicts: Option<u64>, cube: Vec<SignedLiteral> },
+    Cancel,
+    Result(Conquered),
+    Alive,
+}
+
+fn escape(name: &str) -> String {
+    let mut escaped = String::new();
+    for (i, c) in name.char_indices() {
+        if c.is_whitespace() || c == '%' || (i == 0 && c == '-') {
+            let mut bytes = [0; 4];
+            c.encode_utf8(&mut bytes).bytes().for_each(|b| escaped.push_str(&format!("%{:02X}", b)));
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+fn unescape(token: &str) -> io::Result<String> {
+    let mut bytes = vec![];
+    let mut rest = token.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+        if b != b'%' {
+            bytes.push(b);
+            continue;
+        }
+        let hex = rest.get(..2).and_then(|hex| std::str::from_utf8(hex).ok());
+        bytes.push(hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()).ok_or_else(|| invalid("malformed literal"))?);
+        rest = &rest[2..];
+    }
+    String::from_utf8(bytes).map_err(|_| invalid("malformed literal"))
+}
+
+fn literal(l: &SignedLiteral) -> String {
+    match l {
+        SignedLiteral::Id(v) => escape(v.name()),
+        SignedLiteral::Not(v) => format!("-{}", escape(v.name())),
+    }
+}
+
+fn literals<'a, I: IntoIterator<Item = &'a SignedLiteral>>(literals: I) -> String {
+    literals.into_iter().map(literal).collect::<Vec<_>>().join(" ")
+}
+
+fn parse_literal(token: &str) -> io::Result<SignedLiteral> {
+    match token.strip_prefix('-') {
+        Some(name) => Ok(Literal::new(unescape(name)?).not()),
+        None => Ok(Literal::new(unescape(token)?).identity()),
+    }
+}
+
+fn parse_field<T: FromStr>(token: Option<&str>) -> io::Result<T> {
+    token.and_then(|token| token.parse().ok()).ok_or_else(|| invalid("malformed message"))
+}
+
+//"-" for no limit
+fn parse_limit(token: Option<&str>) -> io::Result<Option<u64>> {
+    match token {
+        Some("-") => Ok(None),
+        token => parse_field(token).map(Some),
+    }
+}
+
+impl Message {
+    fn encode(&self) -> String {
+        let mut frame = String::new();
+        match self {
+            Message::Formula { config, clauses, xors } => {
+                frame.push_str(&format!("formula {}\n", config));
+                for clause in clauses {
+                    frame.push_str(&format!("c {}\n", literals(clause.signed_literal())));
+                }
+                for xor in xors {
+                    frame.push_str(&format!("x {} {}\n", xor.parity() as u8, literals(xor.signed_literal())));
+                }
+            }
+            Message::Cube { job, seed, time, conflicts, cube } => {
+                let time = time.map_or("-".to_string(), |time| time.as_millis().to_string());
+                let conflicts = conflicts.map_or("-".to_string(), |conflicts| conflicts.to_string());
+                frame.push_str(&format!("cube {} {} {} {}\n{}\n", job, seed, time, conflicts, literals(cube)));
+            }
+            Message::Cancel => frame.push_str("cancel\n"),
+            Message::Alive => frame.push_str("alive\n"),
+            Message::Result(conquered) => {
+                let satisfiability = match conquered.satisfiability {
+                    Satisfiability::SAT => "SAT",
+                    Satisfiability::UNSAT => "UNSAT",
+                    Satisfiability::Unknown => "UNKNOWN",
+                };
+                let statistics = &conquered.statistics;
+                frame.push_str(&format!(
+                    "result {} {} {} {} {}\n",
+                    conquered.job, satisfiability, statistics.decisions, statistics.propagations, statistics.conflicts
+                ));
+                if let Some(model) = &conquered.model {
+                    let model: Vec<SignedLiteral> = model
+                        .iter()
+                        .filter_map(|(v, value)| match value {
+                            LiteralValue::True => Some(v.identity()),
+                            LiteralValue::False => Some(v.not()),
+                            LiteralValue::Unassigned => None,
+                        })
+                        .collect();
+                    frame.push_str(&format!("model {}\n", literals(&model)));
+                }
+                if let Some((xors, clauses)) = &conquered.core {
+                    let clauses: Vec<String> = clauses.iter().map(|i| i.to_string()).collect();
+                    frame.push_str(&format!("core {} {}\n", *xors as u8, clauses.join(" ")));
+                }
+            }
+        }
+        frame
+    }
+
+    fn decode(frame: &str) -> io::Result<Message> {
+        let mut lines = frame.lines();
+        let mut header = lines.next().unwrap_or_default().split_whitespace();
+        match header.next() {
+            Some("formula") => {
+                let spec: String = parse_field(header.next())?;
+                let config = spec.parse().map_err(|error: String| invalid(&error))?;
+                let (mut clauses, mut xors) = (vec![], vec![]);
+                for line in lines {
+                    let mut tokens = line.split_whitespace();
+                    match tokens.next() {
+                        Some("c") => clauses.push(tokens.map(parse_literal).collect::<io::Result<_>>()?),
+                        Some("x") => {
+                            let parity: u8 = parse_field(tokens.next())?;
+                            let mut xor: Vec<SignedLiteral> = tokens.map(parse_literal).collect::<io::Result<_>>()?;
+                            //XORs are built true, a negated literal flips the parity. Empty XORs with a false
+                            //parity always hold
+                            match xor.first_mut() {
+                                Some(first) if parity == 0 => *first = first.complement(),
+                                None if parity == 0 => continue,
+                                _ => {}
+                            }
+                            xors.push(Xor::new(xor));
+                        }
+                        _ => return Err(invalid("malformed formula")),
+                    }
+                }
+                Ok(Message::Formula { config, clauses, xors })
+            }
+            Some("cube") => Ok(Message::Cube {
+                job: parse_field(header.next())?,
+                seed: parse_field(header.next())?,
+                time: parse_limit(header.next())?.map(Duration::from_millis),
+                conflicts: parse_limit(header.next())?,
+                cube: lines.next().unwrap_or_default().split_whitespace().map(parse_literal).collect::<io::Result<_>>()?,
+            }),
+            Some("cancel") => Ok(Message::Cancel),
+            Some("alive") => Ok(Message::Alive),
+            Some("result") => {
+                let job = parse_field(header.next())?;
+                let satisfiability = match header.next() {
+                    Some("SAT") => Satisfiability::SAT,
+                    Some("UNSAT") => Satisfiability::UNSAT,
+                    Some("UNKNOWN") => Satisfiability::Unknown,
+                    _ => return Err(invalid("malformed result")),
+                };
+                let statistics = Statistics {
+                    decisions: parse_field(header.next())?,
+                    propagations: parse_field(header.next())?,
+                    conflicts: parse_field(header.next())?,
+                    ..Statistics::default()
+                };
+                let mut conquered = Conquered { job, satisfiability, statistics, model: None, core: None };
+                for line in lines {
+                    let mut tokens = line.split_whitespace();
+                    match tokens.next() {
+                        Some("model") => {
+                            let mut model = Assignments::new();
+                            for token in tokens {
+                                model.satisfy(&parse_literal(token)?);
+                            }
+                            conquered.model = Some(model);
+                        }
+                        Some("core") => {
+                            let xors = parse_field::<u8>(tokens.next())? == 1;
+                            let clauses = tokens.map(|token| parse_field(Some(token))).collect::<io::Result<_>>()?;
+                            conquered.core = Some((xors, clauses));
+                        }
+                        _ => return Err(invalid("malformed result")),
+                    }
+                }
+                Ok(Message::Result(conquered))
+            }
+            _ => Err(invalid("unknown message")),
+        }
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+//Worker side: solves the cubes coordinators send with the configuration of their formula frame
+pub struct WorkerListener(Listener);
+
+impl WorkerListener {
+    pub fn bind(endpoint: &Endpoint) -> io::Result<Self> {
+        match endpoint {
+            Endpoint::Tcp(address) => TcpListener::bind(address).map(|l| WorkerListener(Listener::Tcp(l))),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => UnixListener::bind(path).map(|l| WorkerListener(Listener::Unix(l))),
+        }
+    }
+
+    //Address the listener is bound to, with the port the system chose for port 0
+    pub fn endpoint(&self) -> io::Result<Endpoint> {
+        match &self.0 {
+            Listener::Tcp(listener) => Ok(Endpoint::Tcp(listener.local_addr()?.to_string())),
+            #[cfg(unix)]
+            Listener::Unix(listener) => {
+                let address = listener.local_addr()?;
+                let path = address.as_pathname().ok_or_else(|| invalid("unnamed socket"))?;
+                Ok(Endpoint::Unix(path.to_path_buf()))
+            }
+        }
+    }
+
+    //Serves up to MAX_COORDINATORS coordinators, each in a thread of its own, until accepting a connection
+    //fails
+    pub fn serve(&self) -> io::Result<()> {
+        let served = Arc::new(AtomicUsize::new(0));
+        loop {
+            let connection = match &self.0 {
+                Listener::Tcp(listener) => {
+                    let (stream, _) = listener.accept()?;
+                    stream.set_nodelay(true)?;
+                    Connection::Tcp(stream)
+                }
+                #[cfg(unix)]
+                Listener::Unix(listener) => Connection::Unix(listener.accept()?.0),
+            };
+            //The coordinator sees a closed connection and hands the cubes to its other workers
+            if served.fetch_add(1, Ordering::SeqCst) >= MAX_COORDINATORS {
+                served.fetch_sub(1, Ordering::SeqCst);
+                debug!("Coordinator refused, {} connections open", MAX_COORDINATORS);
+                continue;
+            }
+            let served = served.clone();
+            thread::spawn(move || {
+                if let Err(error) = serve(connection) {
+                    debug!("Coordinator connection closed: {}", error);
+                }
+                served.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    }
+}
+
+//Cubes are solved in a thread of their own so that a cancel frame can reach them
+fn serve(connection: Connection) -> io::Result<()> {
+    let mut input = connection.try_clone()?;
+    let output = Arc::new(Mutex::new(connection));
+    let (config, cnf, indices) = match read_frame(&mut input)?.map(|frame| Message::decode(&frame)).transpose()? {
+        Some(Message::Formula { config, clauses, xors }) => {
+            let indices: HashMap<usize, usize> = clauses.iter().enumerate().map(|(i, c)| (c.id(), i)).collect();
+            let cnf = clauses.into_iter().fold(CNF::new(), |cnf, clause| cnf.add_clause(clause));
+            let cnf = xors.into_iter().fold(cnf, |cnf, xor| cnf.add_xor(xor));
+            (config, cnf, Arc::new(indices))
+        }
+        Some(_) => return Err(invalid("expected a formula")),
+        None => return Ok(()),
+    };
+
+    let terminate = Arc::new(AtomicBool::new(false));
+    let mut solving: Option<thread::JoinHandle<()>> = None;
+    //Set while a cube is solved, the result is written under the output lock after clearing it
+    let busy = Arc::new(AtomicBool::new(false));
+    let closed = Arc::new(AtomicBool::new(false));
+    let (heartbeat, beating, beating_output) = (closed.clone(), busy.clone(), output.clone());
+    thread::spawn(move || {
+        while !heartbeat.load(Ordering::Relaxed) {
+            thread::sleep(HEARTBEAT);
+            let mut output = beating_output.lock().unwrap();
+            if beating.load(Ordering::Relaxed) && write_frame(&mut *output, &Message::Alive.encode()).is_err() {
+                break;
+            }
+        }
+    });
+    let result = loop {
+        let message = match read_frame(&mut input) {
+            Ok(Some(frame)) => Message::decode(&frame),
+            Ok(None) => break Ok(()),
+            Err(error) => break Err(error),
+        };
+        match message {
+            Ok(Message::Cube { job, seed, time, conflicts, cube }) => {
+                //The coordinator waits for each answer before it sends the next cube
+                if let Some(handle) = solving.take() {
+                    let _ = handle.join();
+                }
+                terminate.store(false, Ordering::Relaxed);
+                let mut limits = Limits::new().terminate(terminate.clone());
+                if let Some(time) = time {
+                    limits = limits.time(time);
+                }
+                if let Some(conflicts) = conflicts {
+                    limits = limits.conflicts(conflicts);
+                }
+                let config = config.clone().limits(limits).seed(seed);
+                let (cnf, indices, output, busy) = (cnf.clone(), indices.clone(), output.clone(), busy.clone());
+                busy.store(true, Ordering::Relaxed);
+                solving = Some(thread::spawn(move || {
+                    debug!("Conquering cube {} of {} literals", job, cube.len());
+                    let cnf = cube.iter().fold(cnf, |cnf, l| cnf.add_clause([l.clone()].into_iter().collect()));
+                    let mut solver = config.build(cnf);
+                    let satisfiability = solver.solve();
+                    //Cube literals are not part of the formula frame and drop out of the core
+                    let core = solver.core().map(|core| {
+                        let clauses = core.clauses().filter_map(|c| indices.get(&c.id()).copied()).collect();
+                        (core.xors().next().is_some(), clauses)
+                    });
+                    let conquered =
+                        Conquered { job, satisfiability, statistics: solver.statistics(), model: solver.model(), core };
+                    let mut output = output.lock().unwrap();
+                    busy.store(false, Ordering::Relaxed);
+                    if let Err(error) = write_frame(&mut *output, &Message::Result(conquered).encode()) {
+                        debug!("Answer to cube {} lost: {}", job, error);
+                    }
+                }));
+            }
+            Ok(Message::Cancel) => terminate.store(true, Ordering::Relaxed),
+            Ok(_) => break Err(invalid("unexpected message")),
+            Err(error) => break Err(error),
+        }
+    };
+    terminate.store(true, Ordering::Relaxed);
+    if let Some(handle) = solving {
+        let _ = handle.join();
+    }
+    closed.store(true, Ordering::Relaxed);
+    result
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistributedSolverBuilder {
+    config: SolverConfig,
+    workers: Vec<Endpoint>,
+    cuber: Cuber,
+    limits: Limits,
+    seed: Option<u64>,
+}
+
+impl DistributedSolverBuilder {
+    //Cubes are conquered by the workers with solvers of this configuration, with the limits of the
+    //distributed solver
+    pub fn new(config: SolverConfig, workers: Vec<Endpoint>) -> Self {
+        DistributedSolverBuilder { config, workers, cuber: Cuber::new(), limits: Limits::new(), seed: None }
+    }
+
+    pub fn cuber(mut self, cuber: Cuber) -> Self {
+        self.cuber = cuber;
+        self
+    }
+
+    //Shared by all cubes: each one is sent with what is left when it is dispatched
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    //The solver of the n-th cube is seeded with seed + n, replacing the seed of the configuration.
+    //Drawn from entropy if not set
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+impl SolverBuilder for DistributedSolverBuilder {
+    fn build(self, formula: CNF) -> Box<dyn Solver> {
+        Box::new(DistributedSolver {
+            formula,
+            config: self.config,
+            workers: self.workers,
+            cuber: self.cuber,
+            limits: self.limits,
+            seed: self.seed.unwrap_or_else(rand::random),
+            model: None,
+            core: None,
+            statistics: Statistics::default(),
+        })
+    }
+}
+
+pub struct DistributedSolver {
+    formula: CNF,
+    config: SolverConfig,
+    workers: Vec<Endpoint>,
+    cuber: Cuber,
+    limits: Limits,
+    seed: u64,
+    model: Option<Assignments>,
+    core: Option<CNF>,
+    //Summed over all answered cubes, with the wall-clock time of cubing and conquering
+    statistics: Statistics,
+}
+
+struct Dispatch {
+    //Cubes not handed out yet, or handed back by a lost worker
+    queue: VecDeque<usize>,
+    running: usize,
+    //Workers connected or connecting
+    alive: usize,
+    stop: bool,
+    conflicts: u64,
+    results: Vec<Conquered>,
+}
+
+//State of one solve shared by the threads talking to the workers
+struct Coordinator<'a> {
+    solver: &'a DistributedSolver,
+    start: Instant,
+    cubes: Vec<&'a Vec<SignedLiteral>>,
+    formula: String,
+    dispatch: Mutex<Dispatch>,
+    changed: Condvar,
+    //Sending ends of the connections, frames are written whole under their lock
+    outputs: Mutex<Vec<Arc<Mutex<Connection>>>>,
+}
+
+impl Coordinator<'_> {
+    //Hands cubes to the worker until none are left or the search stops. The cube in current when this
+    //returns has not been answered
+    fn conquer(&self, endpoint: &Endpoint, current: &mut Option<usize>) -> io::Result<()> {
+        let connection = Connection::connect(endpoint)?;
+        let mut input = connection.try_clone()?;
+        let output = Arc::new(Mutex::new(connection));
+        self.outputs.lock().unwrap().push(output.clone());
+        write_frame(&mut *output.lock().unwrap(), &self.formula)?;
+
+        loop {
+            let (job, limits) = {
+                let mut state = self.dispatch.lock().unwrap();
+                //Cubes out at other workers may come back
+                while state.queue.is_empty() && state.running > 0 && !state.stop {
+                    state = self.changed.wait(state).unwrap();
+                }
+                if state.stop {
+                    return Ok(());
+                }
+                let Some(job) = state.queue.pop_front() else {
+                    return Ok(());
+                };
+                state.running += 1;
+                *current = Some(job);
+                (job, self.solver.limits.remaining(self.start.elapsed(), state.conflicts))
+            };
+            let cube = Message::Cube {
+                job,
+                seed: self.solver.seed.wrapping_add(job as u64),
+                time: limits.time_limit(),
+                conflicts: limits.conflict_limit(),
+                cube: self.cubes[job].clone(),
+            };
+            {
+                //Cancel frames are sent after stop is set, so a cube sent before it is cancelled too
+                let mut output = output.lock().unwrap();
+                if self.dispatch.lock().unwrap().stop {
+                    return Ok(());
+                }
+                write_frame(&mut *output, &cube.encode())?;
+            }
+
+            //A worker that falls silent or does not answer within the time limit of the cube is lost, like a
+            //crashed one
+            input.set_read_timeout(Some(SILENCE))?;
+            let deadline = limits.time_limit().map(|time| Instant::now() + time + ANSWER_GRACE);
+            let conquered = loop {
+                let Some(frame) = read_frame(&mut input)? else {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "worker closed the connection"));
+                };
+                match Message::decode(&frame)? {
+                    Message::Result(conquered) if conquered.job == job => break conquered,
+                    Message::Alive if deadline.is_some_and(|deadline| Instant::now() > deadline) => {
+                        return Err(io::Error::new(io::ErrorKind::TimedOut, "worker overran the time limit"));
+                    }
+                    Message::Alive => {}
+                    _ => return Err(invalid("unexpected message")),
+                }
+            };
+            let mut state = self.dispatch.lock().unwrap();
+            *current = None;
+            state.running -= 1;
+            state.conflicts += conquered.statistics.conflicts;
+            state.stop |= conquered.satisfiability == Satisfiability::SAT;
+            state.results.push(conquered);
+            self.changed.notify_all();
+        }
+    }
+
+    //Waits until every cube is answered, a model is found, no worker is left or the limits are exceeded,
+    //then cancels the cubes still out
+    fn watch(&self) {
+        let mut state = self.dispatch.lock().unwrap();
+        while !state.stop && state.alive > 0 && (state.running > 0 || !state.queue.is_empty()) {
+            if self.solver.limits.exceeded_at(self.start, state.conflicts) {
+                break;
+            }
+            state = self.changed.wait_timeout(state, POLL).unwrap().0;
+        }
+        state.stop = true;
+        self.changed.notify_all();
+        drop(state);
+        for output in self.outputs.lock().unwrap().iter() {
+            let _ = write_frame(&mut *output.lock().unwrap(), &Message::Cancel.encode());
+        }
+    }
+}
+
+impl Solver for DistributedSolver {
+    fn solve(&mut self) -> Satisfiability {
+        let start = Instant::now();
+        let cubes = self.cuber.cubes(&self.formula);
+        debug!("Distributed: {} cubes, {} refuted, {} workers", cubes.cubes.len(), cubes.refuted.len(), self.workers.len());
+
+        //Cubes refuted by the lookahead are solved too, their refutations are part of the core
+        let work: Vec<&Vec<SignedLiteral>> = cubes.cubes.iter().chain(cubes.refuted.iter()).collect();
+        let formula = Message::Formula {
+            config: self.config.clone(),
+            clauses: self.formula.clauses().cloned().collect(),
+            xors: self.formula.xors().cloned().collect(),
+        };
+        let coordinator = Coordinator {
+            solver: self,
+            start,
+            formula: formula.encode(),
+            dispatch: Mutex::new(Dispatch {
+                queue: (0..work.len()).collect(),
+                running: 0,
+                alive: self.workers.len(),
+                stop: false,
+                conflicts: 0,
+                results: vec![],
+            }),
+            cubes: work,
+            changed: Condvar::new(),
+            outputs: Mutex::new(vec![]),
+        };
+        thread::scope(|scope| {
+            for endpoint in self.workers.iter() {
+                let coordinator = &coordinator;
+                scope.spawn(move || {
+                    let mut current = None;
+                    if let Err(error) = coordinator.conquer(endpoint, &mut current) {
+                        debug!("Worker {} lost: {}", endpoint, error);
+                    }
+                    let mut state = coordinator.dispatch.lock().unwrap();
+                    if let Some(job) = current {
+                        state.queue.push_front(job);
+                        state.running -= 1;
+                    }
+                    state.alive -= 1;
+                    coordinator.changed.notify_all();
+                });
+            }
+            coordinator.watch();
+        });
+
+        let cubes = coordinator.cubes.len();
+        let results = coordinator.dispatch.into_inner().unwrap().results;
+        self.statistics = results.iter().fold(Statistics::default(), |total, result| Statistics {
+            decisions: total.decisions + result.statistics.decisions,
+            propagations: total.propagations + result.statistics.propagations,
+            conflicts: total.conflicts + result.statistics.conflicts,
+            ..total
+        });
+        self.statistics.elapsed = start.elapsed();
+        self.statistics.seed = Some(self.seed);
+        (self.model, self.core) = (None, None);
+        if let Some(sat) = results.iter().find(|r| r.satisfiability == Satisfiability::SAT) {
+            self.model = sat.model.clone();
+            return Satisfiability::SAT;
+        }
+        if results.len() < cubes || results.iter().any(|r| r.satisfiability != Satisfiability::UNSAT) {
+            return Satisfiability::Unknown;
+        }
+
+        //Union of the input clauses of the cube cores, without the cube literals
+        let cores: Option<Vec<(bool, Vec<usize>)>> = results.into_iter().map(|r| r.core).collect();
+        self.core = cores.map(|cores| {
+            let clauses: Vec<&ClauseRef> = self.formula.clauses().collect();
+            let ids = cores.iter().flat_map(|(_, indices)| indices.iter().filter_map(|i| clauses.get(*i).map(|c| c.id())));
+            self.formula.subset(&ids.collect(), cores.iter().any(|(xors, _)| *xors))
+        });
+        Satisfiability::UNSAT
+    }
+
+    fn model(&self) -> Option<Assignments> {
+        self.model.clone()
+    }
+
+    fn core(&self) -> Option<CNF> {
+        self.core.clone()
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.statistics.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::SolverKind, cube::Cutoff, dimacs::read_dimacs_cnf};
+
+    //Workers in threads of the test process, listening on ports chosen by the system
+    fn local_workers(workers: usize) -> Vec<Endpoint> {
+        (0..workers)
+            .map(|_| {
+                let listener = WorkerListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+                let endpoint = listener.endpoint().unwrap();
+                thread::spawn(move || listener.serve());
+                endpoint
+            })
+            .collect()
+    }
+
+    crate::tests::sat_tests!(DistributedSolverBuilder::new(SolverConfig::new(SolverKind::DPLL), local_workers(2))
+        .cuber(Cuber::new().cutoff(Cutoff::Depth(2))));
+
+    #[test]
+    fn messages_round_trip() {
+        let cnf = CNF::from(read_dimacs_cnf("p cnf 3 3\n1 -2 0\n-3 0\nx1 -2 3 0\n".as_bytes()).unwrap());
+        let formula = Message::Formula {
+            config: "pdpll:2:random".parse().unwrap(),
+            clauses: cnf.clauses().cloned().collect(),
+            xors: cnf.xors().cloned().collect(),
+        };
+        let mut model = Assignments::new();
+        model.satisfy(&Literal::new("1".to_string()).not());
+        //Names the text form would otherwise split or read as negated
+        model.satisfy(&Literal::new("-a b%".to_string()).identity());
+        let cube = vec![Literal::new("-2".to_string()).identity(), Literal::new("x\ty".to_string()).not()];
+        let messages = [
+            formula,
+            Message::Cube { job: 3, seed: 9, time: Some(Duration::from_millis(1500)), conflicts: None, cube: vec![] },
+            Message::Cube { job: 5, seed: 0, time: None, conflicts: Some(10), cube },
+            Message::Cancel,
+            Message::Alive,
+            Message::Result(Conquered {
+                job: 3,
+                satisfiability: Satisfiability::SAT,
+                statistics: Statistics { decisions: 4, propagations: 5, conflicts: 6, ..Statistics::default() },
+                model: Some(model),
+                core: None,
+            }),
+            Message::Result(Conquered {
+                job: 4,
+                satisfiability: Satisfiability::UNSAT,
+                statistics: Statistics::default(),
+                model: None,
+                core: Some((true, vec![0, 2])),
+            }),
+        ];
+        for message in messages {
+            let mut bytes = vec![];
+            write_frame(&mut bytes, &message.encode()).unwrap();
+            let frame = read_frame(&mut bytes.as_slice()).unwrap().unwrap();
+            assert_eq!(Message::decode(&frame).unwrap(), message);
+        }
+        assert_eq!(read_frame(&mut [].as_slice()).unwrap(), None);
+        assert!(Message::decode("cube 1 0 - -\n%2").is_err());
+        //Announced lengths are not allocated up front
+        let mut truncated = (MAX_FRAME as u32).to_be_bytes().to_vec();
+        truncated.extend_from_slice(b"cancel");
+        assert_eq!(read_frame(&mut truncated.as_slice()).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        let too_long = ((MAX_FRAME + 1) as u32).to_be_bytes();
+        assert!(read_frame(&mut too_long.as_slice()).is_err());
+    }
+
+    #[test]
+    fn reassigns_cubes_of_lost_workers() {
+        //Takes the formula and a cube, then drops the connection as a crashed worker would
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let crashing = Endpoint::Tcp(listener.local_addr().unwrap().to_string());
+        let took_cube = Arc::new(AtomicBool::new(false));
+        let took = took_cube.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut connection = Connection::Tcp(stream.unwrap());
+                let _ = read_frame(&mut connection);
+                if let Ok(Some(_)) = read_frame(&mut connection) {
+                    took.store(true, Ordering::Relaxed);
+                }
+            }
+        });
+
+        let mut workers = vec![crashing];
+        workers.extend(local_workers(1));
+        for (instance, expected) in [
+            (include_str!("../benchmarks/uf75-01.cnf"), Satisfiability::SAT),
+            (include_str!("../benchmarks/uuf50-01.cnf"), Satisfiability::UNSAT),
+        ] {
+            took_cube.store(false, Ordering::Relaxed);
+            let cnf = CNF::from(read_dimacs_cnf(instance.as_bytes()).unwrap());
+            let mut solver = DistributedSolverBuilder::new(SolverConfig::new(SolverKind::DPLL), workers.clone())
+                .cuber(Cuber::new().cutoff(Cutoff::Depth(3)))
+                .build(cnf.clone());
+            assert_eq!(solver.solve(), expected);
+            assert!(took_cube.load(Ordering::Relaxed));
+            match expected {
+                Satisfiability::SAT => assert_eq!(cnf.evaluate(&solver.model().unwrap()), CNFValue::SAT),
+                _ => {
+                    let core = solver.core().unwrap();
+                    assert_eq!(SolverConfig::new(SolverKind::DPLL).build(core).solve(), Satisfiability::UNSAT);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unresponsive_workers() {
+        //Takes the formula and the cubes but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let silent = Endpoint::Tcp(listener.local_addr().unwrap().to_string());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut connection = Connection::Tcp(stream.unwrap());
+                thread::spawn(move || while let Ok(Some(_)) = read_frame(&mut connection) {});
+            }
+        });
+
+        let cnf = CNF::from(read_dimacs_cnf(include_str!("../benchmarks/uf20-01.cnf").as_bytes()).unwrap());
+        let start = Instant::now();
+        let mut solver = DistributedSolverBuilder::new(SolverConfig::new(SolverKind::DPLL), vec![silent.clone()])
+            .limits(Limits::new().time(Duration::from_millis(100)))
+            .build(cnf);
+        assert_eq!(solver.solve(), Satisfiability::Unknown);
+        assert!(start.elapsed() < Duration::from_millis(100) + SILENCE * 2);
+
+        //Without a time limit the silent worker is given up once it misses its heartbeats, and its cube
+        //goes to the other worker
+        let cnf = CNF::from(read_dimacs_cnf(include_str!("../benchmarks/uuf50-01.cnf").as_bytes()).unwrap());
+        let mut workers = vec![silent];
+        workers.extend(local_workers(1));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            let mut solver = DistributedSolverBuilder::new(SolverConfig::new(SolverKind::DPLL), workers)
+                .cuber(Cuber::new().cutoff(Cutoff::Depth(2)))
+                .build(cnf);
+            let _ = sender.send(solver.solve());
+        });
+        assert_eq!(receiver.recv_timeout(SILENCE * 4), Ok(Satisfiability::UNSAT));
+    }
+
+    #[test]
+    fn heartbeats() {
+        //WalkSAT never finishes an unsatisfiable cube without limits
+        let cnf = CNF::from(read_dimacs_cnf(include_str!("../benchmarks/uuf50-01.cnf").as_bytes()).unwrap());
+        let formula = Message::Formula {
+            config: SolverConfig::new(SolverKind::WalkSAT),
+            clauses: cnf.clauses().cloned().collect(),
+            xors: vec![],
+        };
+        let mut connection = Connection::connect(&local_workers(1)[0]).unwrap();
+        connection.set_read_timeout(Some(HEARTBEAT * 3)).unwrap();
+        let mut output = connection.try_clone().unwrap();
+        write_frame(&mut output, &formula.encode()).unwrap();
+        let cube = Message::Cube { job: 0, seed: 1, time: None, conflicts: None, cube: vec![] };
+        write_frame(&mut output, &cube.encode()).unwrap();
+        let mut next = || Message::decode(&read_frame(&mut connection).unwrap().unwrap()).unwrap();
+        assert!(matches!(next(), Message::Alive));
+        assert!(matches!(next(), Message::Alive));
+        write_frame(&mut output, &Message::Cancel.encode()).unwrap();
+        let answer = loop {
+            match next() {
+                Message::Alive => {}
+                message => break message,
+            }
+        };
+        assert!(matches!(answer, Message::Result(Conquered { job: 0, satisfiability: Satisfiability::Unknown, .. })));
+    }
+
+    #[test]
+    fn unreachable_workers() {
+        //Bound and closed again, so nothing listens on the port
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let cnf = CNF::from(read_dimacs_cnf(include_str!("../benchmarks/uf20-01.cnf").as_bytes()).unwrap());
+        let workers = vec![Endpoint::Tcp(format!("127.0.0.1:{}", port))];
+        let mut solver = DistributedSolverBuilder::new(SolverConfig::new(SolverKind::DPLL), workers).build(cnf);
+        assert_eq!(solver.solve(), Satisfiability::Unknown);
+        assert_eq!(solver.model(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_workers() {
+        let path = std::env::temp_dir().join(format!("sat-solver-worker-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let endpoint: Endpoint = format!("unix:{}", path.display()).parse().unwrap();
+        let listener = WorkerListener::bind(&endpoint).unwrap();
+        assert_eq!(listener.endpoint().unwrap(), endpoint);
+        thread::spawn(move || listener.serve());
+
+        let cnf = CNF::from(read_dimacs_cnf(include_str!("../benchmarks/uf20-01.cnf").as_bytes()).unwrap());
+        let mut solver = DistributedSolverBuilder::new(SolverConfig::new(SolverKind::DPLL), vec![endpoint])
+            .cuber(Cuber::new().cutoff(Cutoff::Depth(2)))
+            .build(cnf.clone());
+        assert_eq!(solver.solve(), Satisfiability::SAT);
+        assert_eq!(cnf.evaluate(&solver.model().unwrap()), CNFValue::SAT);
+        let _ = std::fs::remove_file(&path);
+    }
+}
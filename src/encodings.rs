@@ -0,0 +1,540 @@
+//Cardinality constraint encodings
+//At-most-one: pairwise, sequential counter (Sinz 2005), commander (Klieber & Kwon 2007),
+//product (Chen 2010), bimander (Nguyen & Mai 2015)
+//At-most/at-least/exactly k: sequential counter, totalizer (Bailleux & Boufkhad 2003),
+//cardinality networks (Asín et al. 2011), odd-even merge sorting networks (Eén & Sörensson 2006)
+use crate::definitions::{LiteralPool, SignedLiteral, CNF};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtMostOneEncoding {
+    Pairwise,
+    SequentialCounter,
+    Commander,
+    Product,
+    Bimander,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardinalityEncoding {
+    SequentialCounter,
+    Totalizer,
+    CardinalityNetwork,
+    SortingNetwork,
+}
+
+//Which implications between inputs and unary outputs a counting structure must provide
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    //count >= j implies output j, required to bound from above
+    Up,
+    //output j implies count >= j, required to bound from below
+    Down,
+    Both,
+}
+
+impl Direction {
+    fn up(self) -> bool {
+        self != Direction::Down
+    }
+
+    fn down(self) -> bool {
+        self != Direction::Up
+    }
+}
+
+fn add(cnf: CNF, literals: Vec<SignedLiteral>) -> CNF {
+    cnf.add_clause(literals.into_iter().collect())
+}
+
+fn negate_all(literals: &[SignedLiteral]) -> Vec<SignedLiteral> {
+    literals.iter().map(|l| l.complement()).collect()
+}
+
+pub fn at_least_one(cnf: CNF, literals: &[SignedLiteral]) -> CNF {
+    add(cnf, literals.to_vec())
+}
+
+pub fn at_most_one(
+    cnf: CNF,
+    literals: &[SignedLiteral],
+    encoding: AtMostOneEncoding,
+    pool: &mut LiteralPool,
+) -> CNF {
+    if literals.len() <= 1 {
+        return cnf;
+    }
+    match encoding {
+        AtMostOneEncoding::Pairwise => pairwise(cnf, literals),
+        AtMostOneEncoding::SequentialCounter => sequential_counter(cnf, literals, 1, pool),
+        AtMostOneEncoding::Commander => commander(cnf, literals, pool),
+        AtMostOneEncoding::Product => product(cnf, literals, pool),
+        AtMostOneEncoding::Bimander => bimander(cnf, literals, pool),
+    }
+}
+
+pub fn exactly_one(
+    cnf: CNF,
+    literals: &[SignedLiteral],
+    encoding: AtMostOneEncoding,
+    pool: &mut LiteralPool,
+) -> CNF {
+    let cnf = at_least_one(cnf, literals);
+    at_most_one(cnf, literals, encoding, pool)
+}
+
+pub fn at_most(
+    cnf: CNF,
+    literals: &[SignedLiteral],
+    k: usize,
+    encoding: CardinalityEncoding,
+    pool: &mut LiteralPool,
+) -> CNF {
+    let n = literals.len();
+    if k >= n {
+        return cnf;
+    }
+    if k == 0 {
+        return negate_all(literals).into_iter().fold(cnf, |cnf, l| add(cnf, vec![l]));
+    }
+    match encoding {
+        CardinalityEncoding::SequentialCounter => sequential_counter(cnf, literals, k, pool),
+        _ => {
+            let (cnf, outputs) = counter(cnf, literals, k + 1, encoding, Direction::Up, pool);
+            add(cnf, vec![outputs[k].complement()])
+        }
+    }
+}
+
+pub fn at_least(
+    cnf: CNF,
+    literals: &[SignedLiteral],
+    k: usize,
+    encoding: CardinalityEncoding,
+    pool: &mut LiteralPool,
+) -> CNF {
+    let n = literals.len();
+    if k == 0 {
+        return cnf;
+    }
+    if k > n {
+        return add(cnf, vec![]);
+    }
+    if k == 1 {
+        return at_least_one(cnf, literals);
+    }
+    match encoding {
+        //At least k of x is at most n - k of ~x
+        CardinalityEncoding::SequentialCounter => at_most(cnf, &negate_all(literals), n - k, encoding, pool),
+        _ => {
+            let (cnf, outputs) = counter(cnf, literals, k, encoding, Direction::Down, pool);
+            add(cnf, vec![outputs[k - 1].clone()])
+        }
+    }
+}
+
+pub fn exactly(
+    cnf: CNF,
+    literals: &[SignedLiteral],
+    k: usize,
+    encoding: CardinalityEncoding,
+    pool: &mut LiteralPool,
+) -> CNF {
+    let n = literals.len();
+    match encoding {
+        CardinalityEncoding::SequentialCounter => {
+            let cnf = at_most(cnf, literals, k, encoding, pool);
+            at_least(cnf, literals, k, encoding, pool)
+        }
+        _ if k == 0 || k >= n => {
+            let cnf = at_most(cnf, literals, k, encoding, pool);
+            at_least(cnf, literals, k, encoding, pool)
+        }
+        _ => {
+            let (cnf, outputs) = counter(cnf, literals, k + 1, encoding, Direction::Both, pool);
+            let cnf = add(cnf, vec![outputs[k - 1].clone()]);
+            add(cnf, vec![outputs[k].complement()])
+        }
+    }
+}
+
+fn pairwise(mut cnf: CNF, literals: &[SignedLiteral]) -> CNF {
+    for (i, a) in literals.iter().enumerate() {
+        for b in literals[i + 1..].iter() {
+            cnf = add(cnf, vec![a.complement(), b.complement()]);
+        }
+    }
+    cnf
+}
+
+//At most k of x_1..x_n with registers s_i_j meaning "at least j of x_1..x_i are true"
+fn sequential_counter(mut cnf: CNF, x: &[SignedLiteral], k: usize, pool: &mut LiteralPool) -> CNF {
+    let n = x.len();
+    let s: Vec<Vec<SignedLiteral>> = (0..n - 1)
+        .map(|_| (0..k).map(|_| pool.fresh().identity()).collect())
+        .collect();
+
+    cnf = add(cnf, vec![x[0].complement(), s[0][0].clone()]);
+    for s in s[0].iter().skip(1) {
+        cnf = add(cnf, vec![s.complement()]);
+    }
+    for i in 1..n - 1 {
+        cnf = add(cnf, vec![x[i].complement(), s[i][0].clone()]);
+        cnf = add(cnf, vec![s[i - 1][0].complement(), s[i][0].clone()]);
+        for j in 1..k {
+            cnf = add(cnf, vec![x[i].complement(), s[i - 1][j - 1].complement(), s[i][j].clone()]);
+            cnf = add(cnf, vec![s[i - 1][j].complement(), s[i][j].clone()]);
+        }
+        cnf = add(cnf, vec![x[i].complement(), s[i - 1][k - 1].complement()]);
+    }
+    add(cnf, vec![x[n - 1].complement(), s[n - 2][k - 1].complement()])
+}
+
+fn commander(mut cnf: CNF, literals: &[SignedLiteral], pool: &mut LiteralPool) -> CNF {
+    const GROUP_SIZE: usize = 3;
+    if literals.len() <= GROUP_SIZE + 1 {
+        return pairwise(cnf, literals);
+    }
+
+    let mut commanders = vec![];
+    for group in literals.chunks(GROUP_SIZE) {
+        let c = pool.fresh().identity();
+        cnf = pairwise(cnf, group);
+        // c -> g_1 v ... v g_m
+        let mut clause = group.to_vec();
+        clause.push(c.complement());
+        cnf = add(cnf, clause);
+        // g_i -> c
+        for g in group {
+            cnf = add(cnf, vec![g.complement(), c.clone()]);
+        }
+        commanders.push(c);
+    }
+    commander(cnf, &commanders, pool)
+}
+
+fn product(mut cnf: CNF, literals: &[SignedLiteral], pool: &mut LiteralPool) -> CNF {
+    let n = literals.len();
+    if n <= 4 {
+        return pairwise(cnf, literals);
+    }
+
+    let p = (n as f64).sqrt().ceil() as usize;
+    let q = n.div_ceil(p);
+    let rows: Vec<SignedLiteral> = (0..p).map(|_| pool.fresh().identity()).collect();
+    let columns: Vec<SignedLiteral> = (0..q).map(|_| pool.fresh().identity()).collect();
+    for (i, x) in literals.iter().enumerate() {
+        cnf = add(cnf, vec![x.complement(), rows[i / q].clone()]);
+        cnf = add(cnf, vec![x.complement(), columns[i % q].clone()]);
+    }
+    let cnf = product(cnf, &rows, pool);
+    product(cnf, &columns, pool)
+}
+
+fn bimander(mut cnf: CNF, literals: &[SignedLiteral], pool: &mut LiteralPool) -> CNF {
+    const GROUP_SIZE: usize = 2;
+    let groups: Vec<&[SignedLiteral]> = literals.chunks(GROUP_SIZE).collect();
+    if groups.len() == 1 {
+        return pairwise(cnf, literals);
+    }
+
+    let width = usize::BITS - (groups.len() - 1).leading_zeros();
+    let bits: Vec<SignedLiteral> = (0..width).map(|_| pool.fresh().identity()).collect();
+    for (i, group) in groups.into_iter().enumerate() {
+        cnf = pairwise(cnf, group);
+        for x in group {
+            for (j, b) in bits.iter().enumerate() {
+                let b = if i & (1 << j) != 0 { b.clone() } else { b.complement() };
+                cnf = add(cnf, vec![x.complement(), b]);
+            }
+        }
+    }
+    cnf
+}
+
+//Unary counter over literals; outputs[j] is the (j+1)-th largest input. Only the first `size` outputs are returned
+fn counter(
+    cnf: CNF,
+    literals: &[SignedLiteral],
+    size: usize,
+    encoding: CardinalityEncoding,
+    direction: Direction,
+    pool: &mut LiteralPool,
+) -> (CNF, Vec<SignedLiteral>) {
+    match encoding {
+        CardinalityEncoding::Totalizer => totalizer(cnf, literals, size, direction, pool),
+        CardinalityEncoding::CardinalityNetwork => {
+            let mut network = Network { cnf, direction, pool };
+            let outputs = network.cardinality(literals, size);
+            (network.cnf, outputs)
+        }
+        CardinalityEncoding::SortingNetwork => {
+            let mut network = Network { cnf, direction, pool };
+            let outputs = network.sort(literals);
+            (network.cnf, outputs.into_iter().take(size).collect())
+        }
+        CardinalityEncoding::SequentialCounter => unreachable!("sequential counter has no unary outputs"),
+    }
+}
+
+//Totalizer with outputs truncated at `cap`
+fn totalizer(
+    cnf: CNF,
+    literals: &[SignedLiteral],
+    cap: usize,
+    direction: Direction,
+    pool: &mut LiteralPool,
+) -> (CNF, Vec<SignedLiteral>) {
+    if literals.len() == 1 {
+        return (cnf, literals.to_vec());
+    }
+
+    let (left, right) = literals.split_at(literals.len() / 2);
+    let (cnf, a) = totalizer(cnf, left, cap, direction, pool);
+    let (mut cnf, b) = totalizer(cnf, right, cap, direction, pool);
+    let m = (a.len() + b.len()).min(cap);
+    let o: Vec<SignedLiteral> = (0..m).map(|_| pool.fresh().identity()).collect();
+
+    for i in 0..=a.len() {
+        for j in 0..=b.len() {
+            if direction.up() && i + j > 0 {
+                // a_i ^ b_j -> o_(i+j)
+                let mut clause = vec![o[(i + j).min(m) - 1].clone()];
+                if i > 0 {
+                    clause.push(a[i - 1].complement());
+                }
+                if j > 0 {
+                    clause.push(b[j - 1].complement());
+                }
+                cnf = add(cnf, clause);
+            }
+            if direction.down() && i + j < m {
+                // o_(i+j+1) -> a_(i+1) v b_(j+1)
+                let mut clause = vec![o[i + j].complement()];
+                if i < a.len() {
+                    clause.push(a[i].clone());
+                }
+                if j < b.len() {
+                    clause.push(b[j].clone());
+                }
+                cnf = add(cnf, clause);
+            }
+        }
+    }
+    (cnf, o)
+}
+
+struct Network<'a> {
+    cnf: CNF,
+    direction: Direction,
+    pool: &'a mut LiteralPool,
+}
+
+impl<'a> Network<'a> {
+    fn emit(&mut self, literals: Vec<SignedLiteral>) {
+        let cnf = std::mem::take(&mut self.cnf);
+        self.cnf = add(cnf, literals);
+    }
+
+    fn constant_false(&mut self) -> SignedLiteral {
+        let l = self.pool.fresh().identity();
+        self.emit(vec![l.complement()]);
+        l
+    }
+
+    //Pad with false inputs up to a length of `len`
+    fn pad(&mut self, literals: &[SignedLiteral], len: usize) -> Vec<SignedLiteral> {
+        let mut padded = literals.to_vec();
+        while padded.len() < len {
+            let f = self.constant_false();
+            padded.push(f);
+        }
+        padded
+    }
+
+    //2-comparator: (max, min) of a and b
+    fn comparator(&mut self, a: &SignedLiteral, b: &SignedLiteral) -> (SignedLiteral, SignedLiteral) {
+        let c = self.pool.fresh().identity();
+        let d = self.pool.fresh().identity();
+        if self.direction.up() {
+            self.emit(vec![a.complement(), c.clone()]);
+            self.emit(vec![b.complement(), c.clone()]);
+            self.emit(vec![a.complement(), b.complement(), d.clone()]);
+        }
+        if self.direction.down() {
+            self.emit(vec![c.complement(), a.clone(), b.clone()]);
+            self.emit(vec![d.complement(), a.clone()]);
+            self.emit(vec![d.complement(), b.clone()]);
+        }
+        (c, d)
+    }
+
+    fn odd(literals: &[SignedLiteral]) -> Vec<SignedLiteral> {
+        literals.iter().step_by(2).cloned().collect()
+    }
+
+    fn even(literals: &[SignedLiteral]) -> Vec<SignedLiteral> {
+        literals.iter().skip(1).step_by(2).cloned().collect()
+    }
+
+    //Odd-even merge of two sorted sequences of equal power-of-two length
+    fn merge(&mut self, a: &[SignedLiteral], b: &[SignedLiteral]) -> Vec<SignedLiteral> {
+        if a.len() == 1 {
+            let (c, d) = self.comparator(&a[0], &b[0]);
+            return vec![c, d];
+        }
+
+        let d = self.merge(&Self::odd(a), &Self::odd(b));
+        let e = self.merge(&Self::even(a), &Self::even(b));
+        let n = a.len();
+        let mut c = vec![d[0].clone()];
+        for i in 0..n - 1 {
+            let (max, min) = self.comparator(&d[i + 1], &e[i]);
+            c.push(max);
+            c.push(min);
+        }
+        c.push(e[n - 1].clone());
+        c
+    }
+
+    //Simplified merge: first n + 1 outputs of merging two sorted sequences of length n
+    fn simplified_merge(&mut self, a: &[SignedLiteral], b: &[SignedLiteral]) -> Vec<SignedLiteral> {
+        if a.len() == 1 {
+            let (c, d) = self.comparator(&a[0], &b[0]);
+            return vec![c, d];
+        }
+
+        let d = self.simplified_merge(&Self::odd(a), &Self::odd(b));
+        let e = self.simplified_merge(&Self::even(a), &Self::even(b));
+        let half = a.len() / 2;
+        let mut c = vec![d[0].clone()];
+        for i in 0..half {
+            let (max, min) = self.comparator(&d[i + 1], &e[i]);
+            c.push(max);
+            c.push(min);
+        }
+        c
+    }
+
+    fn sort(&mut self, literals: &[SignedLiteral]) -> Vec<SignedLiteral> {
+        let padded = self.pad(literals, literals.len().next_power_of_two());
+        let sorted = self.sort_power_of_two(&padded);
+        sorted.into_iter().take(literals.len()).collect()
+    }
+
+    fn sort_power_of_two(&mut self, literals: &[SignedLiteral]) -> Vec<SignedLiteral> {
+        if literals.len() == 1 {
+            return literals.to_vec();
+        }
+        let (left, right) = literals.split_at(literals.len() / 2);
+        let a = self.sort_power_of_two(left);
+        let b = self.sort_power_of_two(right);
+        self.merge(&a, &b)
+    }
+
+    //Cardinality network: the `size` largest inputs in sorted order
+    fn cardinality(&mut self, literals: &[SignedLiteral], size: usize) -> Vec<SignedLiteral> {
+        let k = size.next_power_of_two();
+        let n = literals.len().div_ceil(k) * k;
+        let padded = self.pad(literals, n);
+        let outputs = self.cardinality_blocks(&padded, k);
+        outputs.into_iter().take(size.min(literals.len())).collect()
+    }
+
+    fn cardinality_blocks(&mut self, literals: &[SignedLiteral], k: usize) -> Vec<SignedLiteral> {
+        if literals.len() == k {
+            return self.sort_power_of_two(literals);
+        }
+        let (left, right) = literals.split_at(k);
+        let a = self.sort_power_of_two(left);
+        let b = self.cardinality_blocks(right, k);
+        let mut c = self.simplified_merge(&a, &b);
+        c.truncate(k);
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        definitions::{Literal, RefLiteral, Satisfiability},
+        sdpll::DPLLSolverBuilder,
+        SolverBuilder,
+    };
+
+    const AMO_ENCODINGS: [AtMostOneEncoding; 5] = [
+        AtMostOneEncoding::Pairwise,
+        AtMostOneEncoding::SequentialCounter,
+        AtMostOneEncoding::Commander,
+        AtMostOneEncoding::Product,
+        AtMostOneEncoding::Bimander,
+    ];
+
+    const CARDINALITY_ENCODINGS: [CardinalityEncoding; 4] = [
+        CardinalityEncoding::SequentialCounter,
+        CardinalityEncoding::Totalizer,
+        CardinalityEncoding::CardinalityNetwork,
+        CardinalityEncoding::SortingNetwork,
+    ];
+
+    fn inputs(n: usize) -> Vec<RefLiteral> {
+        (0..n).map(|i| Literal::new(format!("x{}", i))).collect()
+    }
+
+    //For every assignment to the inputs, the encoding must be SAT iff the number of true inputs is allowed
+    fn check<F, E>(n: usize, encode: E, allowed: F)
+    where
+        F: Fn(usize) -> bool,
+        E: Fn(CNF, &[SignedLiteral], &mut LiteralPool) -> CNF,
+    {
+        let x = inputs(n);
+        let literals: Vec<SignedLiteral> = x.iter().map(|l| l.identity()).collect();
+        for bits in 0..(1usize << n) {
+            let mut pool = LiteralPool::default();
+            let mut cnf = encode(CNF::new(), &literals, &mut pool);
+            for (i, l) in x.iter().enumerate() {
+                let l = if bits & (1 << i) != 0 { l.identity() } else { l.not() };
+                cnf = cnf.add_clause([l].into_iter().collect());
+            }
+            let expected = if allowed(bits.count_ones() as usize) {
+                Satisfiability::SAT
+            } else {
+                Satisfiability::UNSAT
+            };
+            assert_eq!(DPLLSolverBuilder::new().build(cnf).solve(), expected, "n={} bits={:b}", n, bits);
+        }
+    }
+
+    #[test]
+    fn at_most_one_encodings() {
+        for encoding in AMO_ENCODINGS {
+            for n in [2, 5, 7] {
+                check(n, |cnf, x, pool| at_most_one(cnf, x, encoding, pool), |count| count <= 1);
+                check(n, |cnf, x, pool| exactly_one(cnf, x, encoding, pool), |count| count == 1);
+            }
+        }
+    }
+
+    #[test]
+    fn cardinality_encodings() {
+        for encoding in CARDINALITY_ENCODINGS {
+            for (n, k) in [(4, 0), (4, 1), (5, 2), (6, 3), (5, 5)] {
+                check(n, |cnf, x, pool| at_most(cnf, x, k, encoding, pool), |count| count <= k);
+                check(n, |cnf, x, pool| at_least(cnf, x, k, encoding, pool), |count| count >= k);
+                check(n, |cnf, x, pool| exactly(cnf, x, k, encoding, pool), |count| count == k);
+            }
+        }
+    }
+
+    #[test]
+    fn at_most_one_clause_counts() {
+        let literals: Vec<SignedLiteral> = inputs(30).iter().map(|l| l.identity()).collect();
+        let count = |encoding| {
+            at_most_one(CNF::new(), &literals, encoding, &mut LiteralPool::default()).clauses().count()
+        };
+        let pairwise = count(AtMostOneEncoding::Pairwise);
+        for encoding in AMO_ENCODINGS.into_iter().skip(1) {
+            assert!(count(encoding) < pairwise, "{:?}", encoding);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//All-solutions enumeration with blocking clauses, optionally projected onto a set of variables
+//Solvers return partial models whose every completion is a model, so each solve covers a cube of
+//models: its projection is expanded over the unassigned projected variables and then blocked as a whole
+use std::collections::HashSet;
+
+use crate::{
+    definitions::{Assignments, LiteralValue, RefLiteral, Satisfiability, SignedLiteral, CNF},
+    SolverBuilder,
+};
+
+pub struct Enumerator<B> {
+    builder: B,
+    projection: Option<Vec<RefLiteral>>,
+    limit: Option<usize>,
+}
+
+impl<B: SolverBuilder + Clone> Enumerator<B> {
+    pub fn new(builder: B) -> Self {
+        Enumerator { builder, projection: None, limit: None }
+    }
+
+    //Enumerate the distinct assignments of these variables that extend to a model, instead of all
+    //assignments of the formula's variables
+    pub fn projection<I: IntoIterator<Item = RefLiteral>>(mut self, variables: I) -> Self {
+        self.projection = Some(variables.into_iter().collect());
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn models(self, formula: CNF) -> Models<B> {
+        let variables = match self.projection {
+            Some(variables) => {
+                let mut seen = HashSet::new();
+                variables.into_iter().filter(|v| seen.insert(v.clone())).collect()
+            }
+            None => {
+                let mut seen = HashSet::new();
+                formula.iter_literals().map(|l| l.literal()).filter(|v| seen.insert(v.clone())).collect()
+            }
+        };
+        Models {
+            builder: self.builder,
+            formula,
+            variables,
+            limit: self.limit,
+            found: 0,
+            cube: None,
+            exhausted: false,
+        }
+    }
+}
+
+//Models of the current cube: the assigned projected variables and a counter over the unassigned ones
+struct Cube {
+    assigned: Assignments,
+    free: Vec<RefLiteral>,
+    next: Option<Vec<bool>>,
+}
+
+impl Cube {
+    fn next(&mut self) -> Option<Assignments> {
+        let values = self.next.as_mut()?;
+        let mut model = self.assigned.clone();
+        for (variable, value) in self.free.iter().zip(values.iter()) {
+            model.assign(variable.clone(), if *value { LiteralValue::True } else { LiteralValue::False });
+        }
+
+        //Binary increment, done once every value has wrapped around to false
+        match values.iter().position(|value| !value) {
+            Some(i) => {
+                values[..i].iter_mut().for_each(|value| *value = false);
+                values[i] = true;
+            }
+            None => self.next = None,
+        }
+        Some(model)
+    }
+}
+
+pub struct Models<B> {
+    builder: B,
+    //Input formula and the blocking clauses added so far
+    formula: CNF,
+    variables: Vec<RefLiteral>,
+    limit: Option<usize>,
+    found: usize,
+    cube: Option<Cube>,
+    exhausted: bool,
+}
+
+impl<B: SolverBuilder + Clone> Models<B> {
+    //True once every model has been enumerated, false if the limit or a solver limit ended the iteration
+    pub fn exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    fn next_cube(&mut self) -> Option<Cube> {
+        let mut solver = self.builder.clone().build(self.formula.clone());
+        match solver.solve() {
+            Satisfiability::SAT => {}
+            satisfiability => {
+                self.exhausted = satisfiability == Satisfiability::UNSAT;
+                return None;
+            }
+        }
+        let model = solver.model().unwrap_or_default();
+
+        let mut assigned = Assignments::new();
+        let mut free = vec![];
+        let mut blocking: Vec<SignedLiteral> = vec![];
+        for variable in self.variables.iter() {
+            match model.get(variable) {
+                LiteralValue::Unassigned => free.push(variable.clone()),
+                value => {
+                    assigned.assign(variable.clone(), value);
+                    blocking.push(if value == LiteralValue::True { variable.not() } else { variable.identity() });
+                }
+            }
+        }
+        //An empty blocking clause makes the formula UNSAT once every model of this cube is enumerated
+        self.formula = self.formula.clone().add_clause(blocking.into_iter().collect());
+        let next = Some(vec![false; free.len()]);
+        Some(Cube { assigned, free, next })
+    }
+}
+
+impl<B: SolverBuilder + Clone> Iterator for Models<B> {
+    type Item = Assignments;
+
+    fn next(&mut self) -> Option<Assignments> {
+        if self.limit.is_some_and(|limit| self.found >= limit) {
+            return None;
+        }
+        loop {
+            if let Some(model) = self.cube.as_mut().and_then(|cube| cube.next()) {
+                self.found += 1;
+                return Some(model);
+            }
+            if self.exhausted {
+                return None;
+            }
+            self.cube = Some(self.next_cube()?);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        definitions::{CNFValue, Literal},
+        dimacs::read_dimacs_cnf,
+        sdpll::DPLLSolverBuilder,
+    };
+
+    fn key(model: &Assignments, variables: &[&str]) -> Vec<bool> {
+        variables
+            .iter()
+            .map(|v| model.get(&Literal::new(v.to_string())) == LiteralValue::True)
+            .collect()
+    }
+
+    #[test]
+    fn all_models() {
+        //(1 v 2) ^ (-1 v 3) has 4 models over 1..3
+        let cnf = CNF::from(read_dimacs_cnf("p cnf 3 2\n1 2 0\n-1 3 0\n".as_bytes()).unwrap());
+        let mut models = Enumerator::new(DPLLSolverBuilder::new()).models(cnf.clone());
+        let found: Vec<Assignments> = models.by_ref().collect();
+        assert!(models.exhausted());
+        assert_eq!(found.len(), 4);
+        let distinct: HashSet<Vec<bool>> = found.iter().map(|m| key(m, &["1", "2", "3"])).collect();
+        assert_eq!(distinct.len(), 4);
+        for model in found.iter() {
+            assert_eq!(model.len(), 3);
+            assert_eq!(cnf.clone().evaluate(model), CNFValue::SAT);
+        }
+
+        let mut limited = Enumerator::new(DPLLSolverBuilder::new()).limit(3).models(cnf);
+        assert_eq!(limited.by_ref().count(), 3);
+        assert!(!limited.exhausted());
+    }
+
+    #[test]
+    fn projected_models() {
+        //Projected onto 1 and 4: 4 is free and 1 can take both values
+        let cnf = CNF::from(read_dimacs_cnf("p cnf 3 2\n1 2 0\n-1 3 0\n".as_bytes()).unwrap());
+        let projection = ["1", "4"].map(|v| Literal::new(v.to_string()));
+        let found: Vec<Assignments> = Enumerator::new(DPLLSolverBuilder::new())
+            .projection(projection)
+            .models(cnf)
+            .collect();
+        let distinct: HashSet<Vec<bool>> = found.iter().map(|m| key(m, &["1", "4"])).collect();
+        assert_eq!((found.len(), distinct.len()), (4, 4));
+        assert!(found.iter().all(|m| m.len() == 2));
+
+        let unsat = CNF::from(read_dimacs_cnf("p cnf 1 2\n1 0\n-1 0\n".as_bytes()).unwrap());
+        let mut models = Enumerator::new(DPLLSolverBuilder::new()).models(unsat);
+        assert_eq!(models.next(), None);
+        assert!(models.exhausted());
+    }
+}
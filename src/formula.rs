@@ -0,0 +1,537 @@
+//Propositional formulas over RefLiterals and their conversion to CNF
+//Tseitin: https://en.wikipedia.org/wiki/Tseytin_transformation
+//Plaisted-Greenbaum: only the implications required by the polarity of a subformula are emitted
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use crate::definitions::{
+    Assignments, LiteralPool, LiteralValue, RefLiteral, SignedLiteral, CNF,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Formula {
+    Const(bool),
+    Var(RefLiteral),
+    Not(FormulaRef),
+    And(Vec<FormulaRef>),
+    Or(Vec<FormulaRef>),
+    Implies(FormulaRef, FormulaRef),
+    Iff(FormulaRef, FormulaRef),
+    Xor(FormulaRef, FormulaRef),
+    Ite(FormulaRef, FormulaRef, FormulaRef),
+}
+
+#[derive(Debug)]
+struct Node {
+    id: usize,
+    formula: Formula,
+}
+
+//Structurally hashed node: two FormulaRefs from the same FormulaBuilder are equal iff they are the same node
+#[derive(Debug, Clone)]
+pub struct FormulaRef(Arc<Node>);
+
+impl PartialEq for FormulaRef {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id == other.0.id
+    }
+}
+
+impl Eq for FormulaRef {}
+
+impl Hash for FormulaRef {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.id.hash(state);
+    }
+}
+
+impl FormulaRef {
+    pub fn formula(&self) -> &Formula {
+        &self.0.formula
+    }
+
+    pub fn id(&self) -> usize {
+        self.0.id
+    }
+
+    pub fn evaluate(&self, assignments: &Assignments) -> LiteralValue {
+        let and = |values: &mut dyn Iterator<Item = LiteralValue>| {
+            values.fold(LiteralValue::True, |acc, v| match (acc, v) {
+                (LiteralValue::False, _) | (_, LiteralValue::False) => LiteralValue::False,
+                (LiteralValue::True, LiteralValue::True) => LiteralValue::True,
+                _ => LiteralValue::Unassigned,
+            })
+        };
+        let xor = |a: LiteralValue, b: LiteralValue| match (a, b) {
+            (LiteralValue::Unassigned, _) | (_, LiteralValue::Unassigned) => LiteralValue::Unassigned,
+            (a, b) if a == b => LiteralValue::False,
+            _ => LiteralValue::True,
+        };
+
+        match self.formula() {
+            Formula::Const(true) => LiteralValue::True,
+            Formula::Const(false) => LiteralValue::False,
+            Formula::Var(l) => l.identity().evaluate(assignments),
+            Formula::Not(a) => a.evaluate(assignments).negate(),
+            Formula::And(children) => and(&mut children.iter().map(|c| c.evaluate(assignments))),
+            Formula::Or(children) => and(&mut children.iter().map(|c| c.evaluate(assignments).negate())).negate(),
+            Formula::Implies(a, b) => and(&mut [a.evaluate(assignments), b.evaluate(assignments).negate()].into_iter()).negate(),
+            Formula::Iff(a, b) => xor(a.evaluate(assignments), b.evaluate(assignments)).negate(),
+            Formula::Xor(a, b) => xor(a.evaluate(assignments), b.evaluate(assignments)),
+            Formula::Ite(c, t, e) => match c.evaluate(assignments) {
+                LiteralValue::True => t.evaluate(assignments),
+                LiteralValue::False => e.evaluate(assignments),
+                LiteralValue::Unassigned => {
+                    let (t, e) = (t.evaluate(assignments), e.evaluate(assignments));
+                    if t == e { t } else { LiteralValue::Unassigned }
+                }
+            },
+        }
+    }
+}
+
+//Hash-consing factory for formulas with light constant folding and normalization of commutative operators
+pub struct FormulaBuilder {
+    table: RefCell<HashMap<Formula, FormulaRef>>,
+}
+
+impl FormulaBuilder {
+    pub fn new() -> FormulaBuilder {
+        FormulaBuilder { table: RefCell::new(HashMap::new()) }
+    }
+
+    fn intern(&self, formula: Formula) -> FormulaRef {
+        let mut table = self.table.borrow_mut();
+        let id = table.len();
+        table
+            .entry(formula.clone())
+            .or_insert_with(|| FormulaRef(Arc::new(Node { id, formula })))
+            .clone()
+    }
+
+    //Number of distinct nodes created so far
+    pub fn len(&self) -> usize {
+        self.table.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.borrow().is_empty()
+    }
+
+    pub fn constant(&self, value: bool) -> FormulaRef {
+        self.intern(Formula::Const(value))
+    }
+
+    pub fn var(&self, literal: RefLiteral) -> FormulaRef {
+        self.intern(Formula::Var(literal))
+    }
+
+    pub fn literal(&self, literal: SignedLiteral) -> FormulaRef {
+        match literal {
+            SignedLiteral::Id(l) => self.var(l),
+            SignedLiteral::Not(l) => self.not(self.var(l)),
+        }
+    }
+
+    pub fn not(&self, a: FormulaRef) -> FormulaRef {
+        match a.formula() {
+            Formula::Const(value) => self.constant(!value),
+            Formula::Not(inner) => inner.clone(),
+            _ => self.intern(Formula::Not(a)),
+        }
+    }
+
+    fn is_complement(a: &FormulaRef, b: &FormulaRef) -> bool {
+        matches!(a.formula(), Formula::Not(inner) if inner == b)
+            || matches!(b.formula(), Formula::Not(inner) if inner == a)
+    }
+
+    //Shared normalization of And (dominant = false) and Or (dominant = true)
+    fn junction(&self, children: Vec<FormulaRef>, dominant: bool) -> FormulaRef {
+        let mut flat: Vec<FormulaRef> = vec![];
+        for child in children {
+            match child.formula() {
+                Formula::Const(value) if *value == dominant => return self.constant(dominant),
+                Formula::Const(_) => {}
+                Formula::And(inner) if !dominant => flat.extend(inner.iter().cloned()),
+                Formula::Or(inner) if dominant => flat.extend(inner.iter().cloned()),
+                _ => flat.push(child),
+            }
+        }
+        flat.sort_by_key(|f| f.id());
+        flat.dedup();
+        for (i, a) in flat.iter().enumerate() {
+            if flat[i + 1..].iter().any(|b| Self::is_complement(a, b)) {
+                return self.constant(dominant);
+            }
+        }
+
+        match flat.len() {
+            0 => self.constant(!dominant),
+            1 => flat.pop().unwrap(),
+            _ if dominant => self.intern(Formula::Or(flat)),
+            _ => self.intern(Formula::And(flat)),
+        }
+    }
+
+    pub fn and(&self, children: Vec<FormulaRef>) -> FormulaRef {
+        self.junction(children, false)
+    }
+
+    pub fn or(&self, children: Vec<FormulaRef>) -> FormulaRef {
+        self.junction(children, true)
+    }
+
+    pub fn implies(&self, a: FormulaRef, b: FormulaRef) -> FormulaRef {
+        match (a.formula(), b.formula()) {
+            (Formula::Const(false), _) | (_, Formula::Const(true)) => self.constant(true),
+            (Formula::Const(true), _) => b,
+            (_, Formula::Const(false)) => self.not(a),
+            _ if a == b => self.constant(true),
+            _ => self.intern(Formula::Implies(a, b)),
+        }
+    }
+
+    pub fn iff(&self, a: FormulaRef, b: FormulaRef) -> FormulaRef {
+        match (a.formula(), b.formula()) {
+            (Formula::Const(value), _) => if *value { b } else { self.not(b) },
+            (_, Formula::Const(value)) => if *value { a } else { self.not(a) },
+            _ if a == b => self.constant(true),
+            _ if Self::is_complement(&a, &b) => self.constant(false),
+            _ => {
+                let (a, b) = if a.id() <= b.id() { (a, b) } else { (b, a) };
+                self.intern(Formula::Iff(a, b))
+            }
+        }
+    }
+
+    pub fn xor(&self, a: FormulaRef, b: FormulaRef) -> FormulaRef {
+        match (a.formula(), b.formula()) {
+            (Formula::Const(value), _) => if *value { self.not(b) } else { b },
+            (_, Formula::Const(value)) => if *value { self.not(a) } else { a },
+            _ if a == b => self.constant(false),
+            _ if Self::is_complement(&a, &b) => self.constant(true),
+            _ => {
+                let (a, b) = if a.id() <= b.id() { (a, b) } else { (b, a) };
+                self.intern(Formula::Xor(a, b))
+            }
+        }
+    }
+
+    pub fn ite(&self, c: FormulaRef, t: FormulaRef, e: FormulaRef) -> FormulaRef {
+        match (c.formula(), t.formula(), e.formula()) {
+            (Formula::Const(true), _, _) => t,
+            (Formula::Const(false), _, _) => e,
+            _ if t == e => t,
+            (_, Formula::Const(true), Formula::Const(false)) => c,
+            (_, Formula::Const(false), Formula::Const(true)) => self.not(c),
+            (_, Formula::Const(true), _) => self.or(vec![c, e]),
+            (_, Formula::Const(false), _) => {
+                let not_c = self.not(c);
+                self.and(vec![not_c, e])
+            }
+            (_, _, Formula::Const(true)) => self.implies(c, t),
+            (_, _, Formula::Const(false)) => self.and(vec![c, t]),
+            _ => self.intern(Formula::Ite(c, t, e)),
+        }
+    }
+}
+
+impl Default for FormulaBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CnfEncoding {
+    Tseitin,
+    PlaistedGreenbaum,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Polarity {
+    Positive,
+    Negative,
+    Both,
+}
+
+impl Polarity {
+    fn flip(self) -> Polarity {
+        match self {
+            Polarity::Positive => Polarity::Negative,
+            Polarity::Negative => Polarity::Positive,
+            Polarity::Both => Polarity::Both,
+        }
+    }
+
+    fn positive(self) -> bool {
+        self != Polarity::Negative
+    }
+
+    fn negative(self) -> bool {
+        self != Polarity::Positive
+    }
+}
+
+//Defining literal of an encoded node and which of its implications have been emitted
+struct Definition {
+    literal: SignedLiteral,
+    positive: bool,
+    negative: bool,
+}
+
+pub struct CnfEncoder<'a> {
+    encoding: CnfEncoding,
+    pool: &'a mut LiteralPool,
+    cnf: CNF,
+    definitions: HashMap<FormulaRef, Definition>,
+}
+
+impl<'a> CnfEncoder<'a> {
+    pub fn new(encoding: CnfEncoding, pool: &'a mut LiteralPool) -> CnfEncoder<'a> {
+        CnfEncoder { encoding, pool, cnf: CNF::new(), definitions: HashMap::new() }
+    }
+
+    fn emit(&mut self, literals: Vec<SignedLiteral>) {
+        let cnf = std::mem::take(&mut self.cnf);
+        self.cnf = cnf.add_clause(literals.into_iter().collect());
+    }
+
+    //Literal equivalent to f (or implying/implied by it under Plaisted-Greenbaum) in every model of the emitted clauses
+    pub fn literal(&mut self, f: &FormulaRef) -> SignedLiteral {
+        self.encode(f, Polarity::Both)
+    }
+
+    //Add clauses requiring f to be true
+    pub fn assert(&mut self, f: &FormulaRef) {
+        match f.formula() {
+            Formula::Const(true) => {}
+            Formula::Const(false) => self.emit(vec![]),
+            Formula::And(children) => children.iter().for_each(|c| self.assert(c)),
+            Formula::Or(children) => {
+                let literals = children.iter().map(|c| self.encode(c, Polarity::Positive)).collect();
+                self.emit(literals);
+            }
+            _ => {
+                let l = self.encode(f, Polarity::Positive);
+                self.emit(vec![l]);
+            }
+        }
+    }
+
+    pub fn build(self) -> CNF {
+        self.cnf
+    }
+
+    fn encode(&mut self, f: &FormulaRef, polarity: Polarity) -> SignedLiteral {
+        let polarity = match self.encoding {
+            CnfEncoding::Tseitin => Polarity::Both,
+            CnfEncoding::PlaistedGreenbaum => polarity,
+        };
+
+        match f.formula() {
+            Formula::Var(l) => return l.identity(),
+            Formula::Not(a) => return self.encode(a, polarity.flip()).complement(),
+            Formula::Const(value) => {
+                //Only reachable for constants built outside of FormulaBuilder's folding, e.g. a bare root
+                let l = self.pool.fresh();
+                self.emit(vec![if *value { l.identity() } else { l.not() }]);
+                return l.identity();
+            }
+            _ => {}
+        }
+
+        let pool = &mut self.pool;
+        let definition = self.definitions.entry(f.clone()).or_insert_with(|| Definition {
+            literal: pool.fresh().identity(),
+            positive: false,
+            negative: false,
+        });
+        let x = definition.literal.clone();
+        let positive = polarity.positive() && !definition.positive;
+        let negative = polarity.negative() && !definition.negative;
+        definition.positive |= positive;
+        definition.negative |= negative;
+        let needed = match (positive, negative) {
+            (false, false) => return x,
+            (true, false) => Polarity::Positive,
+            (false, true) => Polarity::Negative,
+            (true, true) => Polarity::Both,
+        };
+
+        let nx = x.complement();
+        match f.formula() {
+            Formula::And(children) => {
+                let literals: Vec<SignedLiteral> = children.iter().map(|c| self.encode(c, needed)).collect();
+                if positive {
+                    // x -> a_i
+                    literals.iter().for_each(|l| self.emit(vec![nx.clone(), l.clone()]));
+                }
+                if negative {
+                    // a_1 ^ ... ^ a_n -> x
+                    let mut clause: Vec<SignedLiteral> = literals.iter().map(|l| l.complement()).collect();
+                    clause.push(x.clone());
+                    self.emit(clause);
+                }
+            }
+            Formula::Or(children) => {
+                let literals: Vec<SignedLiteral> = children.iter().map(|c| self.encode(c, needed)).collect();
+                if positive {
+                    // x -> a_1 v ... v a_n
+                    let mut clause = literals.clone();
+                    clause.push(nx.clone());
+                    self.emit(clause);
+                }
+                if negative {
+                    // a_i -> x
+                    literals.iter().for_each(|l| self.emit(vec![x.clone(), l.complement()]));
+                }
+            }
+            Formula::Implies(a, b) => {
+                let a = self.encode(a, needed.flip());
+                let b = self.encode(b, needed);
+                if positive {
+                    self.emit(vec![nx.clone(), a.complement(), b.clone()]);
+                }
+                if negative {
+                    self.emit(vec![x.clone(), a.clone()]);
+                    self.emit(vec![x.clone(), b.complement()]);
+                }
+            }
+            Formula::Iff(a, b) | Formula::Xor(a, b) => {
+                let a = self.encode(a, Polarity::Both);
+                let b = self.encode(b, Polarity::Both);
+                //Iff is Xor with the second operand complemented
+                let b = if matches!(f.formula(), Formula::Iff(_, _)) { b.complement() } else { b };
+                if positive {
+                    self.emit(vec![nx.clone(), a.clone(), b.clone()]);
+                    self.emit(vec![nx.clone(), a.complement(), b.complement()]);
+                }
+                if negative {
+                    self.emit(vec![x.clone(), a.complement(), b.clone()]);
+                    self.emit(vec![x.clone(), a.clone(), b.complement()]);
+                }
+            }
+            Formula::Ite(c, t, e) => {
+                let c = self.encode(c, Polarity::Both);
+                let t = self.encode(t, needed);
+                let e = self.encode(e, needed);
+                if positive {
+                    self.emit(vec![nx.clone(), c.complement(), t.clone()]);
+                    self.emit(vec![nx.clone(), c.clone(), e.clone()]);
+                }
+                if negative {
+                    self.emit(vec![x.clone(), c.complement(), t.complement()]);
+                    self.emit(vec![x.clone(), c.clone(), e.complement()]);
+                }
+            }
+            Formula::Const(_) | Formula::Var(_) | Formula::Not(_) => unreachable!(),
+        }
+        x
+    }
+}
+
+//Convert a single formula to an equisatisfiable CNF using auxiliary literals from a default pool
+pub fn to_cnf(f: &FormulaRef, encoding: CnfEncoding) -> CNF {
+    let mut pool = LiteralPool::default();
+    let mut encoder = CnfEncoder::new(encoding, &mut pool);
+    encoder.assert(f);
+    encoder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{definitions::{Literal, Satisfiability}, sdpll::DPLLSolverBuilder, SolverBuilder};
+
+    fn solve(cnf: CNF) -> Satisfiability {
+        DPLLSolverBuilder::new().build(cnf).solve()
+    }
+
+    //Check that f under every assignment of inputs is true iff the encoding restricted to that assignment is SAT
+    fn check_equisatisfiable(fb: &FormulaBuilder, f: &FormulaRef, inputs: &[RefLiteral]) {
+        for encoding in [CnfEncoding::Tseitin, CnfEncoding::PlaistedGreenbaum] {
+            for bits in 0..(1usize << inputs.len()) {
+                let mut assignments = Assignments::new();
+                let mut cube = vec![];
+                for (i, input) in inputs.iter().enumerate() {
+                    let value = bits & (1 << i) != 0;
+                    assignments.assign(input.clone(), if value { LiteralValue::True } else { LiteralValue::False });
+                    cube.push(fb.literal(if value { input.identity() } else { input.not() }));
+                }
+                cube.push(f.clone());
+                let expected = match f.evaluate(&assignments) {
+                    LiteralValue::True => Satisfiability::SAT,
+                    _ => Satisfiability::UNSAT,
+                };
+                assert_eq!(solve(to_cnf(&fb.and(cube), encoding)), expected, "{:?} {:?}", encoding, assignments);
+            }
+        }
+    }
+
+    #[test]
+    fn structural_hashing() {
+        let fb = FormulaBuilder::new();
+        let a = fb.var(Literal::new("a".to_string()));
+        let b = fb.var(Literal::new("b".to_string()));
+
+        let f1 = fb.and(vec![a.clone(), fb.not(b.clone())]);
+        let f2 = fb.and(vec![fb.not(b.clone()), a.clone()]);
+        assert_eq!(f1, f2);
+        assert_eq!(fb.not(fb.not(a.clone())), a);
+        assert_eq!(fb.and(vec![a.clone(), fb.not(a.clone())]), fb.constant(false));
+        assert_eq!(fb.or(vec![a.clone(), fb.constant(true)]), fb.constant(true));
+        assert_eq!(fb.xor(a.clone(), b.clone()), fb.xor(b, a));
+    }
+
+    #[test]
+    fn encodings_preserve_semantics() {
+        let fb = FormulaBuilder::new();
+        let inputs: Vec<RefLiteral> = ["a", "b", "c"].iter().map(|n| Literal::new(n.to_string())).collect();
+        let a = fb.var(inputs[0].clone());
+        let b = fb.var(inputs[1].clone());
+        let c = fb.var(inputs[2].clone());
+
+        let formulas = [
+            fb.implies(a.clone(), fb.or(vec![b.clone(), c.clone()])),
+            fb.iff(fb.xor(a.clone(), b.clone()), fb.not(c.clone())),
+            fb.ite(a.clone(), fb.and(vec![b.clone(), c.clone()]), fb.xor(b.clone(), c.clone())),
+            fb.not(fb.ite(c.clone(), fb.iff(a.clone(), b.clone()), fb.implies(b.clone(), a.clone()))),
+            fb.and(vec![fb.or(vec![a.clone(), b.clone()]), fb.not(fb.and(vec![a.clone(), c.clone()]))]),
+        ];
+        for f in formulas.iter() {
+            check_equisatisfiable(&fb, f, &inputs);
+        }
+    }
+
+    #[test]
+    fn unsatisfiable_formula() {
+        let fb = FormulaBuilder::new();
+        let a = fb.var(Literal::new("a".to_string()));
+        let b = fb.var(Literal::new("b".to_string()));
+        let f = fb.and(vec![fb.iff(a.clone(), b.clone()), fb.xor(a, b)]);
+
+        assert_eq!(solve(to_cnf(&f, CnfEncoding::Tseitin)), Satisfiability::UNSAT);
+        assert_eq!(solve(to_cnf(&f, CnfEncoding::PlaistedGreenbaum)), Satisfiability::UNSAT);
+        assert_eq!(solve(to_cnf(&fb.constant(false), CnfEncoding::Tseitin)), Satisfiability::UNSAT);
+    }
+
+    #[test]
+    fn plaisted_greenbaum_is_smaller() {
+        let fb = FormulaBuilder::new();
+        let v: Vec<FormulaRef> = (0..6).map(|i| fb.var(Literal::new(format!("x{}", i)))).collect();
+        let f = fb.or(vec![
+            fb.and(vec![v[0].clone(), v[1].clone(), v[2].clone()]),
+            fb.and(vec![v[3].clone(), v[4].clone(), v[5].clone()]),
+        ]);
+
+        let tseitin = to_cnf(&f, CnfEncoding::Tseitin).clauses().count();
+        let pg = to_cnf(&f, CnfEncoding::PlaistedGreenbaum).clauses().count();
+        assert!(pg < tseitin, "{} >= {}", pg, tseitin);
+    }
+}
@@ -0,0 +1,117 @@
+//Gauss-Jordan elimination over GF(2) on the XOR constraints of a formula
+//A row 0 = 1 is a conflict, a row with a single variable is an implied unit
+use std::collections::HashMap;
+
+use crate::definitions::{RefLiteral, SignedLiteral, XorRef};
+
+#[derive(Debug, PartialEq)]
+pub enum Elimination {
+    Conflict,
+    Units(Vec<SignedLiteral>),
+}
+
+struct Row {
+    bits: Vec<u64>,
+    parity: bool,
+}
+
+impl Row {
+    fn get(&self, column: usize) -> bool {
+        self.bits[column / 64] & (1 << (column % 64)) != 0
+    }
+
+    fn add(&mut self, other: &Row) {
+        self.bits.iter_mut().zip(other.bits.iter()).for_each(|(a, b)| *a ^= b);
+        self.parity ^= other.parity;
+    }
+
+    fn columns(&self) -> impl Iterator<Item = usize> + '_ {
+        self.bits.iter().enumerate().flat_map(|(word, bits)| {
+            (0..64).filter(move |bit| bits & (1 << bit) != 0).map(move |bit| word * 64 + bit)
+        })
+    }
+}
+
+pub fn eliminate<'a, I: IntoIterator<Item = &'a XorRef>>(xors: I) -> Elimination {
+    let mut variables: Vec<RefLiteral> = vec![];
+    let mut columns: HashMap<RefLiteral, usize> = HashMap::new();
+    let xors: Vec<&XorRef> = xors.into_iter().collect();
+    for xor in xors.iter() {
+        for l in xor.signed_literal() {
+            columns.entry(l.literal()).or_insert_with(|| {
+                variables.push(l.literal());
+                variables.len() - 1
+            });
+        }
+    }
+
+    let words = variables.len().div_ceil(64);
+    let mut rows: Vec<Row> = xors
+        .iter()
+        .map(|xor| {
+            let mut row = Row { bits: vec![0; words], parity: xor.parity() };
+            for l in xor.signed_literal() {
+                let column = columns[&l.literal()];
+                row.bits[column / 64] |= 1 << (column % 64);
+            }
+            row
+        })
+        .collect();
+
+    let mut pivot = 0;
+    for column in 0..variables.len() {
+        let Some(r) = (pivot..rows.len()).find(|&r| rows[r].get(column)) else {
+            continue;
+        };
+        rows.swap(pivot, r);
+        let (above, rest) = rows.split_at_mut(pivot);
+        let (pivot_row, below) = rest.split_first_mut().unwrap();
+        for row in above.iter_mut().chain(below.iter_mut()) {
+            if row.get(column) {
+                row.add(pivot_row);
+            }
+        }
+        pivot += 1;
+    }
+
+    let mut units = vec![];
+    for row in rows.iter() {
+        let mut row_columns = row.columns();
+        match (row_columns.next(), row_columns.next()) {
+            (None, _) if row.parity => return Elimination::Conflict,
+            (Some(column), None) => {
+                let variable = &variables[column];
+                units.push(if row.parity { variable.identity() } else { variable.not() });
+            }
+            _ => {}
+        }
+    }
+    Elimination::Units(units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::{Literal, Xor};
+
+    #[test]
+    fn conflict_and_units() {
+        let x: Vec<RefLiteral> = (0..4).map(|i| Literal::new(format!("x{}", i))).collect();
+
+        // x0 ^ x1 = 1, x1 ^ x2 = 1, x0 ^ x2 = 1 sums to 0 = 1
+        let xors = [
+            Xor::new([x[0].identity(), x[1].identity()]),
+            Xor::new([x[1].identity(), x[2].identity()]),
+            Xor::new([x[0].identity(), x[2].identity()]),
+        ];
+        assert_eq!(eliminate(xors.iter()), Elimination::Conflict);
+
+        // x0 ^ x1 ^ x2 = 1, x1 ^ x2 = 0, x2 ^ x3 = 1 implies x0 = 1 but leaves x1..x3 open
+        let xors = [
+            Xor::new([x[0].identity(), x[1].identity(), x[2].identity()]),
+            Xor::new([x[1].identity(), x[2].not()]),
+            Xor::new([x[2].identity(), x[3].identity()]),
+        ];
+        assert_eq!(eliminate(xors.iter()), Elimination::Units(vec![x[0].identity()]));
+    }
+}
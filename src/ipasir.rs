@@ -0,0 +1,277 @@
+//IPASIR, the incremental SAT solver interface of the SAT Race 2015, for C and C++ callers. The functions
+//are exported by the cdylib and staticlib builds of this crate and declared in include/ipasir.h, which
+//cbindgen generates from the doc comments below (see cbindgen.toml)
+//The formula is kept between calls and every ipasir_solve hands it to a fresh DPLL solver, with the
+//assumptions as unit clauses, so nothing learned in one call helps the next. Failed assumptions are those
+//whose unit clauses are in the UNSAT core
+//The terminate callback is polled on the calling thread while the solver runs in a thread of its own
+//Every function takes a pointer returned by ipasir_init and not released yet, as the standard requires
+#![allow(clippy::missing_safety_doc)]
+use std::{
+    collections::HashSet,
+    ffi::{c_char, c_int, c_void},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    config::{SolverConfig, SolverKind},
+    definitions::{Assignments, ClauseRef, LiteralValue, Satisfiability, CNF},
+    dimacs::dimacs_literal,
+    limits::Limits,
+    SolverBuilder,
+};
+
+const SIGNATURE: &str = concat!("sat-solver ", env!("CARGO_PKG_VERSION"), "\0");
+//How often the terminate callback is called
+const POLL: Duration = Duration::from_millis(5);
+
+//Spelled out in the signatures below, where cbindgen does not resolve aliases inside Option
+type Terminate = unsafe extern "C" fn(data: *mut c_void) -> c_int;
+
+struct Ipasir {
+    formula: CNF,
+    //Literals added since the last 0
+    clause: Vec<i32>,
+    assumptions: Vec<i32>,
+    terminate: Option<(*mut c_void, Terminate)>,
+    //Answer of the last solve
+    model: Option<Assignments>,
+    failed: HashSet<i32>,
+}
+
+impl Ipasir {
+    fn new() -> Self {
+        Ipasir {
+            formula: CNF::new(),
+            clause: vec![],
+            assumptions: vec![],
+            terminate: None,
+            model: None,
+            failed: HashSet::new(),
+        }
+    }
+
+    fn add(&mut self, lit: i32) {
+        if lit == 0 {
+            let clause: ClauseRef = self.clause.drain(..).map(dimacs_literal).collect();
+            self.formula = std::mem::take(&mut self.formula).add_clause(clause);
+        } else {
+            self.clause.push(lit);
+        }
+    }
+
+    fn solve(&mut self) -> Satisfiability {
+        let assumptions: Vec<(i32, ClauseRef)> = std::mem::take(&mut self.assumptions)
+            .into_iter()
+            .map(|lit| (lit, [dimacs_literal(lit)].into_iter().collect()))
+            .collect();
+        let formula = assumptions.iter().fold(self.formula.clone(), |cnf, (_, unit)| cnf.add_clause(unit.clone()));
+        let flag = Arc::new(AtomicBool::new(false));
+        let config = SolverConfig::new(SolverKind::DPLL).limits(Limits::new().terminate(flag.clone()));
+
+        let (satisfiability, model, core) = thread::scope(|scope| {
+            let handle = scope.spawn(move || {
+                let mut solver = config.build(formula);
+                let satisfiability = solver.solve();
+                (satisfiability, solver.model(), solver.core())
+            });
+            if let Some((data, terminate)) = self.terminate {
+                while !handle.is_finished() {
+                    if unsafe { terminate(data) } != 0 {
+                        flag.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                    thread::sleep(POLL);
+                }
+            }
+            handle.join().unwrap()
+        });
+
+        self.model = model;
+        self.failed = match core {
+            Some(core) => {
+                let ids: HashSet<usize> = core.clauses().map(|c| c.id()).collect();
+                assumptions.iter().filter(|(_, unit)| ids.contains(&unit.id())).map(|(lit, _)| *lit).collect()
+            }
+            None => HashSet::new(),
+        };
+        satisfiability
+    }
+
+    fn val(&self, lit: i32) -> i32 {
+        let value = match &self.model {
+            Some(model) => dimacs_literal(lit).evaluate(model),
+            None => LiteralValue::Unassigned,
+        };
+        match value {
+            LiteralValue::True => lit,
+            LiteralValue::False => -lit,
+            LiteralValue::Unassigned => 0,
+        }
+    }
+}
+
+unsafe fn solver<'a>(solver: *mut c_void) -> &'a mut Ipasir {
+    &mut *(solver as *mut Ipasir)
+}
+
+/// Name and version of the solver
+#[no_mangle]
+pub extern "C" fn ipasir_signature() -> *const c_char {
+    SIGNATURE.as_ptr() as *const c_char
+}
+
+/// New solver, released with ipasir_release
+#[no_mangle]
+pub extern "C" fn ipasir_init() -> *mut c_void {
+    Box::into_raw(Box::new(Ipasir::new())) as *mut c_void
+}
+
+/// Frees the solver
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_release(s: *mut c_void) {
+    drop(Box::from_raw(s as *mut Ipasir));
+}
+
+/// Adds lit to the clause being built, 0 ends the clause and adds it to the formula
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_add(s: *mut c_void, lit_or_zero: i32) {
+    solver(s).add(lit_or_zero);
+}
+
+/// Assumes lit for the next call to ipasir_solve only
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_assume(s: *mut c_void, lit: i32) {
+    solver(s).assumptions.push(lit);
+}
+
+/// 10 if satisfiable, 20 if unsatisfiable, 0 if interrupted by the terminate callback
+/// Solves the formula from scratch with a fresh DPLL solver, no state is kept from earlier calls
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_solve(s: *mut c_void) -> c_int {
+    match solver(s).solve() {
+        Satisfiability::SAT => 10,
+        Satisfiability::UNSAT => 20,
+        Satisfiability::Unknown => 0,
+    }
+}
+
+/// After a satisfiable solve: lit if it is true in the model, -lit if it is false, 0 if either value works
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_val(s: *mut c_void, lit: i32) -> i32 {
+    solver(s).val(lit)
+}
+
+/// After an unsatisfiable solve: 1 if assumption lit was used to prove unsatisfiability, else 0
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_failed(s: *mut c_void, lit: i32) -> c_int {
+    solver(s).failed.contains(&lit) as c_int
+}
+
+/// Polled during ipasir_solve, which gives up once it returns non-zero. NULL removes the callback
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_set_terminate(
+    s: *mut c_void,
+    data: *mut c_void,
+    terminate: Option<unsafe extern "C" fn(data: *mut c_void) -> c_int>,
+) {
+    solver(s).terminate = terminate.map(|terminate| (data, terminate));
+}
+
+/// Does nothing: learned clauses stay inside the solver of each call, so the callback is never called
+#[no_mangle]
+#[allow(unused_variables)]
+pub unsafe extern "C" fn ipasir_set_learn(
+    s: *mut c_void,
+    data: *mut c_void,
+    max_length: c_int,
+    learn: Option<unsafe extern "C" fn(data: *mut c_void, clause: *mut i32)>,
+) {
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    fn add_clause(s: *mut c_void, clause: &[i32]) {
+        for lit in clause.iter().chain([0].iter()) {
+            unsafe { ipasir_add(s, *lit) };
+        }
+    }
+
+    #[test]
+    fn incremental_solving() {
+        unsafe {
+            assert!(CStr::from_ptr(ipasir_signature()).to_str().unwrap().starts_with("sat-solver"));
+            let s = ipasir_init();
+            add_clause(s, &[1, 2]);
+            add_clause(s, &[-1, 3]);
+            assert_eq!(ipasir_solve(s), 10);
+            assert!(ipasir_val(s, 1) == 1 || ipasir_val(s, 2) == 2);
+            assert!(ipasir_val(s, 1) != 1 || ipasir_val(s, -3) == 3);
+
+            //Assumptions only hold for one solve
+            ipasir_assume(s, -2);
+            ipasir_assume(s, -3);
+            ipasir_assume(s, 4);
+            assert_eq!(ipasir_solve(s), 20);
+            assert_eq!((ipasir_failed(s, -2), ipasir_failed(s, -3), ipasir_failed(s, 4)), (1, 1, 0));
+            assert_eq!(ipasir_solve(s), 10);
+
+            add_clause(s, &[-2]);
+            add_clause(s, &[-3]);
+            assert_eq!(ipasir_solve(s), 20);
+            ipasir_release(s);
+        }
+    }
+
+    unsafe extern "C" fn stop(data: *mut c_void) -> c_int {
+        *(data as *mut usize) += 1;
+        1
+    }
+
+    #[test]
+    fn terminate_callback() {
+        let dimacs = crate::dimacs::read_dimacs_cnf(include_str!("../benchmarks/uuf50-01.cnf").as_bytes()).unwrap();
+        unsafe {
+            let s = ipasir_init();
+            for clause in dimacs.clauses() {
+                add_clause(s, clause);
+            }
+            let mut calls = 0usize;
+            ipasir_set_terminate(s, &mut calls as *mut usize as *mut c_void, Some(stop));
+            assert_eq!(ipasir_solve(s), 0);
+            assert_eq!(calls, 1);
+            assert_eq!(ipasir_val(s, 1), 0);
+
+            ipasir_set_terminate(s, std::ptr::null_mut(), None);
+            assert_eq!(ipasir_solve(s), 20);
+            ipasir_release(s);
+        }
+    }
+
+    //The header is what cbindgen generates from this module, REGENERATE_HEADER=1 rewrites it
+    #[test]
+    fn generated_header() {
+        let root = env!("CARGO_MANIFEST_DIR");
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", root)).unwrap();
+        let bindings = cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(format!("{}/src/ipasir.rs", root))
+            .generate()
+            .unwrap();
+        let mut generated = vec![];
+        bindings.write(&mut generated);
+        let path = format!("{}/include/ipasir.h", root);
+        if std::env::var_os("REGENERATE_HEADER").is_some() {
+            std::fs::write(&path, &generated).unwrap();
+        }
+        assert_eq!(String::from_utf8(generated).unwrap(), std::fs::read_to_string(&path).unwrap());
+    }
+}
@@ -1,4 +1,5 @@
-use definitions::Satisfiability;
+use definitions::{Assignments, Satisfiability, CNF};
+use statistics::Statistics;
 
 /*
 
@@ -27,9 +28,41 @@ pub trait SolverBuilder {
 
 pub trait Solver {
     fn solve(&mut self) -> Satisfiability;
+    //Satisfying assignment found by the last call to solve, if it returned SAT
+    //Variables left unassigned can take either value
+    fn model(&self) -> Option<Assignments>;
+    //Input clauses that are unsatisfiable on their own, if the last call to solve returned UNSAT
+    fn core(&self) -> Option<CNF>;
+    fn statistics(&self) -> Statistics;
 }
 
 pub mod sdpll;
 pub mod pdpll;
 pub mod tests;
-pub mod dimacs;
\ No newline at end of file
+pub mod dimacs;
+pub mod formula;
+pub mod encodings;
+pub mod pb;
+pub mod opb;
+pub mod gauss;
+pub mod statistics;
+pub mod limits;
+pub mod config;
+pub mod verify;
+pub mod maxsat;
+pub mod wcnf;
+mod trace;
+pub mod mus;
+pub mod enumerate;
+pub mod count;
+pub mod approx;
+pub mod backbone;
+pub mod local_search;
+pub mod portfolio;
+pub mod sharing;
+pub mod cube;
+pub mod distributed;
+pub mod ipasir;
+pub mod service;
+#[cfg(feature = "python")]
+pub mod python;
@@ -0,0 +1,78 @@
+//Resource limits after which a solver gives up with Satisfiability::Unknown
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::statistics::Counters;
+
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    time: Option<Duration>,
+    conflicts: Option<u64>,
+    //Flags set from other threads to cancel the search
+    terminate: Vec<Arc<AtomicBool>>,
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Limits::default()
+    }
+
+    pub fn time(mut self, time: Duration) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    pub fn conflicts(mut self, conflicts: u64) -> Self {
+        self.conflicts = Some(conflicts);
+        self
+    }
+
+    //Give up once the flag is set, may be called several times to watch several flags
+    pub fn terminate(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.terminate.push(flag);
+        self
+    }
+
+    //What is left of these limits after running for elapsed with this many conflicts
+    pub(crate) fn remaining(&self, elapsed: Duration, conflicts: u64) -> Limits {
+        Limits {
+            time: self.time.map(|time| time.saturating_sub(elapsed)),
+            conflicts: self.conflicts.map(|limit| limit.saturating_sub(conflicts)),
+            terminate: self.terminate.clone(),
+        }
+    }
+
+    pub(crate) fn time_limit(&self) -> Option<Duration> {
+        self.time
+    }
+
+    pub(crate) fn conflict_limit(&self) -> Option<u64> {
+        self.conflicts
+    }
+
+    pub(crate) fn exceeded(&self, start: Instant, counters: &Counters) -> bool {
+        self.exceeded_at(start, counters.conflicts())
+    }
+
+    //As exceeded, with a conflict count kept by the caller
+    pub(crate) fn exceeded_at(&self, start: Instant, conflicts: u64) -> bool {
+        self.time.is_some_and(|time| start.elapsed() >= time)
+            || self.conflicts.is_some_and(|limit| conflicts >= limit)
+            || self.terminate.iter().any(|flag| flag.load(Ordering::Relaxed))
+    }
+}
+
+//Flags are compared by identity
+impl PartialEq for Limits {
+    fn eq(&self, other: &Self) -> bool {
+        self.time == other.time
+            && self.conflicts == other.conflicts
+            && self.terminate.len() == other.terminate.len()
+            && self.terminate.iter().zip(other.terminate.iter()).all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+}
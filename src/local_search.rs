@@ -0,0 +1,537 @@
+//Stochastic local search: WalkSAT (Selman, Kautz & Cohen, Noise Strategies for Improving Local Search, 1994)
+//and ProbSAT (Balint & Schöning, Choosing Probability Distributions for Stochastic Local Search and the
+//Role of Make versus Break, 2012)
+//Each try starts from a random complete assignment and flips variables of a random falsified constraint
+//until none is left. Break counts (constraints a flip would falsify) are cached and updated per flip.
+//Local search cannot prove unsatisfiability, so solve answers SAT or Unknown.
+//XOR constraints take part like clauses: flipping any of their variables changes their value.
+//Flips are reported as decisions in the statistics.
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{
+    definitions::{Assignments, LiteralValue, RefLiteral, Satisfiability, SignedLiteral, CNF},
+    limits::Limits,
+    statistics::{Counters, Statistics},
+    Solver, SolverBuilder,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Selection {
+    //Probability of a random walk step when every candidate breaks some clause
+    WalkSAT { noise: f64 },
+    //A candidate is chosen with probability proportional to (eps + break)^-cb
+    ProbSAT { cb: f64, eps: f64 },
+}
+
+#[derive(Debug, Clone)]
+struct Options {
+    selection: Selection,
+    max_flips: u64,
+    max_tries: Option<u64>,
+    limits: Limits,
+    seed: Option<u64>,
+}
+
+impl Options {
+    fn build(self, formula: CNF) -> Box<dyn Solver> {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        Box::new(LocalSearchSolver {
+            state: State::new(&formula),
+            options: self,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            counters: Counters::default(),
+            elapsed: Duration::ZERO,
+            model: None,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WalkSATSolverBuilder(Options);
+
+impl WalkSATSolverBuilder {
+    pub fn new() -> Self {
+        WalkSATSolverBuilder(Options {
+            selection: Selection::WalkSAT { noise: 0.567 },
+            max_flips: 100_000,
+            max_tries: None,
+            limits: Limits::new(),
+            seed: None,
+        })
+    }
+
+    pub fn noise(mut self, noise: f64) -> Self {
+        self.0.selection = Selection::WalkSAT { noise };
+        self
+    }
+
+    //Flips per try before restarting from a new random assignment
+    pub fn max_flips(mut self, max_flips: u64) -> Self {
+        self.0.max_flips = max_flips;
+        self
+    }
+
+    //Unbounded if not set, the search then only stops at a model or a limit
+    pub fn max_tries(mut self, max_tries: u64) -> Self {
+        self.0.max_tries = Some(max_tries);
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.0.limits = limits;
+        self
+    }
+
+    //Seed for the initial assignments and the choice of flips, drawn from entropy if not set
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.0.seed = Some(seed);
+        self
+    }
+}
+
+impl Default for WalkSATSolverBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SolverBuilder for WalkSATSolverBuilder {
+    fn build(self, formula: CNF) -> Box<dyn Solver> {
+        self.0.build(formula)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProbSATSolverBuilder(Options);
+
+impl ProbSATSolverBuilder {
+    //Defaults of the polynomial break-only variant for 3-SAT
+    pub fn new() -> Self {
+        ProbSATSolverBuilder(Options {
+            selection: Selection::ProbSAT { cb: 2.06, eps: 0.9 },
+            max_flips: 100_000,
+            max_tries: None,
+            limits: Limits::new(),
+            seed: None,
+        })
+    }
+
+    pub fn cb(mut self, cb: f64) -> Self {
+        if let Selection::ProbSAT { eps, .. } = self.0.selection {
+            self.0.selection = Selection::ProbSAT { cb, eps };
+        }
+        self
+    }
+
+    pub fn eps(mut self, eps: f64) -> Self {
+        if let Selection::ProbSAT { cb, .. } = self.0.selection {
+            self.0.selection = Selection::ProbSAT { cb, eps };
+        }
+        self
+    }
+
+    pub fn max_flips(mut self, max_flips: u64) -> Self {
+        self.0.max_flips = max_flips;
+        self
+    }
+
+    pub fn max_tries(mut self, max_tries: u64) -> Self {
+        self.0.max_tries = Some(max_tries);
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.0.limits = limits;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.0.seed = Some(seed);
+        self
+    }
+}
+
+impl Default for ProbSATSolverBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SolverBuilder for ProbSATSolverBuilder {
+    fn build(self, formula: CNF) -> Box<dyn Solver> {
+        self.0.build(formula)
+    }
+}
+
+//Clauses are constraints 0..clauses.len(), XORs follow
+struct State {
+    variables: Vec<RefLiteral>,
+    //(variable, positive)
+    clauses: Vec<Vec<(usize, bool)>>,
+    xors: Vec<(Vec<usize>, bool)>,
+    //Clauses where the literal of the variable with this sign occurs
+    positive: Vec<Vec<usize>>,
+    negative: Vec<Vec<usize>>,
+    xor_occurrences: Vec<Vec<usize>>,
+
+    values: Vec<bool>,
+    //Per clause: number of true literals and the sum of their variables, which is the critical
+    //variable when only one literal is true
+    true_count: Vec<usize>,
+    true_sum: Vec<usize>,
+    xor_satisfied: Vec<bool>,
+    breaks: Vec<usize>,
+    //Falsified constraints and the position of each in that list
+    falsified: Vec<usize>,
+    position: Vec<Option<usize>>,
+}
+
+impl State {
+    fn new(formula: &CNF) -> Self {
+        let mut index: HashMap<RefLiteral, usize> = HashMap::new();
+        let mut variables = vec![];
+        let mut id = |v: RefLiteral| {
+            *index.entry(v.clone()).or_insert_with(|| {
+                variables.push(v);
+                variables.len() - 1
+            })
+        };
+        let clauses: Vec<Vec<(usize, bool)>> = formula
+            .clauses()
+            .map(|c| {
+                c.signed_literal()
+                    .map(|l| match l {
+                        SignedLiteral::Id(v) => (id(v.clone()), true),
+                        SignedLiteral::Not(v) => (id(v.clone()), false),
+                    })
+                    .collect()
+            })
+            .collect();
+        let xors: Vec<(Vec<usize>, bool)> = formula
+            .xors()
+            .map(|x| (x.signed_literal().map(|l| id(l.literal())).collect(), x.parity()))
+            .collect();
+
+        let n = variables.len();
+        let mut positive = vec![vec![]; n];
+        let mut negative = vec![vec![]; n];
+        let mut xor_occurrences = vec![vec![]; n];
+        for (c, clause) in clauses.iter().enumerate() {
+            for (v, sign) in clause.iter() {
+                if *sign { positive[*v].push(c) } else { negative[*v].push(c) }
+            }
+        }
+        for (x, (xor, _)) in xors.iter().enumerate() {
+            for v in xor.iter() {
+                xor_occurrences[*v].push(x);
+            }
+        }
+        let constraints = clauses.len() + xors.len();
+        State {
+            variables,
+            true_count: vec![0; clauses.len()],
+            true_sum: vec![0; clauses.len()],
+            xor_satisfied: vec![false; xors.len()],
+            clauses,
+            xors,
+            positive,
+            negative,
+            xor_occurrences,
+            values: vec![false; n],
+            breaks: vec![0; n],
+            falsified: vec![],
+            position: vec![None; constraints],
+        }
+    }
+
+    fn unsatisfy(&mut self, c: usize) {
+        self.position[c] = Some(self.falsified.len());
+        self.falsified.push(c);
+    }
+
+    fn satisfy(&mut self, c: usize) {
+        let i = self.position[c].take().unwrap();
+        let last = self.falsified.pop().unwrap();
+        if last != c {
+            self.falsified[i] = last;
+            self.position[last] = Some(i);
+        }
+    }
+
+    fn reset(&mut self, rng: &mut StdRng) {
+        self.values.iter_mut().for_each(|value| *value = rng.gen_bool(0.5));
+        self.breaks.iter_mut().for_each(|b| *b = 0);
+        self.falsified.clear();
+        self.position.iter_mut().for_each(|p| *p = None);
+
+        for c in 0..self.clauses.len() {
+            let true_literals = self.clauses[c].iter().filter(|(v, sign)| self.values[*v] == *sign);
+            self.true_count[c] = true_literals.clone().count();
+            self.true_sum[c] = true_literals.map(|(v, _)| v).sum();
+            match self.true_count[c] {
+                0 => self.unsatisfy(c),
+                1 => self.breaks[self.true_sum[c]] += 1,
+                _ => {}
+            }
+        }
+        for x in 0..self.xors.len() {
+            let (xor, parity) = &self.xors[x];
+            let satisfied = (xor.iter().filter(|v| self.values[**v]).count() % 2 == 1) == *parity;
+            self.xor_satisfied[x] = satisfied;
+            if satisfied {
+                for v in xor.iter() {
+                    self.breaks[*v] += 1;
+                }
+            } else {
+                self.unsatisfy(self.clauses.len() + x);
+            }
+        }
+    }
+
+    fn flip(&mut self, v: usize) {
+        self.values[v] = !self.values[v];
+        let (now_true, now_false) = if self.values[v] {
+            (std::mem::take(&mut self.positive[v]), std::mem::take(&mut self.negative[v]))
+        } else {
+            (std::mem::take(&mut self.negative[v]), std::mem::take(&mut self.positive[v]))
+        };
+        for c in now_true.iter().cloned() {
+            self.true_count[c] += 1;
+            self.true_sum[c] += v;
+            match self.true_count[c] {
+                1 => {
+                    self.satisfy(c);
+                    self.breaks[v] += 1;
+                }
+                2 => self.breaks[self.true_sum[c] - v] -= 1,
+                _ => {}
+            }
+        }
+        for c in now_false.iter().cloned() {
+            self.true_count[c] -= 1;
+            self.true_sum[c] -= v;
+            match self.true_count[c] {
+                0 => {
+                    self.unsatisfy(c);
+                    self.breaks[v] -= 1;
+                }
+                1 => self.breaks[self.true_sum[c]] += 1,
+                _ => {}
+            }
+        }
+        if self.values[v] {
+            (self.positive[v], self.negative[v]) = (now_true, now_false);
+        } else {
+            (self.negative[v], self.positive[v]) = (now_true, now_false);
+        }
+
+        let occurrences = std::mem::take(&mut self.xor_occurrences[v]);
+        for x in occurrences.iter().cloned() {
+            let satisfied = !self.xor_satisfied[x];
+            self.xor_satisfied[x] = satisfied;
+            for u in self.xors[x].0.iter() {
+                if satisfied { self.breaks[*u] += 1 } else { self.breaks[*u] -= 1 }
+            }
+            if satisfied { self.satisfy(self.clauses.len() + x) } else { self.unsatisfy(self.clauses.len() + x) }
+        }
+        self.xor_occurrences[v] = occurrences;
+    }
+
+    fn constraint(&self, c: usize) -> Vec<usize> {
+        match self.clauses.get(c) {
+            Some(clause) => clause.iter().map(|(v, _)| *v).collect(),
+            None => self.xors[c - self.clauses.len()].0.clone(),
+        }
+    }
+
+    fn model(&self) -> Assignments {
+        let mut model = Assignments::new();
+        for (v, value) in self.variables.iter().zip(self.values.iter()) {
+            model.assign(v.clone(), if *value { LiteralValue::True } else { LiteralValue::False });
+        }
+        model
+    }
+}
+
+pub struct LocalSearchSolver {
+    options: Options,
+    state: State,
+    seed: u64,
+    //Reseeded with seed by every solve
+    rng: StdRng,
+    counters: Counters,
+    elapsed: Duration,
+    model: Option<Assignments>,
+}
+
+impl LocalSearchSolver {
+    fn pick(&mut self, candidates: &[usize]) -> usize {
+        let breaks: Vec<usize> = candidates.iter().map(|v| self.state.breaks[*v]).collect();
+        match self.options.selection {
+            Selection::WalkSAT { noise } => {
+                let min = *breaks.iter().min().unwrap();
+                if min > 0 && self.rng.gen_bool(noise) {
+                    return *candidates.choose(&mut self.rng).unwrap();
+                }
+                let best: Vec<usize> = candidates.iter().zip(breaks.iter()).filter(|(_, b)| **b == min).map(|(v, _)| *v).collect();
+                *best.choose(&mut self.rng).unwrap()
+            }
+            Selection::ProbSAT { cb, eps } => {
+                let weights: Vec<f64> = breaks.iter().map(|b| (eps + *b as f64).powf(-cb)).collect();
+                let mut r = self.rng.gen::<f64>() * weights.iter().sum::<f64>();
+                for (v, w) in candidates.iter().zip(weights.iter()) {
+                    if r < *w {
+                        return *v;
+                    }
+                    r -= w;
+                }
+                *candidates.last().unwrap()
+            }
+        }
+    }
+}
+
+impl Solver for LocalSearchSolver {
+    fn solve(&mut self) -> Satisfiability {
+        let start = Instant::now();
+        self.counters = Counters::default();
+        self.model = None;
+        self.rng = StdRng::seed_from_u64(self.seed);
+
+        let mut tries = 0;
+        let satisfiability = 'search: loop {
+            if self.options.max_tries.is_some_and(|max_tries| tries >= max_tries) {
+                break Satisfiability::Unknown;
+            }
+            tries += 1;
+            self.state.reset(&mut self.rng);
+
+            for flip in 0..self.options.max_flips {
+                if self.state.falsified.is_empty() {
+                    break 'search Satisfiability::SAT;
+                }
+                if flip % 1024 == 0 && self.options.limits.exceeded(start, &self.counters) {
+                    break 'search Satisfiability::Unknown;
+                }
+                let c = *self.state.falsified.choose(&mut self.rng).unwrap();
+                let candidates = self.state.constraint(c);
+                if candidates.is_empty() {
+                    //An empty clause can never be satisfied
+                    break 'search Satisfiability::Unknown;
+                }
+                let v = self.pick(&candidates);
+                self.state.flip(v);
+                self.counters.decision();
+            }
+            if self.state.falsified.is_empty() {
+                break Satisfiability::SAT;
+            }
+        };
+
+        self.elapsed = start.elapsed();
+        if satisfiability == Satisfiability::SAT {
+            self.model = Some(self.state.model());
+        }
+        satisfiability
+    }
+
+    fn model(&self) -> Option<Assignments> {
+        self.model.clone()
+    }
+
+    fn core(&self) -> Option<CNF> {
+        None
+    }
+
+    fn statistics(&self) -> Statistics {
+        Statistics { seed: Some(self.seed), ..self.counters.statistics(self.elapsed) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        definitions::{CNFValue, XorValue},
+        dimacs::read_dimacs_cnf,
+    };
+
+    #[test]
+    fn finds_models() {
+        let cnf = CNF::from(read_dimacs_cnf(include_str!("../benchmarks/uf20-01.cnf").as_bytes()).unwrap());
+        for seed in 0..5 {
+            let solvers = [
+                WalkSATSolverBuilder::new().seed(seed).build(cnf.clone()),
+                ProbSATSolverBuilder::new().seed(seed).build(cnf.clone()),
+            ];
+            for mut solver in solvers {
+                assert_eq!(solver.solve(), Satisfiability::SAT);
+                let model = solver.model().unwrap();
+                assert_eq!(cnf.clone().evaluate(&model), CNFValue::SAT);
+                assert!(solver.core().is_none());
+            }
+        }
+
+        //XORs: 1 ^ 2 ^ 3 and -1 ^ 2 with 1 v 3
+        let xors = CNF::from(read_dimacs_cnf("p cnf 3 3\nx1 2 3 0\nx-1 2 0\n1 3 0\n".as_bytes()).unwrap());
+        let mut solver = WalkSATSolverBuilder::new().seed(1).build(xors.clone());
+        assert_eq!(solver.solve(), Satisfiability::SAT);
+        assert_eq!(xors.evaluate(&solver.model().unwrap()), CNFValue::SAT);
+    }
+
+    #[test]
+    fn cached_break_counts() {
+        let mut rng = StdRng::seed_from_u64(9);
+        let cnf = CNF::from(read_dimacs_cnf("p cnf 6 7\n1 -2 3 0\n-1 2 0\n4 5 -6 0\n-3 -4 0\n2 6 0\nx1 4 5 0\nx-2 6 0\n".as_bytes()).unwrap());
+        let mut state = State::new(&cnf);
+        state.reset(&mut rng);
+        for _ in 0..200 {
+            let v = rng.gen_range(0..state.variables.len());
+            state.flip(v);
+
+            let model = state.model();
+            let falsified = |m: &Assignments| {
+                cnf.clauses().filter(|c| c.signed_literal().all(|l| l.evaluate(m) == LiteralValue::False)).count()
+                    + cnf.xors().filter(|x| matches!((*x).clone().evaluate(m), XorValue::False)).count()
+            };
+            let before = falsified(&model);
+            assert_eq!(state.falsified.len(), before);
+            for (u, variable) in state.variables.iter().enumerate() {
+                let mut flipped = model.clone();
+                flipped.assign(variable.clone(), model.get(variable).negate());
+                //Break counts only falsified constraints that were satisfied
+                let broken = cnf
+                    .clauses()
+                    .filter(|c| {
+                        c.signed_literal().all(|l| l.evaluate(&flipped) == LiteralValue::False)
+                            && c.signed_literal().any(|l| l.evaluate(&model) == LiteralValue::True)
+                    })
+                    .count()
+                    + cnf
+                        .xors()
+                        .filter(|x| x.signed_literal().any(|l| l.literal() == *variable))
+                        .filter(|x| matches!((*x).clone().evaluate(&model), XorValue::True))
+                        .count();
+                assert_eq!(state.breaks[u], broken);
+            }
+        }
+    }
+
+    #[test]
+    fn gives_up_on_unsat() {
+        let cnf = CNF::from(read_dimacs_cnf("p cnf 2 4\n1 2 0\n-1 2 0\n1 -2 0\n-1 -2 0\n".as_bytes()).unwrap());
+        let mut solver = WalkSATSolverBuilder::new().seed(1).max_flips(100).max_tries(3).build(cnf.clone());
+        assert_eq!(solver.solve(), Satisfiability::Unknown);
+        assert_eq!(solver.model(), None);
+        assert_eq!(solver.statistics().decisions, 300);
+
+        let mut solver = ProbSATSolverBuilder::new().limits(Limits::new().time(Duration::from_millis(50))).build(cnf);
+        assert_eq!(solver.solve(), Satisfiability::Unknown);
+    }
+}
@@ -1,35 +1,414 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    process::ExitCode,
+    thread,
+    time::Duration,
+};
 
-use sat_solver::{dimacs::parse_dimacs_cnf, definitions::CNF, sdpll::{DPLLSolverBuilder}, Solver, SolverBuilder, pdpll::{PDPLLSolverBuilder}};
-fn main() {
-    pretty_env_logger::init();
+use sat_solver::{
+    config::{self, SolverConfig},
+    cube::{write_icnf, CubeAndConquerSolverBuilder, Cuber, Cutoff},
+    definitions::{Assignments, Heuristic, Literal, LiteralValue, Satisfiability, SignedLiteral, CNF},
+    dimacs::{parse_dimacs_cnf, read_dimacs_cnf},
+    distributed::{DistributedSolverBuilder, Endpoint},
+    limits::Limits,
+    verify::verify_dimacs_model,
+    Solver, SolverBuilder,
+};
 
+const USAGE: &str = "Usage: sat-solver [OPTIONS] [FILE]
 
-    let files_path = "benchmarks/";
+Solves the DIMACS CNF formula in FILE, or on standard input if FILE is omitted or '-'.
+Prints the answer in SAT competition format and exits with 10 (SAT), 20 (UNSAT) or 0 (unknown).
 
-    //Get files in folder
-    let files = std::fs::read_dir(files_path).unwrap();
+Options:
+  --solver <dpll|pdpll|walksat|probsat|portfolio|cube>
+                            Solver to use (default: dpll), walksat and probsat never answer UNSAT,
+                            cube conquers the cubes of a lookahead split with dpll
+  --par-factor <N>          Number of decision levels pdpll splits in parallel (default: 4)
+  --threads <N>             Number of solvers portfolio and cube run in parallel, or of threads pdpll
+                            steals work between with --steal or searches cubes with --deterministic
+                            (default: number of cores, 8 for --deterministic without --steal)
+  --steal                   Let pdpll threads steal open branches from each other instead of splitting
+                            the top --par-factor levels
+  --deterministic           Make pdpll give the same answer, model and statistics on every run with the
+                            same --seed (default 0), --par-factor and --threads, unless the time limit is hit
+  --heuristic <mom|random>  Decision heuristic (default: mom)
+  --time-limit <SECONDS>    Give up after this many seconds
+  --conflict-limit <N>      Give up after this many conflicts
+  --seed <N>                Seed for random choices, printed with the statistics to replay a run
+                            (default: drawn at random)
+  --no-model                Do not print the model
+  --verify                  Check a SAT answer's model against the input before reporting it,
+                            exits with 1 listing the unsatisfied clauses if the check fails
+  --core <FILE>             On UNSAT, write the input clauses of an unsatisfiable core to FILE as DIMACS CNF
+  --cubes <FILE>            Split the formula into cubes, write them to FILE as iCNF and exit without solving
+  --cube-depth <N>          Maximum number of decisions in a cube (default: 8)
+  --workers <ADDR>[,<ADDR>...]
+                            Conquer the cubes on worker processes at these addresses, host:port or
+                            unix:<path>, each cube with the --solver configuration (see the worker binary)
+  -h, --help                Print this help";
 
-    //Iterate over files
-    let cnfs  = files.map(|file| {
-        let file_path = file.unwrap().path();
-        let file_path_str = file_path.to_str().unwrap();
-        CNF::from(parse_dimacs_cnf(file_path_str))
-    });
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+enum SolverKind {
+    DPLL,
+    PDPLL,
+    WalkSAT,
+    ProbSAT,
+    Portfolio,
+    CubeAndConquer,
+}
 
+impl SolverKind {
+    fn name(self) -> &'static str {
+        match self {
+            SolverKind::DPLL => "dpll",
+            SolverKind::PDPLL => "pdpll",
+            SolverKind::WalkSAT => "walksat",
+            SolverKind::ProbSAT => "probsat",
+            SolverKind::Portfolio => "portfolio",
+            SolverKind::CubeAndConquer => "cube",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Options {
+    file: Option<String>,
+    solver: SolverKind,
+    //None where the default applies
+    par_factor: Option<usize>,
+    threads: Option<usize>,
+    heuristic: Option<Heuristic>,
+    limits: Limits,
+    seed: Option<u64>,
+    print_model: bool,
+    verify: bool,
+    core: Option<String>,
+    cubes: Option<String>,
+    cube_depth: Option<usize>,
+    steal: bool,
+    deterministic: bool,
+    workers: Vec<Endpoint>,
+}
 
-    for cnf in cnfs {
-        let mut solvers : Vec<Box<dyn Solver>> = vec![];
-        solvers.push(DPLLSolverBuilder::new().build(cnf.clone()));
-        for i in 1..10 {
-            solvers.push(PDPLLSolverBuilder::new(i).build(cnf.clone()));
+fn parse_value<T: std::str::FromStr>(option: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("missing value for {}", option))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", option, value))
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        file: None,
+        solver: SolverKind::DPLL,
+        par_factor: None,
+        threads: None,
+        heuristic: None,
+        limits: Limits::new(),
+        seed: None,
+        print_model: true,
+        verify: false,
+        core: None,
+        cubes: None,
+        cube_depth: None,
+        steal: false,
+        deterministic: false,
+        workers: vec![],
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--solver" => {
+                options.solver = match parse_value::<String>(&arg, args.next())?.as_str() {
+                    "dpll" => SolverKind::DPLL,
+                    "pdpll" => SolverKind::PDPLL,
+                    "walksat" => SolverKind::WalkSAT,
+                    "probsat" => SolverKind::ProbSAT,
+                    "portfolio" => SolverKind::Portfolio,
+                    "cube" => SolverKind::CubeAndConquer,
+                    other => return Err(format!("unknown solver: {}", other)),
+                }
+            }
+            "--par-factor" => options.par_factor = Some(parse_value(&arg, args.next())?),
+            "--threads" => options.threads = Some(parse_value(&arg, args.next())?),
+            "--heuristic" => {
+                options.heuristic = match parse_value::<String>(&arg, args.next())?.as_str() {
+                    "mom" => Some(Heuristic::MOM),
+                    "random" => Some(Heuristic::Random),
+                    other => return Err(format!("unknown heuristic: {}", other)),
+                }
+            }
+            "--time-limit" => {
+                let seconds: f64 = parse_value(&arg, args.next())?;
+                if !(seconds >= 0.0 && seconds.is_finite()) {
+                    return Err(format!("invalid value for {}: {}", arg, seconds));
+                }
+                options.limits = options.limits.time(Duration::from_secs_f64(seconds));
+            }
+            "--conflict-limit" => options.limits = options.limits.conflicts(parse_value(&arg, args.next())?),
+            "--seed" => options.seed = Some(parse_value(&arg, args.next())?),
+            "--no-model" => options.print_model = false,
+            "--verify" => options.verify = true,
+            "--core" => options.core = Some(parse_value(&arg, args.next())?),
+            "--cubes" => options.cubes = Some(parse_value(&arg, args.next())?),
+            "--cube-depth" => options.cube_depth = Some(parse_value(&arg, args.next())?),
+            "--steal" => options.steal = true,
+            "--deterministic" => options.deterministic = true,
+            "--workers" => {
+                let addresses: String = parse_value(&arg, args.next())?;
+                options.workers = addresses.split(',').map(str::parse).collect::<Result<_, _>>()?;
+            }
+            "-" => options.file = None,
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+            _ => {
+                if options.file.is_some() {
+                    return Err(format!("unexpected argument: {}", arg));
+                }
+                options.file = Some(arg);
+            }
         }
+    }
+    check_options(&options)?;
+    Ok(Some(options))
+}
 
-        for mut solver in solvers {
-            println!("Satisfiability: {:?}", solver.solve());
+//Options that only apply to some solvers are rejected with the others, as in config.rs
+fn check_options(options: &Options) -> Result<(), String> {
+    let solver = options.solver;
+    let cubes = solver == SolverKind::CubeAndConquer || !options.workers.is_empty() || options.cubes.is_some();
+    let checks = [
+        ("--par-factor", options.par_factor.is_some(), solver == SolverKind::PDPLL),
+        ("--steal", options.steal, solver == SolverKind::PDPLL),
+        ("--deterministic", options.deterministic, solver == SolverKind::PDPLL),
+        (
+            "--threads",
+            options.threads.is_some(),
+            matches!(solver, SolverKind::Portfolio | SolverKind::CubeAndConquer) || options.steal || options.deterministic,
+        ),
+        (
+            "--heuristic",
+            options.heuristic.is_some(),
+            matches!(solver, SolverKind::DPLL | SolverKind::PDPLL | SolverKind::CubeAndConquer),
+        ),
+        ("--cube-depth", options.cube_depth.is_some(), cubes),
+    ];
+    if let Some((option, _, _)) = checks.iter().find(|(_, given, applies)| *given && !applies) {
+        return Err(format!("{} does not apply to --solver {}", option, solver.name()));
+    }
+    if options.cubes.is_some() && (solver != SolverKind::DPLL || !options.workers.is_empty()) {
+        return Err("--cubes exits without solving and takes no --solver or --workers".to_string());
+    }
+    Ok(())
+}
+
+fn cuber(options: &Options) -> Cuber {
+    Cuber::new().cutoff(Cutoff::Depth(options.cube_depth.unwrap_or(8)))
+}
+
+fn build_solver(options: &Options, cnf: CNF) -> Box<dyn Solver> {
+    let threads = || options.threads.unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+    let kind = match options.solver {
+        SolverKind::DPLL => config::SolverKind::DPLL,
+        SolverKind::PDPLL => config::SolverKind::PDPLL(options.par_factor.unwrap_or(4)),
+        SolverKind::WalkSAT => config::SolverKind::WalkSAT,
+        SolverKind::ProbSAT => config::SolverKind::ProbSAT,
+        SolverKind::Portfolio => config::SolverKind::Portfolio(threads()),
+        SolverKind::CubeAndConquer => config::SolverKind::DPLL,
+    };
+    let config = SolverConfig::new(kind)
+        .heuristic(options.heuristic.unwrap_or(Heuristic::MOM))
+        .limits(options.limits.clone());
+    let config = match options.seed {
+        Some(seed) => config.seed(seed),
+        None => config,
+    };
+    let config = if options.steal || (options.deterministic && options.threads.is_some()) {
+        config.stealing(threads())
+    } else {
+        config
+    };
+    let config = config.deterministic(options.deterministic);
+    if !options.workers.is_empty() {
+        let builder = DistributedSolverBuilder::new(config, options.workers.clone())
+            .cuber(cuber(options))
+            .limits(options.limits.clone());
+        return match options.seed {
+            Some(seed) => builder.seed(seed).build(cnf),
+            None => builder.build(cnf),
+        };
+    }
+    match options.solver {
+        SolverKind::CubeAndConquer => {
+            let builder = CubeAndConquerSolverBuilder::new(config)
+                .cuber(cuber(options))
+                .threads(threads())
+                .limits(options.limits.clone());
+            match options.seed {
+                Some(seed) => builder.seed(seed).build(cnf),
+                None => builder.build(cnf),
+            }
         }
+        _ => config.build(cnf),
     }
+}
 
+fn dimacs_line(literals: &[i32]) -> String {
+    let mut line: Vec<String> = literals.iter().map(|l| l.to_string()).collect();
+    line.push("0".to_string());
+    line.join(" ")
 }
 
+//Core clauses keep the DIMACS variable numbers of the input as literal names
+fn write_core<W: Write>(out: &mut W, core: &CNF, num_vars: usize) -> io::Result<()> {
+    writeln!(out, "p cnf {} {}", num_vars, core.clauses().count() + core.xors().count())?;
+    let dimacs = |l: &SignedLiteral| {
+        let var: i32 = l.literal().name().parse().unwrap();
+        match l {
+            SignedLiteral::Id(_) => var,
+            SignedLiteral::Not(_) => -var,
+        }
+    };
+    for clause in core.clauses() {
+        let literals: Vec<i32> = clause.signed_literal().map(dimacs).collect();
+        writeln!(out, "{}", dimacs_line(&literals))?;
+    }
+    for xor in core.xors() {
+        let mut literals: Vec<i32> = xor.signed_literal().map(dimacs).collect();
+        //XOR literals are stored positively with the negations folded into the parity
+        if !xor.parity() {
+            match literals.first_mut() {
+                Some(first) => *first = -*first,
+                None => continue,
+            }
+        }
+        writeln!(out, "x{}", dimacs_line(&literals))?;
+    }
+    Ok(())
+}
 
+//v-lines over variables 1..=num_vars; unassigned variables are reported false
+fn write_model<W: Write>(out: &mut W, model: &Assignments, num_vars: usize) -> io::Result<()> {
+    let mut line = String::from("v");
+    for var in 1..=num_vars {
+        let value = model.get(&Literal::new(var.to_string()));
+        let token = if value == LiteralValue::True {
+            format!(" {}", var)
+        } else {
+            format!(" -{}", var)
+        };
+        if line.len() + token.len() > 78 {
+            writeln!(out, "{}", line)?;
+            line = String::from("v");
+        }
+        line.push_str(&token);
+    }
+    writeln!(out, "{} 0", line)
+}
+
+fn main() -> ExitCode {
+    pretty_env_logger::init();
+
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            return ExitCode::from(1);
+        }
+    };
+
+    let dimacs_cnf = match options.file {
+        Some(ref file) => parse_dimacs_cnf(file),
+        None => read_dimacs_cnf(io::stdin().lock()),
+    };
+    let dimacs_cnf = match dimacs_cnf {
+        Ok(dimacs_cnf) => dimacs_cnf,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return ExitCode::from(1);
+        }
+    };
+    let num_vars = dimacs_cnf.num_vars();
+
+    //Keep the input as read for --verify, the solver only sees its own copy
+    let (cnf, input) = if options.verify {
+        (CNF::from(dimacs_cnf.clone()), Some(dimacs_cnf))
+    } else {
+        (CNF::from(dimacs_cnf), None)
+    };
+
+    if let Some(ref file) = options.cubes {
+        let cubes = cuber(&options).cubes(&cnf);
+        let result = File::create(file).and_then(|f| {
+            let mut out = BufWriter::new(f);
+            write_icnf(&mut out, &cnf, &cubes.cubes)?;
+            out.flush()
+        });
+        if let Err(error) = result {
+            eprintln!("error: {}", error);
+            return ExitCode::from(1);
+        }
+        println!("c {} cubes, {} refuted by lookahead", cubes.cubes.len(), cubes.refuted.len());
+        return ExitCode::SUCCESS;
+    }
+
+    let mut solver = build_solver(&options, cnf);
+    let satisfiability = solver.solve();
+
+    if let (Satisfiability::SAT, Some(input)) = (satisfiability, &input) {
+        if let Err(violations) = verify_dimacs_model(input, &solver.model().unwrap_or_default()) {
+            eprintln!("error: model verification failed");
+            for clause in violations.clauses {
+                eprintln!("unsatisfied clause: {}", dimacs_line(clause));
+            }
+            for xor in violations.xors {
+                eprintln!("unsatisfied xor: x{}", dimacs_line(xor));
+            }
+            return ExitCode::from(1);
+        }
+    }
 
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let result = (|| -> io::Result<()> {
+        for line in solver.statistics().to_string().lines() {
+            writeln!(out, "c {}", line)?;
+        }
+        match satisfiability {
+            Satisfiability::SAT => {
+                writeln!(out, "s SATISFIABLE")?;
+                if options.print_model {
+                    write_model(&mut out, &solver.model().unwrap_or_default(), num_vars)?;
+                }
+            }
+            Satisfiability::UNSAT => {
+                writeln!(out, "s UNSATISFIABLE")?;
+                if let (Some(ref file), Some(core)) = (&options.core, solver.core()) {
+                    let mut core_out = BufWriter::new(File::create(file)?);
+                    write_core(&mut core_out, &core, num_vars)?;
+                    core_out.flush()?;
+                }
+            }
+            Satisfiability::Unknown => writeln!(out, "s UNKNOWN")?,
+        }
+        out.flush()
+    })();
+    if let Err(error) = result {
+        eprintln!("error: {}", error);
+        return ExitCode::from(1);
+    }
+
+    match satisfiability {
+        Satisfiability::SAT => ExitCode::from(10),
+        Satisfiability::UNSAT => ExitCode::from(20),
+        Satisfiability::Unknown => ExitCode::SUCCESS,
+    }
+}
@@ -0,0 +1,323 @@
+//Weighted partial MaxSAT: satisfy every hard clause while minimizing the weight of falsified soft clauses
+//Linear SAT-UNSAT search tightens a pseudo-Boolean bound on relaxation literals until UNSAT
+//Fu-Malik/WPM1 relaxes the soft clauses of each unsatisfiable core (Ansótegui, Bonet & Levy, Solving (Weighted) Partial MaxSAT through Satisfiability Testing, 2009)
+use std::collections::HashSet;
+
+use crate::{
+    definitions::{Assignments, ClauseRef, LiteralPool, LiteralValue, RefLiteral, Satisfiability, SignedLiteral, CNF},
+    encodings::{self, AtMostOneEncoding},
+    pb::{self, PBComparator, PBConstraint, PBEncoding},
+    SolverBuilder,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaxSat {
+    hard: CNF,
+    soft: Vec<(u64, ClauseRef)>,
+}
+
+impl MaxSat {
+    pub fn new(hard: CNF) -> MaxSat {
+        MaxSat { hard, soft: vec![] }
+    }
+
+    //Soft clauses of weight 0 never contribute to the cost and are dropped
+    pub fn add_soft(mut self, weight: u64, clause: ClauseRef) -> Self {
+        if weight > 0 {
+            self.soft.push((weight, clause));
+        }
+        self
+    }
+
+    pub fn hard(&self) -> &CNF {
+        &self.hard
+    }
+
+    pub fn soft(&self) -> impl Iterator<Item = &(u64, ClauseRef)> {
+        self.soft.iter()
+    }
+
+    //Weight of the soft clauses the assignments do not satisfy
+    pub fn cost(&self, assignments: &Assignments) -> u64 {
+        self.soft
+            .iter()
+            .filter(|(_, c)| !c.signed_literal().any(|l| l.evaluate(assignments) == LiteralValue::True))
+            .map(|(w, _)| w)
+            .sum()
+    }
+
+    fn variables(&self) -> HashSet<RefLiteral> {
+        self.hard
+            .iter_literals()
+            .chain(self.soft.iter().flat_map(|(_, c)| c.signed_literal()))
+            .map(|l| l.literal())
+            .collect()
+    }
+
+    //Solvers leave variables unassigned when every completion satisfies the formula, so the soft
+    //clauses can be satisfied greedily through them
+    fn complete(&self, mut assignments: Assignments) -> Assignments {
+        for (_, c) in self.soft.iter() {
+            let values: Vec<(&SignedLiteral, LiteralValue)> =
+                c.signed_literal().map(|l| (l, l.evaluate(&assignments))).collect();
+            if !values.iter().any(|(_, v)| *v == LiteralValue::True) {
+                if let Some((l, _)) = values.iter().find(|(_, v)| *v == LiteralValue::Unassigned) {
+                    assignments.satisfy(l);
+                }
+            }
+        }
+        let variables = self.variables();
+        let mut model = Assignments::new();
+        for (literal, value) in assignments.iter() {
+            if variables.contains(literal) {
+                model.assign(literal.clone(), *value);
+            }
+        }
+        model
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaxSatAlgorithm {
+    LinearSatUnsat,
+    //WPM1 on weighted instances
+    FuMalik,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaxSatResult {
+    Optimum { model: Assignments, cost: u64 },
+    //A limit of the underlying solver was reached, the model is the best found so far
+    Feasible { model: Assignments, cost: u64 },
+    //The hard clauses are unsatisfiable
+    Unsatisfiable,
+    Unknown,
+}
+
+pub struct MaxSatSolver<B> {
+    builder: B,
+    algorithm: MaxSatAlgorithm,
+}
+
+impl<B: SolverBuilder + Clone> MaxSatSolver<B> {
+    //Every SAT call builds a fresh solver from a clone of the builder
+    pub fn new(builder: B) -> Self {
+        MaxSatSolver { builder, algorithm: MaxSatAlgorithm::FuMalik }
+    }
+
+    pub fn algorithm(mut self, algorithm: MaxSatAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn solve(&self, maxsat: &MaxSat) -> MaxSatResult {
+        match self.algorithm {
+            MaxSatAlgorithm::LinearSatUnsat => self.linear_sat_unsat(maxsat),
+            MaxSatAlgorithm::FuMalik => self.fu_malik(maxsat),
+        }
+    }
+
+    fn sat(&self, cnf: CNF) -> (Satisfiability, Option<Assignments>) {
+        let mut solver = self.builder.clone().build(cnf);
+        let satisfiability = solver.solve();
+        (satisfiability, solver.model())
+    }
+
+    fn linear_sat_unsat(&self, maxsat: &MaxSat) -> MaxSatResult {
+        let mut pool = LiteralPool::new("__maxsat");
+        let mut relaxed = maxsat.hard.clone();
+        let mut objective = vec![];
+        for (weight, clause) in maxsat.soft.iter() {
+            let r = pool.fresh();
+            relaxed = relaxed.add_clause(clause.signed_literal().cloned().chain([r.identity()]).collect());
+            objective.push((*weight as i64, r.identity()));
+        }
+
+        let mut best: Option<(Assignments, u64)> = None;
+        loop {
+            let cnf = match best {
+                Some((_, 0)) => break,
+                Some((_, cost)) => {
+                    let bound = PBConstraint::new(objective.clone(), PBComparator::LessEqual, cost as i64 - 1);
+                    pb::encode(relaxed.clone(), &bound, PBEncoding::GeneralizedTotalizer, &mut pool)
+                }
+                None => relaxed.clone(),
+            };
+            match self.sat(cnf) {
+                (Satisfiability::SAT, Some(model)) => {
+                    let model = maxsat.complete(model);
+                    let cost = maxsat.cost(&model);
+                    best = Some((model, cost));
+                }
+                (Satisfiability::Unknown, _) => {
+                    return match best {
+                        Some((model, cost)) => MaxSatResult::Feasible { model, cost },
+                        None => MaxSatResult::Unknown,
+                    };
+                }
+                _ => break,
+            }
+        }
+        match best {
+            Some((model, cost)) => MaxSatResult::Optimum { model, cost },
+            None => MaxSatResult::Unsatisfiable,
+        }
+    }
+
+    fn fu_malik(&self, maxsat: &MaxSat) -> MaxSatResult {
+        let mut pool = LiteralPool::new("__maxsat");
+        let mut hard = maxsat.hard.clone();
+        //Weight and current relaxed form of each soft clause, WPM1 splits clauses into copies
+        let mut soft: Vec<(u64, ClauseRef)> = maxsat.soft.clone();
+
+        loop {
+            let clauses: Vec<ClauseRef> = soft.iter().map(|(_, c)| c.clone()).collect();
+            let cnf = clauses.iter().fold(hard.clone(), |cnf, c| cnf.add_clause(c.clone()));
+            let mut solver = self.builder.clone().build(cnf);
+            let core = match solver.solve() {
+                Satisfiability::SAT => {
+                    let model = maxsat.complete(solver.model().unwrap_or_default());
+                    let cost = maxsat.cost(&model);
+                    return MaxSatResult::Optimum { model, cost };
+                }
+                Satisfiability::UNSAT => match solver.core() {
+                    Some(core) => {
+                        let ids: HashSet<usize> = core.clauses().map(|c| c.id()).collect();
+                        (0..clauses.len()).filter(|i| ids.contains(&clauses[*i].id())).collect()
+                    }
+                    None => match self.deletion_core(&hard, &clauses) {
+                        Some(core) => core,
+                        None => return MaxSatResult::Unknown,
+                    },
+                },
+                Satisfiability::Unknown => return MaxSatResult::Unknown,
+            };
+            if core.is_empty() {
+                return MaxSatResult::Unsatisfiable;
+            }
+
+            let weight = core.iter().map(|i| soft[*i].0).min().unwrap();
+            let mut blocking: Vec<SignedLiteral> = vec![];
+            for i in core {
+                let b = pool.fresh().identity();
+                let relaxed: ClauseRef = soft[i].1.signed_literal().cloned().chain([b.clone()]).collect();
+                if soft[i].0 > weight {
+                    soft[i].0 -= weight;
+                    soft.push((weight, relaxed));
+                } else {
+                    soft[i].1 = relaxed;
+                }
+                blocking.push(b);
+            }
+            hard = encodings::exactly_one(hard, &blocking, AtMostOneEncoding::SequentialCounter, &mut pool);
+        }
+    }
+
+    //Deletion-based core for solvers without cores: indices of a minimal subset of the clauses that is
+    //unsatisfiable together with the hard clauses, None if a limit was reached
+    fn deletion_core(&self, hard: &CNF, clauses: &[ClauseRef]) -> Option<Vec<usize>> {
+        let mut core: Vec<usize> = (0..clauses.len()).collect();
+        let mut i = 0;
+        while i < core.len() {
+            let candidate: Vec<usize> = core.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, c)| *c).collect();
+            let cnf = candidate.iter().fold(hard.clone(), |cnf, c| cnf.add_clause(clauses[*c].clone()));
+            match self.sat(cnf).0 {
+                Satisfiability::UNSAT => core = candidate,
+                Satisfiability::SAT => i += 1,
+                Satisfiability::Unknown => return None,
+            }
+        }
+        Some(core)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{definitions::Literal, sdpll::DPLLSolverBuilder, verify::verify_model, wcnf::read_wcnf};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    const ALGORITHMS: [MaxSatAlgorithm; 2] = [MaxSatAlgorithm::LinearSatUnsat, MaxSatAlgorithm::FuMalik];
+
+    fn solve(maxsat: &MaxSat, algorithm: MaxSatAlgorithm) -> MaxSatResult {
+        MaxSatSolver::new(DPLLSolverBuilder::new()).algorithm(algorithm).solve(maxsat)
+    }
+
+    fn brute_force(maxsat: &MaxSat, num_vars: usize) -> Option<u64> {
+        (0..1u32 << num_vars)
+            .filter_map(|bits| {
+                let mut m = Assignments::new();
+                for var in 0..num_vars {
+                    let value = if bits & (1 << var) != 0 { LiteralValue::True } else { LiteralValue::False };
+                    m.assign(Literal::new((var + 1).to_string()), value);
+                }
+                let feasible = maxsat
+                    .hard()
+                    .clauses()
+                    .all(|c| c.signed_literal().any(|l| l.evaluate(&m) == LiteralValue::True));
+                feasible.then(|| maxsat.cost(&m))
+            })
+            .min()
+    }
+
+    #[test]
+    fn weighted_partial() {
+        //x1 or x2, not both; x1 costs 3, x2 or x3 costs 5 unless x3
+        let maxsat = read_wcnf("h 1 2 0\nh -1 -2 0\n3 -1 0\n5 -2 0\n2 -3 0\n1 3 0\n".as_bytes()).unwrap().to_maxsat();
+        for algorithm in ALGORITHMS {
+            match solve(&maxsat, algorithm) {
+                MaxSatResult::Optimum { model, cost } => {
+                    assert_eq!(cost, 4);
+                    assert_eq!(maxsat.cost(&model), 4);
+                }
+                result => panic!("{:?}: {:?}", algorithm, result),
+            }
+        }
+
+        let infeasible = read_wcnf("h 1 0\nh -1 0\n1 2 0\n".as_bytes()).unwrap().to_maxsat();
+        for algorithm in ALGORITHMS {
+            assert_eq!(solve(&infeasible, algorithm), MaxSatResult::Unsatisfiable);
+        }
+    }
+
+    #[test]
+    fn random_against_brute_force() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let num_vars = 5;
+        for _ in 0..20 {
+            let mut wcnf = String::new();
+            let random_clause = |rng: &mut StdRng, len: usize| {
+                let literals: Vec<String> = (0..len)
+                    .map(|_| {
+                        let var = rng.gen_range(1..=num_vars) as i32;
+                        (if rng.gen_bool(0.5) { var } else { -var }).to_string()
+                    })
+                    .collect();
+                literals.join(" ")
+            };
+            for _ in 0..4 {
+                let clause = random_clause(&mut rng, 3);
+                wcnf.push_str(&format!("h {} 0\n", clause));
+            }
+            for _ in 0..8 {
+                let len = rng.gen_range(1..=2);
+                let clause = random_clause(&mut rng, len);
+                wcnf.push_str(&format!("{} {} 0\n", rng.gen_range(1..=4), clause));
+            }
+            let maxsat = read_wcnf(wcnf.as_bytes()).unwrap().to_maxsat();
+            let expected = brute_force(&maxsat, num_vars);
+
+            for algorithm in ALGORITHMS {
+                match (solve(&maxsat, algorithm), expected) {
+                    (MaxSatResult::Optimum { model, cost }, Some(expected)) => {
+                        assert_eq!(cost, expected, "{:?}\n{}", algorithm, wcnf);
+                        assert_eq!(maxsat.cost(&model), cost);
+                        assert!(verify_model(maxsat.hard(), &model).is_ok());
+                    }
+                    (MaxSatResult::Unsatisfiable, None) => {}
+                    (result, expected) => panic!("{:?}: {:?}, expected {:?}\n{}", algorithm, result, expected, wcnf),
+                }
+            }
+        }
+    }
+}
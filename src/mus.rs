@@ -0,0 +1,346 @@
+//Minimal unsatisfiable subsets: removing any clause (or group of clauses) of a MUS makes it satisfiable
+//Deletion-based extraction with model rotation (Marques-Silva & Lynce, On Improving MUS Extraction Algorithms, 2011)
+//QuickXplain (Junker, QUICKXPLAIN: Preferred Explanations and Relaxations for Over-Constrained Problems, 2004)
+//Clause-set refinement drops every clause outside the core of an UNSAT call
+use std::{cell::Cell, collections::HashSet};
+
+use crate::{
+    definitions::{Assignments, ClauseRef, LiteralValue, Satisfiability, CNF},
+    verify::verify_model,
+    SolverBuilder,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MusAlgorithm {
+    Deletion,
+    QuickXplain,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MusResult<T> {
+    Mus(T),
+    Satisfiable,
+    //A limit of the underlying solver was reached
+    Unknown,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+enum Outcome {
+    SAT(Assignments),
+    //Groups of the subset in the solver's core, all of them if it has none
+    UNSAT(Vec<usize>),
+    Unknown,
+}
+
+pub struct MusExtractor<B> {
+    builder: B,
+    algorithm: MusAlgorithm,
+    model_rotation: bool,
+    refinement: bool,
+    calls: Cell<usize>,
+}
+
+impl<B: SolverBuilder + Clone> MusExtractor<B> {
+    pub fn new(builder: B) -> Self {
+        MusExtractor {
+            builder,
+            algorithm: MusAlgorithm::Deletion,
+            model_rotation: true,
+            refinement: true,
+            calls: Cell::new(0),
+        }
+    }
+
+    pub fn algorithm(mut self, algorithm: MusAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    //Only used by the deletion-based algorithm
+    pub fn model_rotation(mut self, model_rotation: bool) -> Self {
+        self.model_rotation = model_rotation;
+        self
+    }
+
+    pub fn refinement(mut self, refinement: bool) -> Self {
+        self.refinement = refinement;
+        self
+    }
+
+    //Number of SAT calls made by the last extraction
+    pub fn solver_calls(&self) -> usize {
+        self.calls.get()
+    }
+
+    //XOR constraints are kept as they are, only clauses are removed
+    pub fn mus(&self, cnf: &CNF) -> MusResult<CNF> {
+        let hard = cnf.xors().fold(CNF::new(), |hard, x| hard.add_xor(x.clone()));
+        let groups: Vec<Vec<ClauseRef>> = cnf.clauses().map(|c| vec![c.clone()]).collect();
+        match self.group_mus(&hard, &groups) {
+            MusResult::Mus(mus) => MusResult::Mus(mus.iter().fold(hard, |mus_cnf, g| mus_cnf.add_clause(groups[*g][0].clone()))),
+            MusResult::Satisfiable => MusResult::Satisfiable,
+            MusResult::Unknown => MusResult::Unknown,
+        }
+    }
+
+    //Indices of a minimal set of groups that is unsatisfiable together with the hard clauses,
+    //in increasing order
+    pub fn group_mus(&self, hard: &CNF, groups: &[Vec<ClauseRef>]) -> MusResult<Vec<usize>> {
+        self.calls.set(0);
+        let all: Vec<usize> = (0..groups.len()).collect();
+        let working = match self.sat(hard, groups, &all) {
+            Outcome::SAT(_) => return MusResult::Satisfiable,
+            Outcome::UNSAT(core) => core,
+            Outcome::Unknown => return MusResult::Unknown,
+        };
+
+        let mus = match self.algorithm {
+            MusAlgorithm::Deletion => self.deletion(hard, groups, working),
+            MusAlgorithm::QuickXplain => match self.sat(hard, groups, &[]) {
+                Outcome::UNSAT(_) => Some(vec![]),
+                Outcome::SAT(_) => self.quick_xplain(hard, groups, &[], false, &working),
+                Outcome::Unknown => None,
+            },
+        };
+        match mus {
+            Some(mut mus) => {
+                mus.sort_unstable();
+                MusResult::Mus(mus)
+            }
+            None => MusResult::Unknown,
+        }
+    }
+
+    fn sat(&self, hard: &CNF, groups: &[Vec<ClauseRef>], subset: &[usize]) -> Outcome {
+        self.calls.set(self.calls.get() + 1);
+        let cnf = subset
+            .iter()
+            .flat_map(|g| groups[*g].iter())
+            .fold(hard.clone(), |cnf, c| cnf.add_clause(c.clone()));
+        let mut solver = self.builder.clone().build(cnf);
+        match solver.solve() {
+            Satisfiability::SAT => Outcome::SAT(solver.model().unwrap_or_default()),
+            Satisfiability::UNSAT => match solver.core() {
+                Some(core) if self.refinement => {
+                    let ids: HashSet<usize> = core.clauses().map(|c| c.id()).collect();
+                    Outcome::UNSAT(
+                        subset
+                            .iter()
+                            .filter(|g| groups[**g].iter().any(|c| ids.contains(&c.id())))
+                            .cloned()
+                            .collect(),
+                    )
+                }
+                _ => Outcome::UNSAT(subset.to_vec()),
+            },
+            Satisfiability::Unknown => Outcome::Unknown,
+        }
+    }
+
+    fn deletion(&self, hard: &CNF, groups: &[Vec<ClauseRef>], mut working: Vec<usize>) -> Option<Vec<usize>> {
+        //Groups known to be in every MUS of the working set
+        let mut necessary: HashSet<usize> = HashSet::new();
+        let mut candidates = working.clone();
+        while let Some(g) = candidates.pop() {
+            if necessary.contains(&g) || !working.contains(&g) {
+                continue;
+            }
+            let subset: Vec<usize> = working.iter().filter(|h| **h != g).cloned().collect();
+            match self.sat(hard, groups, &subset) {
+                Outcome::UNSAT(core) => working = core,
+                Outcome::SAT(model) => {
+                    necessary.insert(g);
+                    if self.model_rotation {
+                        let model = complete(model, hard, groups);
+                        self.rotate(hard, groups, &working, &mut necessary, g, model);
+                    }
+                }
+                Outcome::Unknown => return None,
+            }
+        }
+        Some(working)
+    }
+
+    //The model satisfies every working group but g: flipping a variable of g that leaves exactly one
+    //other group falsified proves that group necessary too, with the flipped model as witness
+    fn rotate(
+        &self,
+        hard: &CNF,
+        groups: &[Vec<ClauseRef>],
+        working: &[usize],
+        necessary: &mut HashSet<usize>,
+        g: usize,
+        model: Assignments,
+    ) {
+        let falsified = |m: &Assignments, c: &ClauseRef| !c.signed_literal().any(|l| l.evaluate(m) == LiteralValue::True);
+        let variables: HashSet<_> = groups[g]
+            .iter()
+            .filter(|c| falsified(&model, c))
+            .flat_map(|c| c.signed_literal().map(|l| l.literal()))
+            .collect();
+        for variable in variables {
+            let mut rotated = model.clone();
+            rotated.assign(variable.clone(), model.get(&variable).negate());
+            let mut unsatisfied = working.iter().filter(|h| groups[**h].iter().any(|c| falsified(&rotated, c)));
+            if let (Some(&h), None) = (unsatisfied.next(), unsatisfied.next()) {
+                if !necessary.contains(&h) && verify_model(hard, &rotated).is_ok() {
+                    necessary.insert(h);
+                    self.rotate(hard, groups, working, necessary, h, rotated);
+                }
+            }
+        }
+    }
+
+    //Minimal subset of candidates that is unsatisfiable together with background, assuming the whole is
+    fn quick_xplain(
+        &self,
+        hard: &CNF,
+        groups: &[Vec<ClauseRef>],
+        background: &[usize],
+        changed: bool,
+        candidates: &[usize],
+    ) -> Option<Vec<usize>> {
+        if changed {
+            match self.sat(hard, groups, background) {
+                Outcome::UNSAT(_) => return Some(vec![]),
+                Outcome::SAT(_) => {}
+                Outcome::Unknown => return None,
+            }
+        }
+        if candidates.len() == 1 {
+            return Some(candidates.to_vec());
+        }
+
+        let (first, second) = candidates.split_at(candidates.len() / 2);
+        let with_first: Vec<usize> = background.iter().chain(first.iter()).cloned().collect();
+        let second_part = self.quick_xplain(hard, groups, &with_first, !first.is_empty(), second)?;
+        let with_second: Vec<usize> = background.iter().chain(second_part.iter()).cloned().collect();
+        let first_part = self.quick_xplain(hard, groups, &with_second, !second_part.is_empty(), first)?;
+        Some(first_part.into_iter().chain(second_part).collect())
+    }
+}
+
+//Rotation needs a value for every variable, any completion of a solver's model satisfies the formula
+fn complete(mut model: Assignments, hard: &CNF, groups: &[Vec<ClauseRef>]) -> Assignments {
+    let literals = hard.iter_literals().chain(groups.iter().flatten().flat_map(|c| c.signed_literal()));
+    for l in literals {
+        if model.get(&l.literal()) == LiteralValue::Unassigned {
+            model.assign(l.literal(), LiteralValue::False);
+        }
+    }
+    model
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dimacs::read_dimacs_cnf, sdpll::DPLLSolverBuilder};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn extractors() -> Vec<MusExtractor<DPLLSolverBuilder>> {
+        let mut extractors = vec![];
+        for rotation in [false, true] {
+            for refinement in [false, true] {
+                extractors.push(
+                    MusExtractor::new(DPLLSolverBuilder::new())
+                        .model_rotation(rotation)
+                        .refinement(refinement),
+                );
+            }
+        }
+        for refinement in [false, true] {
+            extractors.push(
+                MusExtractor::new(DPLLSolverBuilder::new())
+                    .algorithm(MusAlgorithm::QuickXplain)
+                    .refinement(refinement),
+            );
+        }
+        extractors
+    }
+
+    fn assert_minimal(mus: &CNF) {
+        let solve = |cnf: CNF| DPLLSolverBuilder::new().build(cnf).solve();
+        assert_eq!(solve(mus.clone()), Satisfiability::UNSAT);
+        let clauses: Vec<&ClauseRef> = mus.clauses().collect();
+        for i in 0..clauses.len() {
+            let without = clauses
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .fold(CNF::new(), |cnf, (_, c)| cnf.add_clause((*c).clone()));
+            let without = mus.xors().fold(without, |cnf, x| cnf.add_xor(x.clone()));
+            assert_eq!(solve(without), Satisfiability::SAT);
+        }
+    }
+
+    #[test]
+    fn unique_mus() {
+        //1 ^ (-1 v 2) ^ -2 is the only MUS, the other clauses are satisfied by -3, 4
+        let cnf = CNF::from(read_dimacs_cnf("p cnf 4 6\n1 0\n-1 2 0\n-2 0\n3 4 0\n-3 0\n4 -3 0\n".as_bytes()).unwrap());
+        let expected = CNF::from(read_dimacs_cnf("p cnf 2 3\n1 0\n-1 2 0\n-2 0\n".as_bytes()).unwrap());
+        for (i, extractor) in extractors().into_iter().enumerate() {
+            match extractor.mus(&cnf) {
+                MusResult::Mus(mus) => {
+                    assert_eq!(mus.clauses().count(), 3, "extractor {}", i);
+                    assert!(mus.clauses().all(|c| expected.clauses().any(|e| e == c)));
+                }
+                result => panic!("{:?}", result),
+            }
+        }
+
+        let sat = CNF::from(read_dimacs_cnf("p cnf 2 2\n1 2 0\n-1 0\n".as_bytes()).unwrap());
+        assert_eq!(MusExtractor::new(DPLLSolverBuilder::new()).mus(&sat), MusResult::Satisfiable);
+    }
+
+    #[test]
+    fn random_minimal() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut dimacs = String::from("p cnf 8 60\n");
+        for _ in 0..60 {
+            for _ in 0..3 {
+                let var = rng.gen_range(1..=8);
+                dimacs.push_str(&format!("{} ", if rng.gen_bool(0.5) { var } else { -var }));
+            }
+            dimacs.push_str("0\n");
+        }
+        let cnf = CNF::from(read_dimacs_cnf(dimacs.as_bytes()).unwrap());
+        assert_eq!(DPLLSolverBuilder::new().build(cnf.clone()).solve(), Satisfiability::UNSAT);
+
+        let mut calls = vec![];
+        for extractor in extractors() {
+            match extractor.mus(&cnf) {
+                MusResult::Mus(mus) => assert_minimal(&mus),
+                result => panic!("{:?}", result),
+            }
+            calls.push(extractor.solver_calls());
+        }
+        //Rotation and refinement save calls of the deletion-based algorithm on this instance
+        assert!(calls[3] <= calls[0]);
+    }
+
+    #[test]
+    fn groups() {
+        //Requirements: "a" = {1, 2}, "b" = {-1 v -2}, "c" = {3}, "d" = {-3 v 1}; a and b conflict
+        let clauses = CNF::from(read_dimacs_cnf("p cnf 3 5\n1 0\n2 0\n-1 -2 0\n3 0\n-3 1 0\n".as_bytes()).unwrap());
+        let find = |literals: &str| -> ClauseRef {
+            let wanted = CNF::from(read_dimacs_cnf(format!("p cnf 3 1\n{} 0\n", literals).as_bytes()).unwrap());
+            let wanted = wanted.clauses().next().unwrap().clone();
+            clauses.clauses().find(|c| **c == wanted).unwrap().clone()
+        };
+        let groups = vec![
+            vec![find("1"), find("2")],
+            vec![find("-1 -2")],
+            vec![find("3")],
+            vec![find("-3 1")],
+        ];
+        for extractor in extractors() {
+            assert_eq!(extractor.group_mus(&CNF::new(), &groups), MusResult::Mus(vec![0, 1]));
+        }
+
+        //With -2 as a hard clause group a conflicts on its own
+        let hard = CNF::from(read_dimacs_cnf("p cnf 3 1\n-2 0\n".as_bytes()).unwrap());
+        for extractor in extractors() {
+            assert_eq!(extractor.group_mus(&hard, &groups), MusResult::Mus(vec![0]));
+        }
+    }
+}
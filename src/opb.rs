@@ -0,0 +1,212 @@
+//OPB (pseudo-Boolean competition) format parser, linear constraints only
+//https://www.cril.univ-artois.fr/PB12/format.pdf
+use std::fs::File;
+use std::io::{self, BufRead};
+
+use crate::{
+    definitions::{Literal, LiteralPool, SignedLiteral, CNF},
+    pb::{self, PBComparator, PBConstraint, PBEncoding},
+};
+
+pub struct Opb {
+    num_vars: usize,
+    objective: Option<Vec<(i64, SignedLiteral)>>,
+    constraints: Vec<PBConstraint>,
+}
+
+impl Opb {
+    pub fn num_vars(&self) -> usize {
+        self.num_vars
+    }
+
+    //Terms of the minimization objective, if any
+    pub fn objective(&self) -> Option<&Vec<(i64, SignedLiteral)>> {
+        self.objective.as_ref()
+    }
+
+    pub fn constraints(&self) -> impl Iterator<Item = &PBConstraint> {
+        self.constraints.iter()
+    }
+
+    //Variable xN becomes the literal named N, as for DIMACS; auxiliary literals come from the pool
+    pub fn to_cnf(&self, encoding: PBEncoding, pool: &mut LiteralPool) -> CNF {
+        self.constraints
+            .iter()
+            .fold(CNF::new(), |cnf, c| pb::encode(cnf, c, encoding, pool))
+    }
+}
+
+fn parse_literal(token: &str) -> Result<SignedLiteral, String> {
+    let (negated, name) = match token.strip_prefix('~') {
+        Some(name) => (true, name),
+        None => (false, token),
+    };
+    let index: usize = name
+        .strip_prefix('x')
+        .and_then(|index| index.parse().ok())
+        .filter(|index| *index > 0)
+        .ok_or_else(|| format!("invalid OPB variable: {}", token))?;
+    let literal = Literal::new(index.to_string());
+    Ok(if negated { literal.not() } else { literal.identity() })
+}
+
+fn parse_terms(tokens: &[&str]) -> Result<Vec<(i64, SignedLiteral)>, String> {
+    if !tokens.len().is_multiple_of(2) {
+        return Err(format!("invalid OPB terms (non-linear constraints are not supported): {}", tokens.join(" ")));
+    }
+    tokens
+        .chunks(2)
+        .map(|term| {
+            let coefficient = term[0]
+                .parse::<i64>()
+                .map_err(|_| format!("invalid OPB coefficient: {}", term[0]))?;
+            Ok((coefficient, parse_literal(term[1])?))
+        })
+        .collect()
+}
+
+//Errors on unreadable input, malformed statements and a statement missing its ';'
+pub fn read_opb<R: BufRead>(reader: R) -> Result<Opb, String> {
+    let mut opb = Opb { num_vars: 0, objective: None, constraints: vec![] };
+    let mut statement = String::new();
+
+    for line in reader.lines() {
+        let line = line.map_err(|error| error.to_string())?;
+        let line = line.trim();
+        if let Some(comment) = line.strip_prefix('*') {
+            //Header: * #variable= 5 #constraint= 4
+            let parts: Vec<&str> = comment.split_whitespace().collect();
+            if let Some(i) = parts.iter().position(|p| *p == "#variable=") {
+                opb.num_vars = parts.get(i + 1).and_then(|n| n.parse().ok()).unwrap_or(0);
+            }
+            continue;
+        }
+
+        //Statements end with ';' and may span several lines
+        for c in line.chars() {
+            if c == ';' {
+                parse_statement(&mut opb, &statement)?;
+                statement.clear();
+            } else {
+                statement.push(c);
+            }
+        }
+        statement.push(' ');
+    }
+    if !statement.trim().is_empty() {
+        return Err(format!("unterminated OPB statement: {}", statement.trim()));
+    }
+    Ok(opb)
+}
+
+fn parse_statement(opb: &mut Opb, statement: &str) -> Result<(), String> {
+    let statement = statement.trim();
+    if statement.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(objective) = statement.strip_prefix("min:") {
+        let tokens: Vec<&str> = objective.split_whitespace().collect();
+        opb.objective = Some(parse_terms(&tokens)?);
+        return Ok(());
+    }
+
+    let tokens: Vec<&str> = statement.split_whitespace().collect();
+    let position = tokens
+        .iter()
+        .position(|t| matches!(*t, ">=" | "<=" | "="))
+        .ok_or_else(|| format!("invalid OPB constraint: {}", statement))?;
+    let comparator = match tokens[position] {
+        ">=" => PBComparator::GreaterEqual,
+        "<=" => PBComparator::LessEqual,
+        _ => PBComparator::Equal,
+    };
+    let bound = match &tokens[position + 1..] {
+        [bound] => bound.parse::<i64>().ok(),
+        _ => None,
+    };
+    let bound = bound.ok_or_else(|| format!("invalid OPB bound: {}", statement))?;
+    let terms = parse_terms(&tokens[..position])?;
+    for (_, l) in terms.iter() {
+        let index: usize = l.literal().name().parse().unwrap();
+        opb.num_vars = opb.num_vars.max(index);
+    }
+    opb.constraints.push(PBConstraint::new(terms, comparator, bound));
+    Ok(())
+}
+
+pub fn parse_opb(file_path: &str) -> Result<Opb, String> {
+    let file = File::open(file_path).map_err(|error| format!("{}: {}", file_path, error))?;
+    read_opb(io::BufReader::new(file)).map_err(|error| format!("{}: {}", file_path, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        definitions::{Assignments, LiteralValue, Satisfiability},
+        sdpll::DPLLSolverBuilder,
+        SolverBuilder,
+    };
+
+    const INSTANCE: &str = "* #variable= 4 #constraint= 3
+* a comment
+min: +1 x1 +2 x2 -1 ~x3 ;
++1 x1 +1 x2
+  +1 x3 >= 2 ;
++2 x1 -3 ~x4 = -1 ;
+-1 x2 -1 x3 >= -1 ;
+";
+
+    #[test]
+    fn parse() {
+        let opb = read_opb(INSTANCE.as_bytes()).unwrap();
+        assert_eq!(opb.num_vars(), 4);
+        assert_eq!(opb.constraints().count(), 3);
+        assert_eq!(opb.objective().unwrap().len(), 3);
+        assert_eq!(opb.objective().unwrap()[2], (-1, Literal::new("3".to_string()).not()));
+
+        let c = opb.constraints().nth(1).unwrap();
+        assert_eq!(c.comparator(), PBComparator::Equal);
+        assert_eq!(c.bound(), -1);
+
+        //x1 = 1, x2 = 1, x3 = 0, x4 = 0 satisfies every constraint
+        let mut m = Assignments::new();
+        for (name, value) in [("1", true), ("2", true), ("3", false), ("4", false)] {
+            let value = if value { LiteralValue::True } else { LiteralValue::False };
+            m.assign(Literal::new(name.to_string()), value);
+        }
+        assert!(opb.constraints().all(|c| c.is_satisfied(&m)));
+    }
+
+    #[test]
+    fn to_cnf() {
+        let opb = read_opb(INSTANCE.as_bytes()).unwrap();
+        for encoding in [PBEncoding::Adder, PBEncoding::BDD, PBEncoding::GeneralizedTotalizer] {
+            let cnf = opb.to_cnf(encoding, &mut LiteralPool::default());
+            assert_eq!(DPLLSolverBuilder::new().build(cnf).solve(), Satisfiability::SAT);
+        }
+
+        let infeasible = read_opb("+1 x1 +1 x2 >= 2 ;\n+1 ~x1 +1 ~x2 >= 1 ;\n".as_bytes()).unwrap();
+        let cnf = infeasible.to_cnf(PBEncoding::GeneralizedTotalizer, &mut LiteralPool::default());
+        assert_eq!(DPLLSolverBuilder::new().build(cnf).solve(), Satisfiability::UNSAT);
+    }
+    #[test]
+    fn malformed_input() {
+        let malformed: [&[u8]; 8] = [
+            b"+1 x1 +1 y2 >= 1 ;\n",
+            b"+1 x0 >= 1 ;\n",
+            b"one x1 >= 1 ;\n",
+            b"+1 x1 x2 >= 1 ;\n",
+            b"+1 x1 +1 x2 1 ;\n",
+            b"+1 x1 >= one ;\n",
+            b"+1 x1 >= 1 2 ;\n",
+            b"+1 x1 >= 1\n",
+        ];
+        for opb in malformed {
+            assert!(read_opb(opb).is_err(), "{:?}", opb);
+        }
+        assert!(read_opb(b"+1 x1 >= 1 ;\n\xff\n".as_slice()).is_err());
+        assert!(matches!(parse_opb("benchmarks/missing.opb"), Err(error) if error.starts_with("benchmarks/missing.opb: ")));
+    }
+}
@@ -0,0 +1,387 @@
+//Linear pseudo-Boolean constraints sum(a_i * l_i) <op> k and their CNF encodings
+//Adder networks and BDDs: Eén & Sörensson, Translating Pseudo-Boolean Constraints into SAT (2006)
+//Generalized totalizer: Joshi, Martins & Manquinho, Generalized Totalizer Encoding for Pseudo-Boolean Constraints (2015)
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::{
+    definitions::{Assignments, LiteralPool, LiteralValue, RefLiteral, SignedLiteral, CNF},
+    formula::{CnfEncoder, CnfEncoding, FormulaBuilder, FormulaRef},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PBComparator {
+    GreaterEqual,
+    LessEqual,
+    Equal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PBEncoding {
+    Adder,
+    BDD,
+    GeneralizedTotalizer,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PBConstraint {
+    terms: Vec<(i64, SignedLiteral)>,
+    comparator: PBComparator,
+    bound: i64,
+}
+
+//sum(w_i * l_i) <= bound with positive weights and at most one term per variable
+#[derive(Debug, Clone, PartialEq)]
+struct AtMostConstraint {
+    terms: Vec<(u64, SignedLiteral)>,
+    bound: i64,
+}
+
+impl PBConstraint {
+    pub fn new(terms: Vec<(i64, SignedLiteral)>, comparator: PBComparator, bound: i64) -> PBConstraint {
+        PBConstraint { terms, comparator, bound }
+    }
+
+    pub fn terms(&self) -> impl Iterator<Item = &(i64, SignedLiteral)> {
+        self.terms.iter()
+    }
+
+    pub fn comparator(&self) -> PBComparator {
+        self.comparator
+    }
+
+    pub fn bound(&self) -> i64 {
+        self.bound
+    }
+
+    //Literals not assigned true count as false
+    pub fn is_satisfied(&self, assignments: &Assignments) -> bool {
+        let lhs: i64 = self
+            .terms
+            .iter()
+            .filter(|(_, l)| l.evaluate(assignments) == LiteralValue::True)
+            .map(|(a, _)| a)
+            .sum();
+        match self.comparator {
+            PBComparator::GreaterEqual => lhs >= self.bound,
+            PBComparator::LessEqual => lhs <= self.bound,
+            PBComparator::Equal => lhs == self.bound,
+        }
+    }
+
+    fn normalize(&self) -> Vec<AtMostConstraint> {
+        let at_most = |sign: i64| {
+            //Coefficient of the positive literal per variable, in first-seen order
+            let mut order: Vec<RefLiteral> = vec![];
+            let mut coefficients: HashMap<RefLiteral, i64> = HashMap::new();
+            let mut bound = sign * self.bound;
+            for (a, l) in self.terms.iter() {
+                let a = sign * a;
+                let entry = coefficients.entry(l.literal()).or_insert_with(|| {
+                    order.push(l.literal());
+                    0
+                });
+                match l {
+                    SignedLiteral::Id(_) => *entry += a,
+                    // a * ~x = a - a * x
+                    SignedLiteral::Not(_) => {
+                        *entry -= a;
+                        bound -= a;
+                    }
+                }
+            }
+
+            let mut terms = vec![];
+            for x in order {
+                match coefficients[&x] {
+                    0 => {}
+                    a if a > 0 => terms.push((a as u64, x.identity())),
+                    // a * x = a - |a| * ~x
+                    a => {
+                        terms.push((a.unsigned_abs(), x.not()));
+                        bound -= a;
+                    }
+                }
+            }
+            AtMostConstraint { terms, bound }
+        };
+
+        match self.comparator {
+            PBComparator::LessEqual => vec![at_most(1)],
+            PBComparator::GreaterEqual => vec![at_most(-1)],
+            PBComparator::Equal => vec![at_most(1), at_most(-1)],
+        }
+    }
+}
+
+pub fn encode(cnf: CNF, constraint: &PBConstraint, encoding: PBEncoding, pool: &mut LiteralPool) -> CNF {
+    constraint.normalize().into_iter().fold(cnf, |cnf, c| {
+        if c.bound < 0 {
+            return cnf.add_clause([].into_iter().collect());
+        }
+        let bound = c.bound as u64;
+        //Saturate weights above the bound, they force the literal false on their own
+        let mut terms: Vec<(u64, SignedLiteral)> = vec![];
+        let mut cnf = cnf;
+        for (w, l) in c.terms.into_iter() {
+            if w > bound {
+                cnf = cnf.add_clause([l.complement()].into_iter().collect());
+            } else {
+                terms.push((w, l));
+            }
+        }
+        if terms.iter().map(|(w, _)| w).sum::<u64>() <= bound {
+            return cnf;
+        }
+
+        match encoding {
+            PBEncoding::Adder => adder(cnf, &terms, bound, pool),
+            PBEncoding::BDD => bdd(cnf, terms, bound, pool),
+            PBEncoding::GeneralizedTotalizer => generalized_totalizer(cnf, &terms, bound, pool),
+        }
+    })
+}
+
+fn assert_formula(cnf: CNF, f: &FormulaRef, pool: &mut LiteralPool) -> CNF {
+    let mut encoder = CnfEncoder::new(CnfEncoding::PlaistedGreenbaum, pool);
+    encoder.assert(f);
+    encoder
+        .build()
+        .clauses()
+        .cloned()
+        .fold(cnf, |cnf, clause| cnf.add_clause(clause))
+}
+
+//Binary sum of the weighted literals by full and half adders, compared against the bound
+fn adder(cnf: CNF, terms: &[(u64, SignedLiteral)], bound: u64, pool: &mut LiteralPool) -> CNF {
+    let fb = FormulaBuilder::new();
+    let mut buckets: Vec<VecDeque<FormulaRef>> = vec![];
+    for (w, l) in terms.iter() {
+        let width = (u64::BITS - w.leading_zeros()) as usize;
+        for bit in 0..width {
+            if w & (1 << bit) != 0 {
+                if buckets.len() <= bit {
+                    buckets.resize(bit + 1, VecDeque::new());
+                }
+                buckets[bit].push_back(fb.literal(l.clone()));
+            }
+        }
+    }
+
+    let mut sum = vec![];
+    let mut bit = 0;
+    while bit < buckets.len() {
+        while buckets[bit].len() >= 2 {
+            let a = buckets[bit].pop_front().unwrap();
+            let b = buckets[bit].pop_front().unwrap();
+            let (s, carry) = if let Some(c) = buckets[bit].pop_front() {
+                let s = fb.xor(fb.xor(a.clone(), b.clone()), c.clone());
+                let carry = fb.or(vec![
+                    fb.and(vec![a.clone(), b.clone()]),
+                    fb.and(vec![a, c.clone()]),
+                    fb.and(vec![b, c]),
+                ]);
+                (s, carry)
+            } else {
+                (fb.xor(a.clone(), b.clone()), fb.and(vec![a, b]))
+            };
+            buckets[bit].push_back(s);
+            if buckets.len() <= bit + 1 {
+                buckets.push(VecDeque::new());
+            }
+            buckets[bit + 1].push_back(carry);
+        }
+        sum.push(buckets[bit].pop_front().unwrap_or_else(|| fb.constant(false)));
+        bit += 1;
+    }
+
+    //Compare from the least significant bit: sum[0..=j] <= bound[0..=j]
+    let mut le = fb.constant(true);
+    for (j, s) in sum.into_iter().enumerate() {
+        le = if j < 64 && bound & (1 << j) != 0 {
+            fb.or(vec![fb.not(s), le])
+        } else {
+            fb.and(vec![fb.not(s), le])
+        };
+    }
+    assert_formula(cnf, &le, pool)
+}
+
+//Reduced ordered BDD over the terms, heaviest first; node (i, k) holds iff sum of terms i.. is at most k
+fn bdd(cnf: CNF, mut terms: Vec<(u64, SignedLiteral)>, bound: u64, pool: &mut LiteralPool) -> CNF {
+    terms.sort_by_key(|(w, _)| std::cmp::Reverse(*w));
+    let mut suffix = vec![0u64; terms.len() + 1];
+    for i in (0..terms.len()).rev() {
+        suffix[i] = suffix[i + 1] + terms[i].0;
+    }
+
+    let fb = FormulaBuilder::new();
+    let mut memo: HashMap<(usize, u64), FormulaRef> = HashMap::new();
+    fn node(
+        i: usize,
+        k: u64,
+        terms: &[(u64, SignedLiteral)],
+        suffix: &[u64],
+        fb: &FormulaBuilder,
+        memo: &mut HashMap<(usize, u64), FormulaRef>,
+    ) -> FormulaRef {
+        if suffix[i] <= k {
+            return fb.constant(true);
+        }
+        if let Some(f) = memo.get(&(i, k)) {
+            return f.clone();
+        }
+        let (w, l) = &terms[i];
+        let high = if *w > k {
+            fb.constant(false)
+        } else {
+            node(i + 1, k - w, terms, suffix, fb, memo)
+        };
+        let low = node(i + 1, k, terms, suffix, fb, memo);
+        let f = fb.ite(fb.literal(l.clone()), high, low);
+        memo.insert((i, k), f.clone());
+        f
+    }
+
+    let root = node(0, bound, &terms, &suffix, &fb, &mut memo);
+    assert_formula(cnf, &root, pool)
+}
+
+//Each node maps every attainable partial sum (capped at bound + 1) to a literal implied by reaching it
+fn generalized_totalizer(cnf: CNF, terms: &[(u64, SignedLiteral)], bound: u64, pool: &mut LiteralPool) -> CNF {
+    fn tree(
+        cnf: CNF,
+        terms: &[(u64, SignedLiteral)],
+        cap: u64,
+        pool: &mut LiteralPool,
+    ) -> (CNF, BTreeMap<u64, SignedLiteral>) {
+        if terms.len() == 1 {
+            let (w, l) = terms[0].clone();
+            return (cnf, [(w.min(cap), l)].into_iter().collect());
+        }
+
+        let (left, right) = terms.split_at(terms.len() / 2);
+        let (cnf, a) = tree(cnf, left, cap, pool);
+        let (mut cnf, b) = tree(cnf, right, cap, pool);
+        let mut outputs: BTreeMap<u64, SignedLiteral> = BTreeMap::new();
+        let zero = [(0u64, None)];
+        let a_sums = zero.iter().cloned().chain(a.iter().map(|(w, l)| (*w, Some(l))));
+        for (wa, la) in a_sums {
+            let b_sums = zero.iter().cloned().chain(b.iter().map(|(w, l)| (*w, Some(l))));
+            for (wb, lb) in b_sums {
+                if wa + wb == 0 {
+                    continue;
+                }
+                let o = outputs
+                    .entry((wa + wb).min(cap))
+                    .or_insert_with(|| pool.fresh().identity())
+                    .clone();
+                // a_wa ^ b_wb -> o_(wa+wb)
+                let clause = [la, lb].into_iter().flatten().map(|l| l.complement()).chain([o]);
+                cnf = cnf.add_clause(clause.collect());
+            }
+        }
+        (cnf, outputs)
+    }
+
+    let cap = bound + 1;
+    let (cnf, outputs) = tree(cnf, terms, cap, pool);
+    match outputs.get(&cap) {
+        Some(o) => cnf.add_clause([o.complement()].into_iter().collect()),
+        None => cnf,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        definitions::{Literal, Satisfiability},
+        sdpll::DPLLSolverBuilder,
+        SolverBuilder,
+    };
+
+    const ENCODINGS: [PBEncoding; 3] = [PBEncoding::Adder, PBEncoding::BDD, PBEncoding::GeneralizedTotalizer];
+
+    //For every assignment to the inputs, the encoding must be SAT iff the constraint holds
+    fn check(constraint: &PBConstraint, inputs: &[RefLiteral]) {
+        for encoding in ENCODINGS {
+            for bits in 0..(1usize << inputs.len()) {
+                let mut assignments = Assignments::new();
+                let mut cnf = encode(CNF::new(), constraint, encoding, &mut LiteralPool::default());
+                for (i, x) in inputs.iter().enumerate() {
+                    let (l, value) = if bits & (1 << i) != 0 {
+                        (x.identity(), LiteralValue::True)
+                    } else {
+                        (x.not(), LiteralValue::False)
+                    };
+                    assignments.assign(x.clone(), value);
+                    cnf = cnf.add_clause([l].into_iter().collect());
+                }
+                let expected = if constraint.is_satisfied(&assignments) {
+                    Satisfiability::SAT
+                } else {
+                    Satisfiability::UNSAT
+                };
+                assert_eq!(
+                    DPLLSolverBuilder::new().build(cnf).solve(),
+                    expected,
+                    "{:?} {:?} bits={:b}",
+                    encoding,
+                    constraint,
+                    bits
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn pb_encodings() {
+        let x: Vec<RefLiteral> = (0..5).map(|i| Literal::new(format!("x{}", i))).collect();
+        let constraints = [
+            PBConstraint::new(
+                vec![(3, x[0].identity()), (2, x[1].identity()), (2, x[2].identity()), (1, x[3].identity())],
+                PBComparator::GreaterEqual,
+                4,
+            ),
+            PBConstraint::new(
+                vec![(5, x[0].identity()), (3, x[1].not()), (-2, x[2].identity()), (4, x[3].identity()), (1, x[4].identity())],
+                PBComparator::LessEqual,
+                6,
+            ),
+            PBConstraint::new(
+                vec![(1, x[0].identity()), (2, x[1].identity()), (3, x[2].identity()), (4, x[3].not())],
+                PBComparator::Equal,
+                5,
+            ),
+            PBConstraint::new(
+                vec![(2, x[0].identity()), (1, x[0].not()), (7, x[1].identity()), (3, x[2].identity())],
+                PBComparator::GreaterEqual,
+                9,
+            ),
+        ];
+        for constraint in constraints.iter() {
+            check(constraint, &x);
+        }
+    }
+
+    #[test]
+    fn trivial_constraints() {
+        let x: Vec<RefLiteral> = (0..2).map(|i| Literal::new(format!("x{}", i))).collect();
+        let infeasible = PBConstraint::new(
+            vec![(1, x[0].identity()), (1, x[1].identity())],
+            PBComparator::GreaterEqual,
+            3,
+        );
+        let tautology = PBConstraint::new(
+            vec![(1, x[0].identity()), (1, x[1].identity())],
+            PBComparator::LessEqual,
+            2,
+        );
+        for encoding in ENCODINGS {
+            let cnf = encode(CNF::new(), &infeasible, encoding, &mut LiteralPool::default());
+            assert_eq!(DPLLSolverBuilder::new().build(cnf).solve(), Satisfiability::UNSAT);
+            let cnf = encode(CNF::new(), &tautology, encoding, &mut LiteralPool::default());
+            assert_eq!(cnf.clauses().count(), 0);
+        }
+    }
+}
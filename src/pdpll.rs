@@ -1,52 +1,185 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
 use crate::{
+    cube::{Cuber, Cutoff},
     definitions::{
-        Assignments, CNFValue, LiteralValue, RefLiteral, Satisfiability, SignedLiteral, CNF,
+        Assignments, CNFValue, ClauseRef, ClauseValue, Conflict, Heuristic, LiteralValue, RefLiteral, Satisfiability,
+        SearchResult, SignedLiteral, CNF,
     },
+    gauss::{self, Elimination},
+    limits::Limits,
+    sharing::{ClauseBuffer, Sharing},
+    statistics::{Counters, Statistics},
+    trace::Trace,
     Solver, SolverBuilder,
 };
 use log::debug;
-use rand::{seq::IteratorRandom, thread_rng, Rng};
-use rayon::iter::{IntoParallelIterator, ParallelBridge, ParallelIterator};
+use rand::{rngs::StdRng, seq::IteratorRandom, Rng, SeedableRng};
+use rayon::iter::{ParallelBridge, ParallelIterator};
 
+#[derive(Clone)]
 pub struct PDPLLSolverBuilder {
     par_factor: usize,
+    heuristic: Heuristic,
+    limits: Limits,
+    seed: Option<u64>,
+    sharing: Option<Sharing>,
+    stealing: Option<usize>,
+    deterministic: bool,
 }
 
 impl PDPLLSolverBuilder {
     pub fn new(par_factor: usize) -> Self {
-        PDPLLSolverBuilder { par_factor }
+        PDPLLSolverBuilder {
+            par_factor,
+            heuristic: Heuristic::MOM,
+            limits: Limits::new(),
+            seed: None,
+            sharing: None,
+            stealing: None,
+            deterministic: false,
+        }
+    }
+
+    pub fn heuristic(mut self, heuristic: Heuristic) -> Self {
+        self.heuristic = heuristic;
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    //Seed for random decisions, drawn from entropy if not set
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    //Exchange learned clauses between the parallel branches
+    pub fn sharing(mut self, sharing: Sharing) -> Self {
+        self.sharing = Some(sharing);
+        self
+    }
+
+    //Search with this many threads that steal open branches from each other, instead of splitting the top
+    //par_factor levels
+    pub fn stealing(mut self, threads: usize) -> Self {
+        self.stealing = Some(threads);
+        self
+    }
+
+    //Same answer, model and statistics on every run with the same seed (0 if not set), par_factor and
+    //threads, as long as no time limit is hit. The top par_factor levels are split by the lookahead cuber,
+    //the cubes are searched by at most as many threads as given to stealing (ROUND_THREADS if not set),
+    //thread i of T taking cubes i, i + T, ... in turn, and the threads exchange clauses and answers at a
+    //barrier after every ROUND nodes. Replaces the stealing search
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    fn solver(self, formula: CNF) -> PDPLLSolver {
+        let seed = match self.seed {
+            Some(seed) => seed,
+            None if self.deterministic => 0,
+            None => rand::random(),
+        };
+        PDPLLSolver {
+            formula,
+            depth_par_factor: if self.stealing.is_some() || self.deterministic { 0 } else { self.par_factor },
+            cubing_depth: self.par_factor,
+            heuristic: self.heuristic,
+            limits: self.limits,
+            seed,
+            sharing: self.sharing,
+            buffer: None,
+            shared: 0,
+            stealing: if self.deterministic { None } else { self.stealing },
+            round_threads: self.stealing.unwrap_or(ROUND_THREADS).max(1),
+            deterministic: self.deterministic,
+            rounds: None,
+            pool: None,
+            steals: 0,
+            counters: Counters::default(),
+            start: Instant::now(),
+            elapsed: Duration::ZERO,
+            model: None,
+            trace: Trace::default(),
+            core: None,
+        }
     }
 }
+
 impl SolverBuilder for PDPLLSolverBuilder {
     fn build(self, formula: CNF) -> Box<dyn Solver> {
-        Box::new(PDPLLSolver {
-            formula,
-            depth_par_factor: self.par_factor,
-        })
+        Box::new(self.solver(formula))
     }
 }
 
 pub struct PDPLLSolver {
     formula: CNF,
     depth_par_factor: usize,
+    //Levels split by the cuber in deterministic mode
+    cubing_depth: usize,
+    heuristic: Heuristic,
+    limits: Limits,
+    seed: u64,
+    sharing: Option<Sharing>,
+    //Clauses published during the current solve
+    buffer: Option<ClauseBuffer>,
+    //Clauses published in the last solve
+    shared: usize,
+    stealing: Option<usize>,
+    //Open branches waiting for a thread during the current solve
+    pool: Option<Pool>,
+    //Branches taken by a thread other than the one that opened them in the last solve
+    steals: usize,
+    deterministic: bool,
+    //Threads searching the cubes in deterministic mode
+    round_threads: usize,
+    //Barrier between the rounds of the current deterministic solve
+    rounds: Option<Rounds>,
+    counters: Counters,
+    start: Instant,
+    elapsed: Duration,
+    model: Option<Assignments>,
+    trace: Trace,
+    core: Option<CNF>,
 }
 
 impl Solver for PDPLLSolver {
     fn solve(&mut self) -> Satisfiability {
         let func = |f: CNF| f.mom(1);
-        let vdi = if true {
-            VDI::LazyLiteralList(func)
-        } else {
-            VDI::RandomDecision
+        let vdi = match self.heuristic {
+            Heuristic::MOM => VDI::LazyLiteralList(func),
+            Heuristic::Random => VDI::RandomDecision,
         };
         let di = DI::<_>{
             remaining_depth: self.depth_par_factor,
             variable: vdi,
         };
 
+        self.start = Instant::now();
+        self.counters = Counters::default();
+        self.trace = Trace::default();
+        self.buffer = self.sharing.as_ref().map(|sharing| sharing.buffer());
+        //Each parallel branch reseeds its own generator from this one
+        let mut rng = StdRng::seed_from_u64(self.seed);
+
         //Pure literal elimination- if a literal l only appears as positive/negative, assign it to true/false
         //to strive for satisfiability
         let mut m = Assignments::new();
+        let mut worker = Worker::new(1);
         self.formula.pure_literals().iter().for_each(|l| {
             let value = match l {
                 SignedLiteral::Id(_) => LiteralValue::True,
@@ -54,27 +187,66 @@ impl Solver for PDPLLSolver {
             };
             debug!("Pure literal elimination: Literal {:?}, {:?}", l, value);
             m.assign(l.literal(), value);
+            worker.push(l.clone(), Reason::Assumed, 0);
         });
 
-        self.dpll_recursive(CNFValue::Formula(self.formula.clone()), m, di)
-            .unwrap()
+        let result = match self.stealing {
+            _ if self.deterministic => self.run_rounds(m, di, &mut rng, worker),
+            Some(threads) => self.steal(m, di, &mut rng, worker, threads),
+            None => self.dpll_recursive(CNFValue::Formula(self.formula.clone()), m, di, &mut rng, &mut worker),
+        };
+        self.shared = self.buffer.take().map_or(0, |buffer| buffer.len());
+        debug!("Shared clauses: {}", self.shared);
+        self.elapsed = self.start.elapsed();
+        let satisfiability = result.satisfiability();
+        self.core = (satisfiability == Satisfiability::UNSAT).then(|| self.trace.core(&self.formula));
+        self.model = match result {
+            SearchResult::SAT(model) => Some(model),
+            _ => None,
+        };
+        satisfiability
+    }
+
+    fn model(&self) -> Option<Assignments> {
+        self.model.clone()
+    }
+
+    fn core(&self) -> Option<CNF> {
+        self.core.clone()
+    }
+
+    fn statistics(&self) -> Statistics {
+        Statistics { seed: Some(self.seed), ..self.counters.statistics(self.elapsed) }
     }
 }
 
 #[derive(Debug,PartialEq)]
-struct DI<F: Fn(CNF)->Vec<RefLiteral>> {
+struct DI<F: Fn(CNF)->Vec<RefLiteral> + Copy> {
     variable: VDI<F>,
     remaining_depth: usize,
 }
 
 #[derive(Debug,PartialEq)]
+#[allow(dead_code, clippy::upper_case_acronyms)]
 //Next iteration decision instruction for choosing variable to assign
-enum VDI<F: Fn(CNF)->Vec<RefLiteral>> {
+enum VDI<F: Fn(CNF)->Vec<RefLiteral> + Copy> {
     RandomDecision,
     LiteralList(Vec<RefLiteral>),
     LazyLiteralList(F),
 }
 
+impl<F: Fn(CNF)->Vec<RefLiteral> + Copy> VDI<F> {
+    //Instruction for the decision after this one
+    fn next(&self) -> VDI<F> {
+        match self {
+            VDI::RandomDecision => VDI::RandomDecision,
+            VDI::LiteralList(l) if l.len() > 1 => VDI::LiteralList(l[1..].to_vec()),
+            VDI::LiteralList(_) => VDI::RandomDecision,
+            VDI::LazyLiteralList(func) => VDI::LazyLiteralList(*func),
+        }
+    }
+}
+
 
 
 impl PDPLLSolver {
@@ -82,125 +254,915 @@ impl PDPLLSolver {
     /*
         DPLL(F,m):
         Input: CNF, partial assigment m
-        Output: SAT/UNSAT
+        Output: SAT (with the assignments leading to it)/UNSAT, or Unknown once a limit is hit
     */
-    fn dpll_recursive<F: Fn(CNF) -> Vec<RefLiteral>>(
+    fn dpll_recursive<F: Fn(CNF) -> Vec<RefLiteral> + Copy + Sync>(
         &self,
         formula: CNFValue,
         m: Assignments,
         di: DI<F>,
-    ) -> CNFValue {
-        match formula.evaluate(&m) {
-            CNFValue::Formula(f) => {
+        rng: &mut StdRng,
+        worker: &mut Worker,
+    ) -> SearchResult {
+        if let Some(rounds) = &self.rounds {
+            //Limits are checked against the conflicts counted at the last barrier and by this worker since
+            worker.steps += 1;
+            if worker.steps.is_multiple_of(ROUND) && !rounds.sync(self, worker) {
+                return SearchResult::Unknown;
+            }
+            if self.limits.exceeded_at(self.start, worker.conflicts) {
+                return SearchResult::Unknown;
+            }
+        } else if self.limits.exceeded(self.start, &self.counters) {
+            return SearchResult::Unknown;
+        }
+        if let Some(pool) = &self.pool {
+            if pool.stop.load(Ordering::Relaxed) {
+                return SearchResult::Unknown;
+            }
+            if pool.hungry.load(Ordering::Relaxed) > 0 {
+                if let Some(path) = worker.guiding_path() {
+                    pool.give(path);
+                }
+            }
+        }
+        let formula = match self.import(formula, worker) {
+            Some(formula) => formula,
+            None => {
+                self.conflict(worker);
+                return SearchResult::UNSAT;
+            }
+        };
+
+        let result = match formula.evaluate_traced(&m) {
+            Ok(CNFValue::Formula(f)) => {
                 //Unit clause propogation- unit p becomes a unit literal for some clause
-                let m = f.clauses().find_map(|clause| {
+                let unit = f.clauses().find_map(|clause| {
                     if let Some(l) = clause.is_unit_clause() {
                         let value = match l {
                             SignedLiteral::Id(_) => LiteralValue::True,
                             SignedLiteral::Not(_) => LiteralValue::False,
                         };
                         debug!("Unit propogation: Clause {:?}, {:?}", clause, value);
-                        return Some(Assignments::new().assign(l.literal(), value).to_owned());
+                        self.trace.clause(clause);
+                        return Some((clause.origin(), l));
                     }
                     None
                 });
 
-                if let Some(m) = m {
-                    return self.dpll_recursive::<F>(
+                if let Some((reason, l)) = unit {
+                    self.counters.propagation();
+                    let mark = worker.mark();
+                    worker.push(l.clone(), Reason::Clause(reason), worker.level());
+                    let unit = Assignments::new().satisfy(&l).to_owned();
+                    let result = self.dpll_recursive::<F>(CNFValue::Formula(f.clone()), unit, di, rng, worker);
+                    worker.restore(mark);
+                    result
+                } else {
+                    self.decide(f, di, rng, worker)
+                }
+            }
+
+            //Base cases: if F is satisfied/unsatisfied by assignments
+            Ok(CNFValue::SAT) => SearchResult::SAT(Assignments::new()),
+            Ok(CNFValue::UNSAT) => unreachable!("evaluate_traced reports UNSAT as a conflict"),
+            Err(conflict) => {
+                self.trace.conflict(&conflict);
+                self.conflict(worker);
+                if let Conflict::Clause(clause) = conflict {
+                    self.learn(&clause, worker);
+                }
+                SearchResult::UNSAT
+            }
+        };
+
+        //Assignments made at this level are part of any model found below it
+        match result {
+            SearchResult::SAT(mut model) => {
+                model.extend(m);
+                SearchResult::SAT(model)
+            }
+            v => v,
+        }
+    }
+
+    fn decide<F: Fn(CNF) -> Vec<RefLiteral> + Copy + Sync>(
+        &self,
+        f: CNF,
+        di: DI<F>,
+        rng: &mut StdRng,
+        worker: &mut Worker,
+    ) -> SearchResult {
+        //XOR propogation- Gauss-Jordan elimination over the XOR constraints finds conflicts and implied units
+        if f.xors().next().is_some() {
+            match gauss::eliminate(f.xors()) {
+                Elimination::Conflict => {
+                    debug!("XOR conflict");
+                    self.trace.xors();
+                    self.conflict(worker);
+                    return SearchResult::UNSAT;
+                }
+                Elimination::Units(units) if !units.is_empty() => {
+                    self.trace.xors();
+                    let mut m = Assignments::new();
+                    let mark = worker.mark();
+                    for l in units.iter() {
+                        debug!("XOR propogation: {:?}", l);
+                        self.counters.propagation();
+                        m.satisfy(l);
+                        worker.push(l.clone(), Reason::Assumed, worker.level());
+                    }
+                    let result = self.dpll_recursive::<F>(CNFValue::Formula(f.clone()), m, di, rng, worker);
+                    worker.restore(mark);
+                    return result;
+                }
+                Elimination::Units(_) => {}
+            }
+        }
+
+        //Decision: If at this point in time we don't find a unit literal or reach a base case, let's make a decision
+        //First decision: Choose an unassigned literal p and a random bit b in {0,1} and check for satisfiability
+        let p = match di.variable {
+            VDI::RandomDecision => f.iter_literals()
+                .choose(rng).unwrap().literal(),
+            VDI::LiteralList(ref l) => {
+                if let Some(l) = l.first() {
+                    l.to_owned()
+                } else {
+                    f.iter_literals()
+                        .choose(rng).unwrap().literal()
+                }
+            },
+            VDI::LazyLiteralList(ref func) => {
+                if let Some(l) = func(f.clone()).first() {
+                    l.to_owned()
+                } else {
+                    f.iter_literals()
+                        .choose(rng).unwrap().literal()
+                }
+            },
+        };
+        self.counters.decision();
+        let level = worker.level() + 1;
+
+        if di.remaining_depth > 0 {
+            let seeds: [u64; 2] = [rng.gen(), rng.gen()];
+            let unknown = AtomicBool::new(false);
+            let parent: &Worker = worker;
+            Self::VALUES
+                .iter()
+                .zip(seeds)
+                .enumerate()
+                .par_bridge()
+                .find_map_any(|(i, (&value, seed))| {
+                    //Branches are workers of their own, numbered like the nodes of a binary heap
+                    let mut worker = parent.child(2 * parent.id + i);
+                    let decision = if value == LiteralValue::True { p.identity() } else { p.not() };
+                    worker.push(decision, Reason::Assumed, level);
+                    match self.dpll_recursive::<F>(
                         CNFValue::Formula(f.clone()),
-                        m,
+                        Assignments::new().assign(p.clone(), value).to_owned(),
                         DI::<F>{
-                            remaining_depth:di.remaining_depth,
-                            variable:VDI::RandomDecision,
+                            remaining_depth:di.remaining_depth-1,
+                            variable: di.variable.next(),
+                        },
+                        &mut StdRng::seed_from_u64(seed),
+                        &mut worker,
+                    ) {
+                        SearchResult::SAT(model) => {
+                            debug!("SAT: {:?}", &model);
+                            Some(SearchResult::SAT(model))
                         }
+                        SearchResult::Unknown => {
+                            unknown.store(true, Ordering::Relaxed);
+                            None
+                        }
+                        SearchResult::UNSAT => None,
+                    }
+                })
+                .unwrap_or_else(|| {
+                    if unknown.load(Ordering::Relaxed) {
+                        SearchResult::Unknown
+                    } else {
+                        SearchResult::UNSAT
+                    }
+                })
+        }
+        else {
+            let value = LiteralValue::True;
+            debug!("Set {:?} to {:?}", p, value);
+            let mark = worker.mark();
+            if self.pool.is_some() {
+                worker.open(p.not(), level);
+            }
+            worker.push(p.identity(), Reason::Assumed, level);
+            let result = self.dpll_recursive::<F>(
+                CNFValue::Formula(f.clone()),
+                Assignments::new().assign(p.clone(), value).to_owned(),
+                DI::<F>{
+                    remaining_depth:di.remaining_depth,
+                    variable: di.variable.next(),
+                },
+                rng,
+                worker,
+            );
+            worker.restore(mark);
+            let donated = self.pool.is_some() && worker.close();
+            match result {
+                //Another thread searches the second branch
+                SearchResult::UNSAT if donated => SearchResult::UNSAT,
+                // Let's backtrack in case the first decision doesn't work out
+                SearchResult::UNSAT => {
+                    worker.push(p.not(), Reason::Assumed, level);
+                    let result = self.dpll_recursive::<F>(
+                        CNFValue::Formula(f),
+                        Assignments::new()
+                            .assign(p.clone(), value.negate())
+                            .to_owned(),
+                        DI::<F>{
+                            remaining_depth:di.remaining_depth,
+                            variable: di.variable.next(),
+                        },
+                        rng,
+                        worker,
                     );
+                    worker.restore(mark);
+                    result
+                }
+                v => {
+                    debug!("{:?}", v.satisfiability());
+                    v
                 }
+            }
+        }
+    }
+}
 
-                //Decision: If at this point in time we don't find a unit literal or reach a base case, let's make a decision
-                //First decision: Choose an unassigned literal p and a random bit b in {0,1} and check for satisfiability
-                let p = match di.variable {
-                    VDI::RandomDecision => f.iter_literals()
-                        .choose(&mut thread_rng()).unwrap().literal(),
-                    VDI::LiteralList(ref l) => {
-                        if let Some(l) = l.first() {
-                            l.to_owned()
-                        } else {
-                            f.iter_literals()
-                                .choose(&mut thread_rng()).unwrap().literal()
-                        }
-                    },
-                    VDI::LazyLiteralList(ref func) => {
-                        if let Some(l) = func(f.clone()).first() {
-                            l.to_owned()
-                        } else {
-                            f.iter_literals()
-                                .choose(&mut thread_rng()).unwrap().literal()
+impl PDPLLSolver {
+    //Threads take guiding paths from the pool and search below them until every path is refuted or one
+    //of them finds a model. The root is the first path, busy threads give away the open branch closest
+    //to the root whenever a thread is waiting
+    fn steal<F: Fn(CNF) -> Vec<RefLiteral> + Copy + Sync>(
+        &mut self,
+        m: Assignments,
+        di: DI<F>,
+        rng: &mut StdRng,
+        root: Worker,
+        threads: usize,
+    ) -> SearchResult {
+        let threads = threads.max(1);
+        self.pool = Some(Pool::new(root.trail, threads));
+        let seeds: Vec<u64> = (0..threads).map(|_| rng.gen()).collect();
+        let model: Mutex<Option<Assignments>> = Mutex::new(None);
+        let (unknown, taken) = (AtomicBool::new(false), AtomicUsize::new(0));
+        let solver = &*self;
+        let pool = solver.pool.as_ref().unwrap();
+        thread::scope(|scope| {
+            for (id, seed) in seeds.into_iter().enumerate() {
+                let (m, di, model, unknown, taken) = (&m, &di, &model, &unknown, &taken);
+                scope.spawn(move || {
+                    let mut rng = StdRng::seed_from_u64(seed);
+                    while let Some(path) = pool.take() {
+                        taken.fetch_add(1, Ordering::Relaxed);
+                        let mut worker = Worker::new(id);
+                        let mut m = m.clone();
+                        for (literal, reason, level) in path {
+                            m.satisfy(&literal);
+                            worker.push(literal, reason, level);
                         }
-                    },
-                };
-
-                if di.remaining_depth > 0 {
-                    return Self::VALUES 
-                        .iter()
-                        .par_bridge()
-                        .find_map_any(|&value| {
-                            if self.dpll_recursive::<F>(
-                                CNFValue::Formula(f.clone()),
-                                Assignments::new().assign(p.clone(), value).to_owned(),
-                                DI::<F>{
-                                    remaining_depth:di.remaining_depth-1,
-                                    variable: VDI::RandomDecision,
-                                }
-                            ) == CNFValue::SAT
-                            {
-                                debug!("SAT: {:?}", &m);
-                                return Some(CNFValue::SAT);
-                            }
-                            else {
-                                None
+                        let di = DI { remaining_depth: 0, variable: di.variable.next() };
+                        let formula = CNFValue::Formula(solver.formula.clone());
+                        match solver.dpll_recursive::<F>(formula, m, di, &mut rng, &mut worker) {
+                            SearchResult::SAT(found) => {
+                                *model.lock().unwrap() = Some(found);
+                                pool.halt();
                             }
-                        })
-                        .unwrap_or(CNFValue::UNSAT);
-                }
-                else {
-                    let value = LiteralValue::True;
-                    debug!("Set {:?} to {:?}", p, value);
-                    if self.dpll_recursive::<F>(
-                        CNFValue::Formula(f.clone()),
-                        Assignments::new().assign(p.clone(), value).to_owned(),
-                        DI::<F>{
-                            remaining_depth:di.remaining_depth,
-                            variable: VDI::RandomDecision,
+                            SearchResult::Unknown => unknown.store(true, Ordering::Relaxed),
+                            SearchResult::UNSAT => {}
                         }
-                    ) == CNFValue::SAT
-                    {
-                        debug!("SAT: {:?}", &m);
-                        return CNFValue::SAT;
                     }
-                    // Let's backtrack in case the first decision doesn't work out
-                    else {
-                        return self.dpll_recursive::<F>(
-                            CNFValue::Formula(f.clone()),
-                            Assignments::new()
-                                .assign(p.clone(), value.negate())
-                                .to_owned(),
-                            DI::<F>{
-                                remaining_depth:di.remaining_depth,
-                                variable: VDI::RandomDecision,
-                            }
-                        );
+                });
+            }
+        });
+        self.pool = None;
+        self.steals = taken.into_inner().saturating_sub(1);
+        debug!("Stolen branches: {}", self.steals);
+        match model.into_inner().unwrap() {
+            Some(model) => SearchResult::SAT(model),
+            None if unknown.into_inner() => SearchResult::Unknown,
+            None => SearchResult::UNSAT,
+        }
+    }
+
+    fn conflict(&self, worker: &mut Worker) {
+        self.counters.conflict();
+        worker.conflicts += 1;
+    }
+
+    fn run_rounds<F: Fn(CNF) -> Vec<RefLiteral> + Copy + Sync>(
+        &mut self,
+        m: Assignments,
+        di: DI<F>,
+        rng: &mut StdRng,
+        root: Worker,
+    ) -> SearchResult {
+        let cubes = match self.formula.clone().evaluate(&m) {
+            CNFValue::Formula(f) => {
+                let cubes = Cuber::new().cutoff(Cutoff::Depth(self.cubing_depth)).cubes(&f);
+                //Cubes refuted by the lookahead are searched too, for the core
+                cubes.cubes.into_iter().chain(cubes.refuted).collect()
+            }
+            _ => vec![vec![]],
+        };
+        debug!("Deterministic search of {} cubes", cubes.len());
+        let paths: Vec<Path> = cubes
+            .into_iter()
+            .map(|cube| {
+                let decisions = cube.into_iter().enumerate().map(|(i, l)| (l, Reason::Assumed, i + 1));
+                root.trail.iter().cloned().chain(decisions).collect()
+            })
+            .collect();
+        let seeds: Vec<u64> = paths.iter().map(|_| rng.gen()).collect();
+        let threads = paths.len().min(self.round_threads);
+        self.rounds = Some(Rounds::new(threads));
+        let results: Vec<Mutex<Option<SearchResult>>> = paths.iter().map(|_| Mutex::new(None)).collect();
+        let (paths, seeds) = (&paths, &seeds);
+        let solver = &*self;
+        let rounds = solver.rounds.as_ref().unwrap();
+        thread::scope(|scope| {
+            for thread in 0..threads {
+                let (m, di, results) = (&m, &di, &results);
+                scope.spawn(move || {
+                    //Nodes and conflicts counted so far carry over from one cube of the thread to the next
+                    let (mut steps, mut conflicts) = (0, 0);
+                    for id in (thread..paths.len()).step_by(threads) {
+                        let mut worker = Worker::new(id);
+                        (worker.steps, worker.conflicts) = (steps, conflicts);
+                        let mut m = m.clone();
+                        for (literal, reason, level) in paths[id].iter().cloned() {
+                            m.satisfy(&literal);
+                            worker.push(literal, reason, level);
+                        }
+                        let di = DI { remaining_depth: 0, variable: di.variable.next() };
+                        let formula = CNFValue::Formula(solver.formula.clone());
+                        let mut rng = StdRng::seed_from_u64(seeds[id]);
+                        let result = solver.dpll_recursive::<F>(formula, m, di, &mut rng, &mut worker);
+                        let done = result != SearchResult::UNSAT;
+                        rounds.finish(&mut worker, matches!(result, SearchResult::SAT(_)));
+                        *results[id].lock().unwrap() = Some(result);
+                        (steps, conflicts) = (worker.steps, worker.conflicts);
+                        //The search stopped or found a model, the remaining cubes stay unsearched
+                        if done {
+                            break;
+                        }
                     }
+                    rounds.leave(solver);
+                });
+            }
+        });
+        self.rounds = None;
+
+        //The model of the first cube, in cube order, that has one. Unsearched cubes count as unknown
+        let results: Vec<SearchResult> = results
+            .into_iter()
+            .map(|r| r.into_inner().unwrap().unwrap_or(SearchResult::Unknown))
+            .collect();
+        let unknown = results.contains(&SearchResult::Unknown);
+        match results.into_iter().find(|r| matches!(r, SearchResult::SAT(_))) {
+            Some(sat) => sat,
+            None if unknown => SearchResult::Unknown,
+            None => SearchResult::UNSAT,
+        }
+    }
+
+    //Adds the clauses other workers published, simplified by the trail. Clauses with too many unassigned
+    //literals wait for a deeper node, and clauses removed from the formula by backtracking are added again
+    //at the next node, so every import holds for the rest of the worker's search
+    //None if one of them is falsified by the trail
+    fn import(&self, formula: CNFValue, worker: &mut Worker) -> Option<CNFValue> {
+        let (Some(sharing), Some(buffer)) = (&self.sharing, &self.buffer) else {
+            return Some(formula);
+        };
+        for shared in buffer.read(&mut worker.cursor) {
+            if shared.worker != worker.id {
+                worker.waiting.push(worker.imported.len());
+                worker.imported.push(shared.clause.clone());
+            }
+        }
+        let mut f = match formula {
+            CNFValue::Formula(f) if !worker.waiting.is_empty() => f,
+            formula => return Some(formula),
+        };
+
+        let assignments = worker.assignments();
+        let waiting = std::mem::take(&mut worker.waiting);
+        for (n, &i) in waiting.iter().enumerate() {
+            let clause = worker.imported[i].clone();
+            match clause.clone().evaluate(&assignments) {
+                ClauseValue::True => worker.integrated.push(i),
+                ClauseValue::False => {
+                    debug!("Imported clause falsified: {:?}", clause);
+                    worker.waiting.extend_from_slice(&waiting[n..]);
+                    self.trace.clause(&clause);
+                    self.learn(&clause, worker);
+                    return None;
+                }
+                ClauseValue::Clause(c) if sharing.imports(c.signed_literal().count()) => {
+                    f = f.add_clause(c);
+                    worker.integrated.push(i);
                 }
+                ClauseValue::Clause(_) => worker.waiting.push(i),
             }
+        }
+        Some(CNFValue::Formula(f))
+    }
 
-            //Base cases: if F is satisfied/unsatisfied by assignments
-            v => v,
+    //Resolves the falsified clause with the reasons on the trail until one literal of the last decision
+    //level is left (first UIP), and publishes the result if it is short or spans few decision levels
+    fn learn(&self, conflict: &ClauseRef, worker: &mut Worker) {
+        let (Some(sharing), Some(buffer)) = (&self.sharing, &self.buffer) else {
+            return;
+        };
+        let mut antecedents: HashSet<usize> = self.trace.antecedents(conflict).into_iter().collect();
+        //Literals of the clause, by the trail position of their variable
+        let mut learned: BTreeMap<usize, SignedLiteral> = BTreeMap::new();
+        for l in conflict.origin().signed_literal() {
+            learned.insert(*worker.positions.get(&l.literal()).unwrap(), l.clone());
+        }
+
+        //Levels grow along the trail, so the last literal of the clause is at the conflict level
+        let level = |position: &usize| worker.trail[*position].2;
+        let conflict_level = learned.keys().last().map_or(0, level);
+        while learned.keys().filter(|p| level(p) == conflict_level).count() > 1 {
+            let (position, l) = learned.pop_last().unwrap();
+            match &worker.trail[position].1 {
+                Reason::Assumed => {
+                    learned.insert(position, l);
+                    break;
+                }
+                Reason::Clause(reason) => {
+                    antecedents.extend(self.trace.antecedents(reason));
+                    for k in reason.signed_literal().filter(|k| k.literal() != l.literal()) {
+                        learned.insert(*worker.positions.get(&k.literal()).unwrap(), k.clone());
+                    }
+                }
+            }
+        }
+        let levels: HashSet<usize> = learned.keys().map(level).collect();
+        let learned: Vec<SignedLiteral> = learned.into_values().collect();
+
+        if sharing.exports(learned.len(), levels.len()) {
+            let clause: ClauseRef = learned.into_iter().collect();
+            debug!("Learned clause: {:?}", clause);
+            self.trace.derive(&clause, antecedents.into_iter().collect());
+            match self.rounds {
+                Some(_) => worker.outbox.push(clause),
+                None => {
+                    buffer.publish(worker.id, clause);
+                }
+            }
         }
     }
 }
 
+#[derive(Debug, Clone)]
+enum Reason {
+    //Decisions, pure literals and XOR propagations
+    Assumed,
+    //Input or learned clause that became unit
+    Clause(ClauseRef),
+}
+
+//Decisions, propagations and the rest of a trail, with the reason and decision level of each literal
+type Path = Vec<(SignedLiteral, Reason, usize)>;
+
+//Second branch of a decision on the trail, not searched yet
+#[derive(Debug, Clone)]
+struct Open {
+    //Trail length at the decision
+    length: usize,
+    literal: SignedLiteral,
+    level: usize,
+    //Given away to another thread
+    donated: bool,
+}
+
+//The path from the root to the current node of a branch, for learning clauses and importing them
+#[derive(Debug, Clone)]
+struct Worker {
+    id: usize,
+    //Literal, why it holds and its decision level
+    trail: Path,
+    positions: HashMap<RefLiteral, usize>,
+    //Next clause of the shared buffer to read
+    cursor: usize,
+    //Clauses read from the shared buffer, kept for the whole search
+    imported: Vec<ClauseRef>,
+    //Imported clauses added to the formula of the current node or satisfied by the trail, in that order
+    integrated: Vec<usize>,
+    //Imported clauses not integrated at the current node
+    waiting: Vec<usize>,
+    //Only kept when stealing
+    open: Vec<Open>,
+    //Deterministic mode: nodes searched, conflicts as of the last barrier and clauses learned since
+    steps: usize,
+    conflicts: u64,
+    outbox: Vec<ClauseRef>,
+}
+
+impl Worker {
+    fn new(id: usize) -> Self {
+        Worker {
+            id,
+            trail: vec![],
+            positions: HashMap::new(),
+            cursor: 0,
+            imported: vec![],
+            integrated: vec![],
+            waiting: vec![],
+            open: vec![],
+            steps: 0,
+            conflicts: 0,
+            outbox: vec![],
+        }
+    }
+
+    fn child(&self, id: usize) -> Self {
+        Worker { id, ..self.clone() }
+    }
+
+    fn level(&self) -> usize {
+        self.trail.last().map_or(0, |(_, _, level)| *level)
+    }
+
+    fn push(&mut self, literal: SignedLiteral, reason: Reason, level: usize) {
+        self.positions.insert(literal.literal(), self.trail.len());
+        self.trail.push((literal, reason, level));
+    }
+
+    //State to restore when backtracking to the current node
+    fn mark(&self) -> (usize, usize) {
+        (self.trail.len(), self.integrated.len())
+    }
+
+    //Imported clauses integrated below the node wait to be added again
+    fn restore(&mut self, (length, integrated): (usize, usize)) {
+        for (literal, _, _) in self.trail.drain(length..) {
+            self.positions.remove(&literal.literal());
+        }
+        let undone = self.integrated.drain(integrated..);
+        self.waiting.extend(undone);
+    }
+
+    //Called before the first branch of a decision is searched
+    fn open(&mut self, literal: SignedLiteral, level: usize) {
+        self.open.push(Open { length: self.trail.len(), literal, level, donated: false });
+    }
+
+    //Called once the first branch is done, true if the second one was given away
+    fn close(&mut self) -> bool {
+        self.open.pop().is_some_and(|open| open.donated)
+    }
+
+    //Path to the open branch closest to the root, which is no longer searched by this worker
+    fn guiding_path(&mut self) -> Option<Path> {
+        let open = self.open.iter_mut().find(|open| !open.donated)?;
+        open.donated = true;
+        let mut path = self.trail[..open.length].to_vec();
+        path.push((open.literal.clone(), Reason::Assumed, open.level));
+        Some(path)
+    }
+
+    fn assignments(&self) -> Assignments {
+        let mut assignments = Assignments::new();
+        for (literal, _, _) in self.trail.iter() {
+            assignments.satisfy(literal);
+        }
+        assignments
+    }
+}
+
+//Guiding paths given away by busy threads, taken by idle ones
+struct Pool {
+    state: Mutex<PoolState>,
+    available: Condvar,
+    threads: usize,
+    //Threads waiting for a path that is not in the pool yet
+    hungry: AtomicUsize,
+    //Set once a model is found
+    stop: AtomicBool,
+}
+
+struct PoolState {
+    paths: Vec<Path>,
+    idle: usize,
+    done: bool,
+}
+
+impl Pool {
+    fn new(root: Path, threads: usize) -> Self {
+        Pool {
+            state: Mutex::new(PoolState { paths: vec![root], idle: 0, done: false }),
+            available: Condvar::new(),
+            threads,
+            hungry: AtomicUsize::new(0),
+            stop: AtomicBool::new(false),
+        }
+    }
+
+    //Waits for a path, None once all threads are idle with an empty pool or a model was found
+    fn take(&self) -> Option<Path> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.done || self.stop.load(Ordering::Relaxed) {
+                return None;
+            }
+            if let Some(path) = state.paths.pop() {
+                return Some(path);
+            }
+            state.idle += 1;
+            if state.idle == self.threads {
+                state.done = true;
+                self.available.notify_all();
+                return None;
+            }
+            self.hungry.store(state.idle, Ordering::Relaxed);
+            state = self.available.wait(state).unwrap();
+            state.idle -= 1;
+            self.hungry.store(state.idle.saturating_sub(state.paths.len()), Ordering::Relaxed);
+        }
+    }
+
+    fn give(&self, path: Path) {
+        let mut state = self.state.lock().unwrap();
+        state.paths.push(path);
+        self.hungry.store(state.idle.saturating_sub(state.paths.len()), Ordering::Relaxed);
+        self.available.notify_one();
+    }
+
+    fn halt(&self) {
+        let _state = self.state.lock().unwrap();
+        self.stop.store(true, Ordering::Relaxed);
+        self.available.notify_all();
+    }
+}
+
+//Nodes a worker searches between two barriers in deterministic mode
+const ROUND: usize = 256;
+//Threads searching the cubes in deterministic mode unless set by stealing, fixed so the answer does not
+//depend on the machine
+const ROUND_THREADS: usize = 8;
+
+//Barrier of the deterministic mode between the threads searching the cubes. The last thread to arrive
+//publishes the clauses learned during the round in cube order and decides whether the search goes on, so
+//what every thread sees after the barrier only depends on the work done before it
+struct Rounds {
+    state: Mutex<RoundState>,
+    next: Condvar,
+}
+
+struct RoundState {
+    //Threads still searching
+    active: usize,
+    arrived: usize,
+    round: u64,
+    outboxes: BTreeMap<usize, Vec<ClauseRef>>,
+    conflicts: u64,
+    //A model was found during the round, the search stops at the next barrier
+    found: bool,
+    stop: bool,
+}
+
+impl Rounds {
+    fn new(workers: usize) -> Self {
+        Rounds {
+            state: Mutex::new(RoundState {
+                active: workers,
+                arrived: 0,
+                round: 0,
+                outboxes: BTreeMap::new(),
+                conflicts: 0,
+                found: false,
+                stop: false,
+            }),
+            next: Condvar::new(),
+        }
+    }
+
+    //Waits for the other workers, false if the search is over
+    fn sync(&self, solver: &PDPLLSolver, worker: &mut Worker) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.outboxes.insert(worker.id, std::mem::take(&mut worker.outbox));
+        state.arrived += 1;
+        let round = state.round;
+        if state.arrived == state.active {
+            Self::advance(&mut state, solver);
+            self.next.notify_all();
+        } else {
+            while state.round == round {
+                state = self.next.wait(state).unwrap();
+            }
+        }
+        worker.conflicts = state.conflicts;
+        !state.stop
+    }
+
+    //Called by a thread when the search of one of its cubes returns
+    fn finish(&self, worker: &mut Worker, found: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.outboxes.insert(worker.id, std::mem::take(&mut worker.outbox));
+        state.found |= found;
+    }
+
+    //Called once by every thread when it has no cube left to search
+    fn leave(&self, solver: &PDPLLSolver) {
+        let mut state = self.state.lock().unwrap();
+        state.active -= 1;
+        if state.active > 0 && state.arrived == state.active {
+            Self::advance(&mut state, solver);
+            self.next.notify_all();
+        }
+    }
+
+    fn advance(state: &mut RoundState, solver: &PDPLLSolver) {
+        if let Some(buffer) = &solver.buffer {
+            for (id, clauses) in std::mem::take(&mut state.outboxes) {
+                for clause in clauses {
+                    buffer.publish(id, clause);
+                }
+            }
+        }
+        state.conflicts = solver.counters.conflicts();
+        state.stop |= state.found;
+        state.stop |= solver.limits.exceeded_at(solver.start, state.conflicts);
+        state.arrived = 0;
+        state.round += 1;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     crate::tests::sat_tests!(PDPLLSolverBuilder::new(4));
+
+    mod sharing {
+        use super::*;
+        crate::tests::sat_tests!(PDPLLSolverBuilder::new(2).sharing(Sharing::new()));
+    }
+
+    mod stealing {
+        use super::*;
+        crate::tests::sat_tests!(PDPLLSolverBuilder::new(4).stealing(4));
+    }
+
+    mod deterministic {
+        use super::*;
+        crate::tests::sat_tests!(PDPLLSolverBuilder::new(3).deterministic(true));
+    }
+
+    #[test]
+    fn deterministic_runs() {
+        use crate::dimacs::read_dimacs_cnf;
+        //More cubes than threads from the third on
+        for (instance, heuristic, par_factor, threads) in [
+            (include_str!("../benchmarks/uf75-01.cnf"), Heuristic::MOM, 3, None),
+            (include_str!("../benchmarks/uuf50-01.cnf"), Heuristic::Random, 3, None),
+            (include_str!("../benchmarks/uf75-01.cnf"), Heuristic::MOM, 6, None),
+            (include_str!("../benchmarks/uuf50-01.cnf"), Heuristic::MOM, 6, None),
+            (include_str!("../benchmarks/uf75-01.cnf"), Heuristic::MOM, 4, Some(2)),
+            (include_str!("../benchmarks/uuf50-01.cnf"), Heuristic::Random, 4, Some(3)),
+        ] {
+            let cnf = CNF::from(read_dimacs_cnf(instance.as_bytes()).unwrap());
+            let mut builder = PDPLLSolverBuilder::new(par_factor)
+                .heuristic(heuristic)
+                .sharing(Sharing::new())
+                .deterministic(true)
+                .seed(7);
+            if let Some(threads) = threads {
+                builder = builder.stealing(threads);
+            }
+            let runs: Vec<_> = (0..3)
+                .map(|_| {
+                    let mut solver = builder.clone().build(cnf.clone());
+                    let satisfiability = solver.solve();
+                    let statistics = solver.statistics();
+                    let counts = (statistics.decisions, statistics.propagations, statistics.conflicts);
+                    let mut model: Vec<(String, LiteralValue)> = solver
+                        .model()
+                        .map(|m| m.iter().map(|(l, v)| (l.name().to_string(), *v)).collect())
+                        .unwrap_or_default();
+                    model.sort_by(|a, b| a.0.cmp(&b.0));
+                    (satisfiability, counts, model, solver.core().map(|c| c.clauses().count()))
+                })
+                .collect();
+            assert!(runs.iter().all(|run| *run == runs[0]));
+        }
+    }
+
+    #[test]
+    fn stolen_branches() {
+        use crate::{definitions::CNFValue, dimacs::read_dimacs_cnf, sdpll::DPLLSolverBuilder};
+        let cnf = CNF::from(read_dimacs_cnf(include_str!("../benchmarks/uuf50-01.cnf").as_bytes()).unwrap());
+        //Whether branches are stolen depends on the timing of the threads, the answer does not
+        let mut solver = PDPLLSolverBuilder::new(4).stealing(4).solver(cnf.clone());
+        assert_eq!(solver.solve(), Satisfiability::UNSAT);
+        let core = solver.core().unwrap();
+        assert_eq!(DPLLSolverBuilder::new().build(core).solve(), Satisfiability::UNSAT);
+
+        let mut solver = PDPLLSolverBuilder::new(4).stealing(4).sharing(Sharing::new()).solver(cnf);
+        assert_eq!(solver.solve(), Satisfiability::UNSAT);
+
+        let cnf = CNF::from(read_dimacs_cnf(include_str!("../benchmarks/uf75-01.cnf").as_bytes()).unwrap());
+        let mut solver = PDPLLSolverBuilder::new(4).stealing(3).build(cnf.clone());
+        assert_eq!(solver.solve(), Satisfiability::SAT);
+        assert_eq!(cnf.evaluate(&solver.model().unwrap()), CNFValue::SAT);
+    }
+
+    #[test]
+    fn guiding_paths() {
+        use crate::definitions::Literal;
+        let [a, b] = ["a", "b"].map(|v| Literal::new(v.to_string()));
+        let pool = Pool::new(vec![], 1);
+        assert_eq!(pool.take().map(|path| path.len()), Some(0));
+
+        //Branches are given away from the root down, each once
+        let mut worker = Worker::new(0);
+        worker.open(a.not(), 1);
+        worker.push(a.identity(), Reason::Assumed, 1);
+        worker.open(b.not(), 2);
+        worker.push(b.identity(), Reason::Assumed, 2);
+        let literals = |path: Path| path.into_iter().map(|(l, _, level)| (l, level)).collect::<Vec<_>>();
+        let first = worker.guiding_path().unwrap();
+        assert_eq!(literals(first.clone()), [(a.not(), 1)]);
+        assert_eq!(literals(worker.guiding_path().unwrap()), [(a.identity(), 1), (b.not(), 2)]);
+        assert!(worker.guiding_path().is_none());
+        assert!(worker.close() && worker.close());
+
+        //A thread takes the path, and once every thread is idle with an empty pool the search is over
+        pool.give(first);
+        assert_eq!(pool.take().map(literals), Some(vec![(a.not(), 1)]));
+        assert!(pool.take().is_none());
+    }
+
+    #[test]
+    fn shared_clauses() {
+        use crate::{definitions::CNFValue, dimacs::read_dimacs_cnf, sdpll::DPLLSolverBuilder};
+        let cnf = CNF::from(read_dimacs_cnf(include_str!("../benchmarks/uuf50-01.cnf").as_bytes()).unwrap());
+        //Deterministic, so the same clauses are published on every run
+        let mut sharing = PDPLLSolverBuilder::new(3).sharing(Sharing::new()).deterministic(true).solver(cnf.clone());
+        assert_eq!(sharing.solve(), Satisfiability::UNSAT);
+        assert!(sharing.shared > 0);
+
+        //Learned clauses used in the refutation are replaced by the input clauses they came from
+        let core = sharing.core().unwrap();
+        assert!(core.clauses().all(|c| cnf.clauses().any(|f| f == c)));
+        assert_eq!(DPLLSolverBuilder::new().build(core).solve(), Satisfiability::UNSAT);
+
+        //Random 3-SAT around the threshold
+        let mut rng = StdRng::seed_from_u64(13);
+        for _ in 0..20 {
+            let mut dimacs = "p cnf 30 128\n".to_string();
+            for _ in 0..128 {
+                let clause: Vec<String> = (0..3)
+                    .map(|_| {
+                        let var: i32 = rng.gen_range(1..=30);
+                        (if rng.gen_bool(0.5) { var } else { -var }).to_string()
+                    })
+                    .collect();
+                dimacs.push_str(&format!("{} 0\n", clause.join(" ")));
+            }
+            let cnf = CNF::from(read_dimacs_cnf(dimacs.as_bytes()).unwrap());
+            let expected = DPLLSolverBuilder::new().build(cnf.clone()).solve();
+            let mut solver = PDPLLSolverBuilder::new(2).sharing(Sharing::new().max_size(20)).build(cnf.clone());
+            assert_eq!(solver.solve(), expected, "{}", dimacs);
+            if let Some(model) = solver.model() {
+                assert_eq!(cnf.evaluate(&model), CNFValue::SAT);
+            }
+        }
+    }
+
+    #[test]
+    fn persistent_imports() {
+        use crate::definitions::Literal;
+        let [a, b, c, d] = ["a", "b", "c", "d"].map(|v| Literal::new(v.to_string()));
+        let cnf = CNF::new().add_clause([a.identity(), d.identity()].into_iter().collect());
+        let mut solver = PDPLLSolverBuilder::new(1).sharing(Sharing::new().max_import_size(1)).solver(cnf.clone());
+        solver.buffer = solver.sharing.as_ref().map(|sharing| sharing.buffer());
+        solver.buffer.as_ref().unwrap().publish(2, [a.identity(), b.identity(), c.identity()].into_iter().collect());
+        let imported = |solver: &PDPLLSolver, worker: &mut Worker| match solver.import(CNFValue::Formula(cnf.clone()), worker) {
+            Some(CNFValue::Formula(f)) => f.clauses().count() - 1,
+            _ => unreachable!(),
+        };
+
+        //c is the only unassigned literal below ~a ~b, above it the clause waits
+        let mut worker = Worker::new(1);
+        worker.push(a.not(), Reason::Assumed, 1);
+        let mark = worker.mark();
+        worker.push(b.not(), Reason::Assumed, 2);
+        assert_eq!(imported(&solver, &mut worker), 1);
+        worker.restore(mark);
+        assert_eq!(imported(&solver, &mut worker), 0);
+        worker.push(b.not(), Reason::Assumed, 2);
+        assert_eq!(imported(&solver, &mut worker), 1);
+    }
 }
@@ -0,0 +1,200 @@
+//Portfolio solving: differently configured solvers run on the same formula in their own threads, the
+//first SAT or UNSAT answer wins and the other solvers are cancelled through a terminate flag in their
+//limits
+//Local search solvers ignore conflict limits and cannot prove UNSAT, so they are also cancelled once
+//every complete solver of the portfolio has given up. A portfolio of local search solvers alone never
+//answers UNSAT and runs until its time limit or terminate flag on an unsatisfiable formula
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+use crate::{
+    config::{SolverConfig, SolverKind},
+    definitions::{Assignments, Heuristic, Polarity, Satisfiability, CNF},
+    limits::Limits,
+    statistics::Statistics,
+    Solver, SolverBuilder,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortfolioSolverBuilder {
+    configs: Vec<SolverConfig>,
+    limits: Limits,
+    //Seed the configurations were derived from by diverse
+    seed: Option<u64>,
+}
+
+impl PortfolioSolverBuilder {
+    //One thread per configuration
+    pub fn new(configs: Vec<SolverConfig>) -> Self {
+        PortfolioSolverBuilder { configs, limits: Limits::new(), seed: None }
+    }
+
+    //A MOM DPLL solver, which makes the portfolio complete, followed by WalkSAT, random-decision DPLL
+    //trying False first, ProbSAT and MOM DPLL with random polarities in turn, seeded with seed, seed + 1,
+    //... Restart policies are not varied, as the DPLL solvers backtrack chronologically without restarts
+    pub fn diverse(threads: usize, seed: u64) -> Self {
+        let configs = (0..threads)
+            .map(|i| {
+                let config = match i {
+                    0 => SolverConfig::new(SolverKind::DPLL),
+                    _ => match (i - 1) % 4 {
+                        0 => SolverConfig::new(SolverKind::WalkSAT),
+                        1 => SolverConfig::new(SolverKind::DPLL).heuristic(Heuristic::Random).polarity(Polarity::Negative),
+                        2 => SolverConfig::new(SolverKind::ProbSAT),
+                        _ => SolverConfig::new(SolverKind::DPLL).polarity(Polarity::Random),
+                    },
+                };
+                config.seed(seed.wrapping_add(i as u64))
+            })
+            .collect();
+        PortfolioSolverBuilder { seed: Some(seed), ..PortfolioSolverBuilder::new(configs) }
+    }
+
+    //Replaces the limits of every configuration
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+}
+
+impl SolverBuilder for PortfolioSolverBuilder {
+    fn build(self, formula: CNF) -> Box<dyn Solver> {
+        Box::new(PortfolioSolver {
+            formula,
+            configs: self.configs,
+            limits: self.limits,
+            seed: self.seed,
+            model: None,
+            core: None,
+            statistics: Statistics::default(),
+        })
+    }
+}
+
+pub struct PortfolioSolver {
+    formula: CNF,
+    configs: Vec<SolverConfig>,
+    limits: Limits,
+    seed: Option<u64>,
+    model: Option<Assignments>,
+    core: Option<CNF>,
+    //Summed over all solvers, with the wall-clock time of the portfolio
+    statistics: Statistics,
+}
+
+struct Answer {
+    member: usize,
+    satisfiability: Satisfiability,
+    model: Option<Assignments>,
+    core: Option<CNF>,
+    statistics: Statistics,
+}
+
+impl Solver for PortfolioSolver {
+    fn solve(&mut self) -> Satisfiability {
+        let start = Instant::now();
+        let terminate = Arc::new(AtomicBool::new(false));
+        let complete = |config: &SolverConfig| !matches!(config.kind(), SolverKind::WalkSAT | SolverKind::ProbSAT);
+        let running = Arc::new(AtomicUsize::new(self.configs.iter().filter(|c| complete(c)).count()));
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            for (member, config) in self.configs.iter().enumerate() {
+                let is_complete = complete(config);
+                let config = config.clone().limits(self.limits.clone().terminate(terminate.clone()));
+                let (formula, terminate, running) = (self.formula.clone(), terminate.clone(), running.clone());
+                let sender = sender.clone();
+                scope.spawn(move || {
+                    let mut solver = config.build(formula);
+                    let satisfiability = solver.solve();
+                    let last = is_complete && running.fetch_sub(1, Ordering::Relaxed) == 1;
+                    if satisfiability != Satisfiability::Unknown || last {
+                        terminate.store(true, Ordering::Relaxed);
+                    }
+                    let answer = Answer {
+                        member,
+                        satisfiability,
+                        model: solver.model(),
+                        core: solver.core(),
+                        statistics: solver.statistics(),
+                    };
+                    sender.send(answer).unwrap();
+                });
+            }
+        });
+        drop(sender);
+
+        //Answers arrive in the order the solvers finished
+        let answers: Vec<Answer> = receiver.iter().collect();
+        self.statistics = answers.iter().fold(Statistics::default(), |total, answer| Statistics {
+            decisions: total.decisions + answer.statistics.decisions,
+            propagations: total.propagations + answer.statistics.propagations,
+            conflicts: total.conflicts + answer.statistics.conflicts,
+            elapsed: Duration::ZERO,
+            seed: self.seed,
+        });
+        self.statistics.elapsed = start.elapsed();
+        match answers.into_iter().find(|answer| answer.satisfiability != Satisfiability::Unknown) {
+            Some(answer) => {
+                debug!("Portfolio: {} answered {:?}", self.configs[answer.member], answer.satisfiability);
+                self.model = answer.model;
+                self.core = answer.core;
+                answer.satisfiability
+            }
+            None => {
+                (self.model, self.core) = (None, None);
+                Satisfiability::Unknown
+            }
+        }
+    }
+
+    fn model(&self) -> Option<Assignments> {
+        self.model.clone()
+    }
+
+    fn core(&self) -> Option<CNF> {
+        self.core.clone()
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.statistics.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    crate::tests::sat_tests!(PortfolioSolverBuilder::diverse(5, 1));
+
+    #[test]
+    fn cancels_local_search() {
+        let cnf = CNF::from(crate::dimacs::read_dimacs_cnf(include_str!("../benchmarks/uuf50-01.cnf").as_bytes()).unwrap());
+        let configs = vec![SolverConfig::new(SolverKind::WalkSAT), SolverConfig::new(SolverKind::DPLL)];
+        let mut solver = PortfolioSolverBuilder::new(configs.clone()).build(cnf.clone());
+        assert_eq!(solver.solve(), Satisfiability::UNSAT);
+        assert!(solver.core().is_some());
+
+        //Cancelled from outside through the limits
+        let terminate = Arc::new(AtomicBool::new(true));
+        let mut solver = PortfolioSolverBuilder::new(configs).limits(Limits::new().terminate(terminate)).build(cnf);
+        assert_eq!(solver.solve(), Satisfiability::Unknown);
+    }
+
+    #[test]
+    fn local_search_only() {
+        let cnf = CNF::from(crate::dimacs::read_dimacs_cnf(include_str!("../benchmarks/uuf50-01.cnf").as_bytes()).unwrap());
+        let configs = vec![SolverConfig::new(SolverKind::WalkSAT), SolverConfig::new(SolverKind::ProbSAT)];
+        let limits = Limits::new().time(Duration::from_millis(200));
+        let mut solver = PortfolioSolverBuilder::new(configs).limits(limits).build(cnf);
+        assert_eq!(solver.solve(), Satisfiability::Unknown);
+        assert!(solver.statistics().elapsed >= Duration::from_millis(200));
+        assert!(solver.core().is_none());
+    }
+}
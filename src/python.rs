@@ -0,0 +1,373 @@
+//Python bindings in the style of PySAT: formulas are lists of DIMACS clauses, solvers take the name of a
+//solver configuration (see config.rs) and answer True, False or None
+//    import sat_solver
+//    cnf = sat_solver.parse_dimacs_cnf("benchmarks/uf20-01.cnf")
+//    solver = sat_solver.Solver("pdpll:2", bootstrap_with=cnf, time_limit=10)
+//    if solver.solve(assumptions=[1, -2]):
+//        print(solver.get_model())
+//    else:
+//        print(solver.get_core(), solver.accum_stats())
+//Like ipasir.rs, every solve hands the formula to a fresh solver with the assumptions as unit clauses, and
+//Ctrl-C interrupts it between two polls
+//The pyo3 macros convert every PyResult into a PyResult
+#![allow(clippy::useless_conversion)]
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::BufReader,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use pyo3::{
+    exceptions::{PyOSError, PyValueError},
+    prelude::*,
+    types::PyDict,
+};
+
+use crate::{
+    config::SolverConfig,
+    definitions::{ClauseRef, Literal, LiteralValue, Satisfiability, Xor, CNF},
+    dimacs::{dimacs_literal, read_dimacs_cnf},
+    limits::Limits,
+    statistics::Statistics,
+    SolverBuilder,
+};
+
+//How often a solve checks for Ctrl-C
+const POLL: Duration = Duration::from_millis(50);
+
+fn check_literals(literals: &[i32]) -> PyResult<()> {
+    match literals.contains(&0) {
+        true => Err(PyValueError::new_err("0 is not a literal")),
+        false => Ok(()),
+    }
+}
+
+#[pyclass(name = "CNF", module = "sat_solver")]
+#[derive(Debug, Clone, Default)]
+struct PyCNF {
+    #[pyo3(get)]
+    clauses: Vec<Vec<i32>>,
+    //l_1 ^ l_2 ^ ... ^ l_n = true, as in DIMACS x-lines
+    #[pyo3(get)]
+    xors: Vec<Vec<i32>>,
+}
+
+#[pymethods]
+impl PyCNF {
+    #[new]
+    #[pyo3(signature = (from_clauses=None))]
+    fn new(from_clauses: Option<Vec<Vec<i32>>>) -> PyResult<Self> {
+        let mut cnf = PyCNF::default();
+        for clause in from_clauses.unwrap_or_default() {
+            cnf.add_clause(clause)?;
+        }
+        Ok(cnf)
+    }
+
+    fn add_clause(&mut self, clause: Vec<i32>) -> PyResult<()> {
+        check_literals(&clause)?;
+        self.clauses.push(clause);
+        Ok(())
+    }
+
+    fn add_xor(&mut self, xor: Vec<i32>) -> PyResult<()> {
+        check_literals(&xor)?;
+        self.xors.push(xor);
+        Ok(())
+    }
+
+    fn extend(&mut self, clauses: Vec<Vec<i32>>) -> PyResult<()> {
+        clauses.into_iter().try_for_each(|clause| self.add_clause(clause))
+    }
+
+    //Largest variable
+    #[getter]
+    fn nv(&self) -> u32 {
+        self.clauses.iter().chain(self.xors.iter()).flatten().map(|l| l.unsigned_abs()).max().unwrap_or(0)
+    }
+
+    fn __len__(&self) -> usize {
+        self.clauses.len() + self.xors.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("CNF(nv={}, clauses={}, xors={})", self.nv(), self.clauses.len(), self.xors.len())
+    }
+}
+
+//Clauses are a CNF or a list of clauses
+fn extract_clauses(formula: &Bound<'_, PyAny>) -> PyResult<PyCNF> {
+    match formula.extract::<PyCNF>() {
+        Ok(cnf) => Ok(cnf),
+        Err(_) => PyCNF::new(Some(formula.extract()?)),
+    }
+}
+
+#[pyfunction]
+fn parse_dimacs_cnf(path: &str) -> PyResult<PyCNF> {
+    let file = File::open(path).map_err(|error| PyOSError::new_err(format!("{}: {}", path, error)))?;
+    let dimacs = read_dimacs_cnf(BufReader::new(file)).map_err(|error| PyValueError::new_err(format!("{}: {}", path, error)))?;
+    Ok(PyCNF { clauses: dimacs.clauses().cloned().collect(), xors: dimacs.xors().cloned().collect() })
+}
+
+//Core of an UNSAT solve
+struct Core {
+    //Assumptions used to prove UNSAT
+    failed: Vec<i32>,
+    clauses: Vec<Vec<i32>>,
+    xors: Vec<Vec<i32>>,
+}
+
+#[pyclass(name = "Solver", module = "sat_solver")]
+struct PySolver {
+    config: SolverConfig,
+    limits: Limits,
+    cnf: PyCNF,
+    model: Option<Vec<i32>>,
+    core: Option<Core>,
+    statistics: Statistics,
+}
+
+#[pymethods]
+impl PySolver {
+    #[new]
+    #[pyo3(signature = (name="dpll", bootstrap_with=None, seed=None, time_limit=None, conflict_limit=None))]
+    fn new(
+        name: &str,
+        bootstrap_with: Option<&Bound<'_, PyAny>>,
+        seed: Option<u64>,
+        time_limit: Option<f64>,
+        conflict_limit: Option<u64>,
+    ) -> PyResult<Self> {
+        let config: SolverConfig = name.parse().map_err(PyValueError::new_err)?;
+        let config = match seed {
+            Some(seed) => config.seed(seed),
+            None => config,
+        };
+        let mut solver = PySolver {
+            config,
+            limits: Limits::new(),
+            cnf: PyCNF::default(),
+            model: None,
+            core: None,
+            statistics: Statistics::default(),
+        };
+        solver.set_limits(time_limit, conflict_limit)?;
+        if let Some(formula) = bootstrap_with {
+            solver.append_formula(formula)?;
+        }
+        Ok(solver)
+    }
+
+    //Replaces the limits of the following solves, None for no limit
+    #[pyo3(signature = (time_limit=None, conflict_limit=None))]
+    fn set_limits(&mut self, time_limit: Option<f64>, conflict_limit: Option<u64>) -> PyResult<()> {
+        let mut limits = Limits::new();
+        if let Some(seconds) = time_limit {
+            let time = Duration::try_from_secs_f64(seconds).map_err(|error| PyValueError::new_err(error.to_string()))?;
+            limits = limits.time(time);
+        }
+        if let Some(conflicts) = conflict_limit {
+            limits = limits.conflicts(conflicts);
+        }
+        self.limits = limits;
+        Ok(())
+    }
+
+    fn add_clause(&mut self, clause: Vec<i32>) -> PyResult<()> {
+        self.cnf.add_clause(clause)
+    }
+
+    fn add_xor(&mut self, xor: Vec<i32>) -> PyResult<()> {
+        self.cnf.add_xor(xor)
+    }
+
+    fn append_formula(&mut self, formula: &Bound<'_, PyAny>) -> PyResult<()> {
+        let formula = extract_clauses(formula)?;
+        self.cnf.clauses.extend(formula.clauses);
+        self.cnf.xors.extend(formula.xors);
+        Ok(())
+    }
+
+    //True if SAT, False if UNSAT, None if a limit was hit
+    #[pyo3(signature = (assumptions=vec![]))]
+    fn solve(&mut self, py: Python<'_>, assumptions: Vec<i32>) -> PyResult<Option<bool>> {
+        check_literals(&assumptions)?;
+        let clauses: Vec<ClauseRef> =
+            self.cnf.clauses.iter().map(|clause| clause.iter().map(|l| dimacs_literal(*l)).collect()).collect();
+        let units: Vec<ClauseRef> = assumptions.iter().map(|l| [dimacs_literal(*l)].into_iter().collect()).collect();
+        let formula = clauses.iter().chain(units.iter()).fold(CNF::new(), |cnf, c| cnf.add_clause(c.clone()));
+        let formula = self
+            .cnf
+            .xors
+            .iter()
+            .fold(formula, |cnf, xor| cnf.add_xor(Xor::new(xor.iter().map(|l| dimacs_literal(*l)))));
+
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let config = self.config.clone().limits(self.limits.clone().terminate(interrupt.clone()));
+        let mut signal = Ok(());
+        let (satisfiability, model, core, statistics) = thread::scope(|scope| {
+            let handle = scope.spawn(move || {
+                let mut solver = config.build(formula);
+                let satisfiability = solver.solve();
+                (satisfiability, solver.model(), solver.core(), solver.statistics())
+            });
+            while !handle.is_finished() {
+                if signal.is_ok() {
+                    signal = py.check_signals();
+                    interrupt.store(signal.is_err(), Ordering::Relaxed);
+                }
+                py.allow_threads(|| thread::sleep(POLL));
+            }
+            handle.join().unwrap()
+        });
+        signal?;
+
+        let nv = self.cnf.nv().max(assumptions.iter().map(|l| l.unsigned_abs()).max().unwrap_or(0)) as i32;
+        //Unassigned variables are reported false
+        self.model = model.map(|model| {
+            let value = |v: i32| model.get(&Literal::new(v.to_string()));
+            (1..=nv).map(|v| if value(v) == LiteralValue::True { v } else { -v }).collect()
+        });
+        self.core = core.map(|core| {
+            let ids: HashSet<usize> = core.clauses().map(|c| c.id()).collect();
+            let failed = assumptions.iter().zip(units.iter()).filter(|(_, u)| ids.contains(&u.id())).map(|(l, _)| *l);
+            let clauses = self.cnf.clauses.iter().zip(clauses.iter()).filter(|(_, c)| ids.contains(&c.id()));
+            Core {
+                failed: failed.collect(),
+                clauses: clauses.map(|(clause, _)| clause.clone()).collect(),
+                //Cores have all the XORs or none
+                xors: if core.xors().next().is_some() { self.cnf.xors.clone() } else { vec![] },
+            }
+        });
+        self.statistics = statistics;
+        Ok(match satisfiability {
+            Satisfiability::SAT => Some(true),
+            Satisfiability::UNSAT => Some(false),
+            Satisfiability::Unknown => None,
+        })
+    }
+
+    //Value of every variable up to the largest one, after a SAT solve
+    fn get_model(&self) -> Option<Vec<i32>> {
+        self.model.clone()
+    }
+
+    //Assumptions used to prove UNSAT, after an UNSAT solve
+    fn get_core(&self) -> Option<Vec<i32>> {
+        self.core.as_ref().map(|core| core.failed.clone())
+    }
+
+    //Input clauses that are UNSAT on their own together with get_core_xors() and get_core(), after an UNSAT solve
+    fn get_core_clauses(&self) -> Option<Vec<Vec<i32>>> {
+        self.core.as_ref().map(|core| core.clauses.clone())
+    }
+
+    //Input XORs used to prove UNSAT, after an UNSAT solve
+    fn get_core_xors(&self) -> Option<Vec<Vec<i32>>> {
+        self.core.as_ref().map(|core| core.xors.clone())
+    }
+
+    //Statistics of the last solve
+    fn accum_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let statistics = PyDict::new_bound(py);
+        statistics.set_item("decisions", self.statistics.decisions)?;
+        statistics.set_item("propagations", self.statistics.propagations)?;
+        statistics.set_item("conflicts", self.statistics.conflicts)?;
+        statistics.set_item("time", self.statistics.elapsed.as_secs_f64())?;
+        statistics.set_item("seed", self.statistics.seed)?;
+        Ok(statistics)
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __exit__(&mut self, _type: &Bound<'_, PyAny>, _value: &Bound<'_, PyAny>, _traceback: &Bound<'_, PyAny>) {}
+}
+
+#[pymodule]
+fn sat_solver(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyCNF>()?;
+    module.add_class::<PySolver>()?;
+    module.add_function(wrap_pyfunction!(parse_dimacs_cnf, module)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(code: &str) {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let module = PyModule::new_bound(py, "sat_solver").unwrap();
+            sat_solver(&module).unwrap();
+            let locals = PyDict::new_bound(py);
+            locals.set_item("sat_solver", module).unwrap();
+            if let Err(error) = py.run_bound(code, None, Some(&locals)) {
+                error.print(py);
+                panic!("Python code failed");
+            }
+        });
+    }
+
+    #[test]
+    fn solves_with_assumptions() {
+        run(r#"
+cnf = sat_solver.CNF(from_clauses=[[1, 2], [-1, 3]])
+assert (cnf.nv, len(cnf)) == (3, 2)
+with sat_solver.Solver(bootstrap_with=cnf, seed=1) as solver:
+    assert solver.solve()
+    model = solver.get_model()
+    assert len(model) == 3 and (1 in model or 2 in model) and (-1 in model or 3 in model)
+    assert solver.solve(assumptions=[-2, -3, 4]) is False
+    assert sorted(solver.get_core()) == [-3, -2]
+    assert solver.get_core_xors() == []
+    assert solver.get_model() is None
+    assert solver.accum_stats()["seed"] == 1
+
+    solver.add_xor([1, 3])
+    assert solver.solve(assumptions=[-2]) is False
+    assert solver.get_core() == [-2]
+    assert solver.get_core_xors() == [[1, 3]]
+    assert sat_solver.Solver(bootstrap_with=solver.get_core_clauses() + [[-2]]).solve() is True
+
+try:
+    sat_solver.Solver("nope")
+    assert False
+except ValueError:
+    pass
+"#);
+    }
+
+    #[test]
+    fn dimacs_files_and_limits() {
+        run(r#"
+cnf = sat_solver.parse_dimacs_cnf("benchmarks/uuf50-01.cnf")
+solver = sat_solver.Solver("pdpll:2", bootstrap_with=cnf.clauses, conflict_limit=1)
+assert solver.solve() is None
+solver.set_limits()
+assert solver.solve() is False
+core = solver.get_core_clauses()
+assert 0 < len(core) <= len(cnf.clauses)
+assert sat_solver.Solver(bootstrap_with=core).solve() is False
+
+import tempfile
+with tempfile.NamedTemporaryFile("w", suffix=".cnf") as malformed:
+    malformed.write("p cnf 1 2\n1 0\n")
+    malformed.flush()
+    try:
+        sat_solver.parse_dimacs_cnf(malformed.name)
+        assert False
+    except ValueError as error:
+        assert "header declares 2 clauses, found 1" in str(error)
+"#);
+    }
+}
@@ -1,39 +1,107 @@
+use std::{cell::RefCell, time::{Duration, Instant}};
+
 use crate::{
-    definitions::{Assignments, CNFValue, LiteralValue, Satisfiability, CNF, SignedLiteral, RefLiteral},
+    definitions::{Assignments, CNFValue, Heuristic, LiteralValue, Polarity, Satisfiability, SearchResult, CNF, SignedLiteral, RefLiteral},
+    gauss::{self, Elimination},
+    limits::Limits,
+    statistics::{Counters, Statistics},
+    trace::Trace,
     Solver, SolverBuilder,
 };
-use rand::{Rng, seq::IteratorRandom, rngs, thread_rng};
+use rand::{rngs::StdRng, seq::IteratorRandom, Rng, SeedableRng};
 use log::debug;
 
+#[derive(Clone)]
 pub struct DPLLSolverBuilder {
+    heuristic: Heuristic,
+    polarity: Polarity,
+    limits: Limits,
+    seed: Option<u64>,
 }
 
 impl DPLLSolverBuilder {
     pub fn new() -> Self {
-        DPLLSolverBuilder { }
+        DPLLSolverBuilder { heuristic: Heuristic::MOM, polarity: Polarity::Positive, limits: Limits::new(), seed: None }
+    }
+
+    pub fn heuristic(mut self, heuristic: Heuristic) -> Self {
+        self.heuristic = heuristic;
+        self
+    }
+
+    pub fn polarity(mut self, polarity: Polarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
     }
 
+    //Seed for random decisions and polarities, drawn from entropy if not set
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+}
+
+impl Default for DPLLSolverBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SolverBuilder for DPLLSolverBuilder {
     fn build(self, formula: CNF) -> Box<dyn Solver> {
-        Box::new(DPLLSolver{formula})
+        let seed = self.seed.unwrap_or_else(rand::random);
+        Box::new(DPLLSolver {
+            formula,
+            heuristic: self.heuristic,
+            polarity: self.polarity,
+            limits: self.limits,
+            seed,
+            rng: RefCell::new(StdRng::seed_from_u64(seed)),
+            counters: Counters::default(),
+            start: Instant::now(),
+            elapsed: Duration::ZERO,
+            model: None,
+            trace: Trace::default(),
+            core: None,
+        })
     }
 }
 
 pub struct DPLLSolver {
     formula: CNF,
+    heuristic: Heuristic,
+    polarity: Polarity,
+    limits: Limits,
+    seed: u64,
+    //Reseeded with seed by every solve
+    rng: RefCell<StdRng>,
+    counters: Counters,
+    start: Instant,
+    elapsed: Duration,
+    model: Option<Assignments>,
+    trace: Trace,
+    core: Option<CNF>,
 }
 
 impl Solver for DPLLSolver {
     fn solve(&mut self) -> Satisfiability {
         let func = |f: CNF| f.mom(1);
-        let di = if true {
-            DI::LazyLiteralList(func)
-        } else {
-            DI::RandomDecision
+        let di = match self.heuristic {
+            Heuristic::MOM => DI::LazyLiteralList(func),
+            Heuristic::Random => DI::RandomDecision,
         };
 
+        self.start = Instant::now();
+        self.counters = Counters::default();
+        self.trace = Trace::default();
+        self.rng = RefCell::new(StdRng::seed_from_u64(self.seed));
+
         //Pure literal elimination- if a literal l only appears as positive/negative, assign it to true/false
         //to strive for satisfiability
         let mut m = Assignments::new();
@@ -46,30 +114,66 @@ impl Solver for DPLLSolver {
             m.assign(l.literal(), value);
         });
 
-        self.dpll_recursive(CNFValue::Formula(self.formula.clone()), m, di)
-            .unwrap()
+        let result = self.dpll_recursive(CNFValue::Formula(self.formula.clone()), m, di);
+        self.elapsed = self.start.elapsed();
+        let satisfiability = result.satisfiability();
+        self.core = (satisfiability == Satisfiability::UNSAT).then(|| self.trace.core(&self.formula));
+        self.model = match result {
+            SearchResult::SAT(model) => Some(model),
+            _ => None,
+        };
+        satisfiability
+    }
+
+    fn model(&self) -> Option<Assignments> {
+        self.model.clone()
+    }
+
+    fn core(&self) -> Option<CNF> {
+        self.core.clone()
+    }
+
+    fn statistics(&self) -> Statistics {
+        Statistics { seed: Some(self.seed), ..self.counters.statistics(self.elapsed) }
     }
 }
 
 #[derive(Debug,PartialEq)]
+#[allow(dead_code)]
 //Next iteration decision instruction for choosing variable to assign
-enum DI<F: Fn(CNF)->Vec<RefLiteral>> {
+enum DI<F: Fn(CNF)->Vec<RefLiteral> + Copy> {
     RandomDecision,
     LiteralList(Vec<RefLiteral>),
     LazyLiteralList(F),
 }
 
+impl<F: Fn(CNF)->Vec<RefLiteral> + Copy> DI<F> {
+    //Instruction for the decision after this one
+    fn next(&self) -> DI<F> {
+        match self {
+            DI::RandomDecision => DI::RandomDecision,
+            DI::LiteralList(l) if l.len() > 1 => DI::LiteralList(l[1..].to_vec()),
+            DI::LiteralList(_) => DI::RandomDecision,
+            DI::LazyLiteralList(func) => DI::LazyLiteralList(*func),
+        }
+    }
+}
+
 impl DPLLSolver {
     /*
         DPLL(F,m):
         Input: CNF, partial assigment m
-        Output: SAT/UNSAT
+        Output: SAT (with the assignments leading to it)/UNSAT, or Unknown once a limit is hit
     */
-    fn dpll_recursive<F: Fn(CNF)->Vec<RefLiteral>>(&self, formula: CNFValue, m: Assignments, di: DI<F>) -> CNFValue {
-        match formula.evaluate(&m) {
-            CNFValue::Formula(f) => {
+    fn dpll_recursive<F: Fn(CNF)->Vec<RefLiteral> + Copy>(&self, formula: CNFValue, m: Assignments, di: DI<F>) -> SearchResult {
+        if self.limits.exceeded(self.start, &self.counters) {
+            return SearchResult::Unknown;
+        }
+
+        let result = match formula.evaluate_traced(&m) {
+            Ok(CNFValue::Formula(f)) => {
                 //Unit propogation- unit p becomes a unit literal for some clause
-                let m = || -> Option<Assignments> {
+                let unit = || -> Option<Assignments> {
                     for clause in f.clauses() {
                         if let Some(l) = clause.is_unit_clause() {
                             let value = match l {
@@ -77,65 +181,113 @@ impl DPLLSolver {
                                 SignedLiteral::Not(_) => LiteralValue::False,
                             };
                             debug!("Unit propogation: Clause {:?}, {:?}", clause, value);
+                            self.trace.clause(clause);
                             return Some(Assignments::new().assign(l.literal(), value).to_owned());
                         }
                     }
                     None
                 }();
 
-                if let Some(m) = m {
-                    return self.dpll_recursive::<F>(CNFValue::Formula(f.clone()), m, DI::RandomDecision);
+                if let Some(unit) = unit {
+                    self.counters.propagation();
+                    self.dpll_recursive::<F>(CNFValue::Formula(f.clone()), unit, di)
+                } else {
+                    self.decide(f, di)
                 }
+            }
 
-                //Decision: If at this point in time we don't find a unit literal or reach a base case, let's make a decision
-
-                //First decision: Choose an unassigned literal p and a random bit b in {0,1} and check for satisfiability
-                let p = match di {
-                    DI::RandomDecision => f.iter_literals()
-                        .choose(&mut thread_rng()).unwrap().literal(),
-                    DI::LiteralList(ref l) => {
-                        if let Some(l) = l.first() {
-                            l.to_owned()
-                        } else {
-                            f.iter_literals()
-                                .choose(&mut thread_rng()).unwrap().literal()
-                        }
-                    },
-                    DI::LazyLiteralList(ref func) => {
-                        if let Some(l) = func(f.clone()).first() {
-                            l.to_owned()
-                        } else {
-                            f.iter_literals()
-                                .choose(&mut thread_rng()).unwrap().literal()
-                        }
-                    },
-                };
-                
-                //Positive bias
-                let value = LiteralValue::True;
-
-                debug!("Set {:?} to {:?}", p, value);
-                if self.dpll_recursive::<F>(
-                    CNFValue::Formula(f.clone()),
-                    Assignments::new().assign(p.clone(), value).to_owned(), DI::RandomDecision
-                ) == CNFValue::SAT
-                {
-                    debug!("SAT: {:?}", m);
-                    return CNFValue::SAT;
+            //Base cases: if F is satisfied/unsatisfied by assignments
+            Ok(CNFValue::SAT) => SearchResult::SAT(Assignments::new()),
+            Ok(CNFValue::UNSAT) => unreachable!("evaluate_traced reports UNSAT as a conflict"),
+            Err(conflict) => {
+                self.trace.conflict(&conflict);
+                self.counters.conflict();
+                SearchResult::UNSAT
+            }
+        };
+
+        //Assignments made at this level are part of any model found below it
+        match result {
+            SearchResult::SAT(mut model) => {
+                model.extend(m);
+                SearchResult::SAT(model)
+            }
+            v => v,
+        }
+    }
+
+    fn decide<F: Fn(CNF)->Vec<RefLiteral> + Copy>(&self, f: CNF, di: DI<F>) -> SearchResult {
+        //XOR propogation- Gauss-Jordan elimination over the XOR constraints finds conflicts and implied units
+        if f.xors().next().is_some() {
+            match gauss::eliminate(f.xors()) {
+                Elimination::Conflict => {
+                    debug!("XOR conflict");
+                    self.trace.xors();
+                    self.counters.conflict();
+                    return SearchResult::UNSAT;
                 }
-                // Let's backtrack in case the first decision doesn't work out
-                else {
-                    return self.dpll_recursive::<F>(
-                        CNFValue::Formula(f.clone()),
-                        Assignments::new()
-                            .assign(p.clone(), value.negate())
-                            .to_owned(), DI::RandomDecision
-                    );
+                Elimination::Units(units) if !units.is_empty() => {
+                    self.trace.xors();
+                    let mut m = Assignments::new();
+                    for l in units.iter() {
+                        debug!("XOR propogation: {:?}", l);
+                        self.counters.propagation();
+                        m.satisfy(l);
+                    }
+                    return self.dpll_recursive::<F>(CNFValue::Formula(f.clone()), m, di);
                 }
+                Elimination::Units(_) => {}
             }
+        }
 
-            //Base cases: if F is satisfied/unsatisfied by assignments
-            v => v,
+        //Decision: If at this point in time we don't find a unit literal or reach a base case, let's make a decision
+
+        //First decision: Choose an unassigned literal p and a random bit b in {0,1} and check for satisfiability
+        let random = || f.iter_literals().choose(&mut *self.rng.borrow_mut()).unwrap().literal();
+        let p = match di {
+            DI::RandomDecision => random(),
+            DI::LiteralList(ref l) => {
+                if let Some(l) = l.first() {
+                    l.to_owned()
+                } else {
+                    random()
+                }
+            },
+            DI::LazyLiteralList(ref func) => {
+                if let Some(l) = func(f.clone()).first() {
+                    l.to_owned()
+                } else {
+                    random()
+                }
+            },
+        };
+        self.counters.decision();
+
+        let value = match self.polarity {
+            Polarity::Positive => LiteralValue::True,
+            Polarity::Negative => LiteralValue::False,
+            Polarity::Random if self.rng.borrow_mut().gen_bool(0.5) => LiteralValue::True,
+            Polarity::Random => LiteralValue::False,
+        };
+
+        debug!("Set {:?} to {:?}", p, value);
+        match self.dpll_recursive::<F>(
+            CNFValue::Formula(f.clone()),
+            Assignments::new().assign(p.clone(), value).to_owned(), di.next()
+        ) {
+            // Let's backtrack in case the first decision doesn't work out
+            SearchResult::UNSAT => {
+                self.dpll_recursive::<F>(
+                    CNFValue::Formula(f),
+                    Assignments::new()
+                        .assign(p.clone(), value.negate())
+                        .to_owned(), di.next()
+                )
+            }
+            v => {
+                debug!("{:?}", v.satisfiability());
+                v
+            }
         }
     }
 }
@@ -144,4 +296,38 @@ impl DPLLSolver {
 mod tests {
     use super::*;
     crate::tests::sat_tests!(DPLLSolverBuilder::new());
+
+    #[test]
+    fn replays_seed() {
+        use crate::dimacs::read_dimacs_cnf;
+        let cnf = CNF::from(read_dimacs_cnf(include_str!("../benchmarks/uf75-01.cnf").as_bytes()).unwrap());
+        let builder = DPLLSolverBuilder::new().heuristic(Heuristic::Random);
+        let run = |solver: &mut Box<dyn Solver>| {
+            let satisfiability = solver.solve();
+            let statistics = solver.statistics();
+            (satisfiability, statistics.decisions, statistics.propagations, statistics.seed, solver.model())
+        };
+
+        let mut solver = builder.clone().build(cnf.clone());
+        let first = run(&mut solver);
+        assert!(first.3.is_some());
+        assert_eq!(run(&mut solver), first);
+        assert_eq!(run(&mut builder.seed(first.3.unwrap()).build(cnf)), first);
+    }
+
+    #[test]
+    fn polarities() {
+        use crate::{definitions::Literal, dimacs::read_dimacs_cnf};
+        //Two decisions set all three variables, to True and then False or to False and then True
+        let cnf = CNF::from(read_dimacs_cnf("p cnf 3 2\n1 2 3 0\n-1 -2 -3 0\n".as_bytes()).unwrap());
+        let trues = |polarity| {
+            let mut solver = DPLLSolverBuilder::new().polarity(polarity).seed(1).build(cnf.clone());
+            assert_eq!(solver.solve(), Satisfiability::SAT);
+            let model = solver.model().unwrap();
+            (1..=3).filter(|v| model.get(&Literal::new(v.to_string())) == LiteralValue::True).count()
+        };
+        assert_eq!(trues(Polarity::Positive), 2);
+        assert_eq!(trues(Polarity::Negative), 1);
+        assert!([1, 2].contains(&trues(Polarity::Random)));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,845 @@
+//HTTP solving service: submitted jobs wait in a bounded queue for one of a fixed pool of solver threads,
+//in submission order, and are kept for fetching until FINISHED later jobs have finished
+//  POST   /jobs              submits a job and answers 202 with it, or 503 if the queue is full. The body is
+//                            DIMACS with the options in the query, /jobs?solver=pdpll:2&time_limit=10, or with
+//                            Content-Type application/json an object with "clauses" and "xors" as lists of
+//                            DIMACS literals, or "dimacs" as text, and the options as fields
+//                            Options: solver (a configuration as in config.rs, default dpll), seed,
+//                            time_limit in seconds and conflict_limit
+//  GET    /jobs              every job, without models and cores
+//  GET    /jobs/<id>         the job: status (queued, running, done, cancelled or failed), answer (SAT,
+//                            UNSAT or UNKNOWN), model, core and statistics
+//  GET    /jobs/<id>/model   +v or -v for every variable in the formula of a SAT job, else null. Likewise /core,
+//                            the indices of the input clauses of an UNSAT core and whether it needs the XORs,
+//                            and /statistics
+//  GET    /jobs/<id>/events  the job as one JSON line per status change, until it is finished
+//  DELETE /jobs/<id>         cancels the job
+//Every job runs under the time limit of the service, or its own if shorter, and may not ask for more than
+//MAX_PAR_FACTOR or the number of cores as parallelism
+//One request per connection, answers close it. Connections over the limit of the service are answered 503
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use log::debug;
+use serde_json::{json, Value};
+
+use crate::{
+    config::{SolverConfig, SolverKind},
+    definitions::{ClauseRef, Literal, LiteralValue, Satisfiability, Xor, CNF},
+    dimacs::{dimacs_literal, read_dimacs_cnf},
+    limits::Limits,
+    statistics::Statistics,
+    SolverBuilder,
+};
+
+const MAX_BODY: usize = 64 << 20;
+//Largest pdpll parallel factor of a job, which splits into up to 2^MAX_PAR_FACTOR cubes
+const MAX_PAR_FACTOR: usize = 8;
+//Finished jobs kept for fetching
+const FINISHED: usize = 1024;
+//How long a client may take to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+//How long and how much of a rejected request is read before closing its connection
+const REJECT_LINGER: Duration = Duration::from_millis(50);
+const MAX_REJECTED: u64 = 64 << 10;
+
+//Status code and JSON body of an answer
+type Response = (u16, Value);
+
+fn error(status: u16, message: &str) -> Response {
+    (status, json!({ "error": message }))
+}
+
+#[derive(Debug, Clone)]
+pub struct ServiceOptions {
+    threads: usize,
+    queue: usize,
+    connections: usize,
+    time: Duration,
+}
+
+impl ServiceOptions {
+    pub fn new() -> Self {
+        ServiceOptions {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            queue: 64,
+            connections: 256,
+            time: Duration::from_secs(300),
+        }
+    }
+
+    //Jobs solved at the same time
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    //Jobs waiting for a thread before submissions are refused
+    pub fn queue(mut self, queue: usize) -> Self {
+        self.queue = queue;
+        self
+    }
+
+    //Connections open at the same time, including the ones streaming events
+    pub fn connections(mut self, connections: usize) -> Self {
+        self.connections = connections.max(1);
+        self
+    }
+
+    //Time limit of every job
+    pub fn time(mut self, time: Duration) -> Self {
+        self.time = time;
+        self
+    }
+}
+
+impl Default for ServiceOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//A job as submitted
+struct Input {
+    config: SolverConfig,
+    limits: Limits,
+    clauses: Vec<Vec<i32>>,
+    xors: Vec<Vec<i32>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Queued,
+    Running,
+    Done,
+    Cancelled,
+    Failed,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Queued => "queued",
+            Status::Running => "running",
+            Status::Done => "done",
+            Status::Cancelled => "cancelled",
+            Status::Failed => "failed",
+        }
+    }
+
+    fn finished(self) -> bool {
+        !matches!(self, Status::Queued | Status::Running)
+    }
+}
+
+struct Outcome {
+    answer: Satisfiability,
+    model: Option<Vec<i32>>,
+    //Indices of the input clauses of the core, and whether it has the XORs
+    core: Option<(Vec<usize>, bool)>,
+    statistics: Statistics,
+}
+
+struct Job {
+    status: Status,
+    solver: String,
+    //Taken by the thread that solves it
+    input: Option<Input>,
+    cancel: Arc<AtomicBool>,
+    outcome: Option<Outcome>,
+    error: Option<String>,
+}
+
+impl Job {
+    fn summary(&self, id: u64) -> Value {
+        let answer = self.outcome.as_ref().map(|outcome| match outcome.answer {
+            Satisfiability::SAT => "SAT",
+            Satisfiability::UNSAT => "UNSAT",
+            Satisfiability::Unknown => "UNKNOWN",
+        });
+        json!({ "id": id, "status": self.status.name(), "solver": self.solver, "answer": answer })
+    }
+
+    fn to_json(&self, id: u64) -> Value {
+        let mut job = self.summary(id);
+        let outcome = self.outcome.as_ref();
+        job["model"] = json!(outcome.and_then(|outcome| outcome.model.as_ref()));
+        job["core"] = json!(outcome
+            .and_then(|outcome| outcome.core.as_ref())
+            .map(|(clauses, xors)| json!({ "clauses": clauses, "xors": xors })));
+        job["statistics"] = json!(outcome.map(|outcome| {
+            let statistics = &outcome.statistics;
+            json!({
+                "decisions": statistics.decisions,
+                "propagations": statistics.propagations,
+                "conflicts": statistics.conflicts,
+                "time": statistics.elapsed.as_secs_f64(),
+                "seed": statistics.seed,
+            })
+        }));
+        if let Some(error) = &self.error {
+            job["error"] = json!(error);
+        }
+        job
+    }
+}
+
+#[derive(Default)]
+struct Jobs {
+    next: u64,
+    queue: VecDeque<u64>,
+    finished: VecDeque<u64>,
+    jobs: HashMap<u64, Job>,
+}
+
+impl Jobs {
+    fn finish(&mut self, id: u64) {
+        self.finished.push_back(id);
+        while self.finished.len() > FINISHED {
+            let forgotten = self.finished.pop_front().unwrap();
+            self.jobs.remove(&forgotten);
+        }
+    }
+}
+
+struct Shared {
+    options: ServiceOptions,
+    jobs: Mutex<Jobs>,
+    //Notified on every change of a job
+    changed: Condvar,
+    connections: AtomicUsize,
+}
+
+pub struct Service {
+    listener: TcpListener,
+    shared: Arc<Shared>,
+}
+
+impl Service {
+    //Listens on address and starts the solver threads
+    pub fn bind<A: ToSocketAddrs>(address: A, options: ServiceOptions) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let shared = Arc::new(Shared {
+            options,
+            jobs: Mutex::new(Jobs::default()),
+            changed: Condvar::new(),
+            connections: AtomicUsize::new(0),
+        });
+        for _ in 0..shared.options.threads {
+            let shared = shared.clone();
+            thread::spawn(move || shared.work());
+        }
+        Ok(Service { listener, shared })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    //Answers requests until the listener fails, each connection on a thread of its own
+    pub fn serve(&self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            let mut stream = stream?;
+            if self.shared.connections.fetch_add(1, Ordering::Relaxed) >= self.shared.options.connections {
+                self.shared.connections.fetch_sub(1, Ordering::Relaxed);
+                if let Err(error) = reject(&mut stream) {
+                    debug!("connection failed: {}", error);
+                }
+                continue;
+            }
+            let shared = self.shared.clone();
+            thread::spawn(move || {
+                if let Err(error) = shared.handle(stream) {
+                    debug!("connection failed: {}", error);
+                }
+                shared.connections.fetch_sub(1, Ordering::Relaxed);
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Shared {
+    fn work(&self) {
+        loop {
+            let (id, input, cancel) = {
+                let mut jobs = self.jobs.lock().unwrap();
+                let id = loop {
+                    match jobs.queue.pop_front() {
+                        Some(id) => break id,
+                        None => jobs = self.changed.wait(jobs).unwrap(),
+                    }
+                };
+                let job = jobs.jobs.get_mut(&id).unwrap();
+                job.status = Status::Running;
+                (id, job.input.take().unwrap(), job.cancel.clone())
+            };
+            self.changed.notify_all();
+            debug!("solving job {}", id);
+
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| solve(input, cancel.clone())));
+            let mut jobs = self.jobs.lock().unwrap();
+            let job = jobs.jobs.get_mut(&id).unwrap();
+            match outcome {
+                Ok(outcome) => {
+                    let cancelled = cancel.load(Ordering::Relaxed) && outcome.answer == Satisfiability::Unknown;
+                    job.status = if cancelled { Status::Cancelled } else { Status::Done };
+                    job.outcome = Some(outcome);
+                }
+                Err(payload) => {
+                    job.status = Status::Failed;
+                    job.error = payload
+                        .downcast_ref::<&str>()
+                        .map(|s| s.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned());
+                }
+            }
+            jobs.finish(id);
+            self.changed.notify_all();
+        }
+    }
+
+    fn submit(&self, input: Input) -> Response {
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.queue.len() >= self.options.queue {
+            return error(503, "job queue is full");
+        }
+        jobs.next += 1;
+        let id = jobs.next;
+        let job = Job {
+            status: Status::Queued,
+            solver: input.config.to_string(),
+            input: Some(input),
+            cancel: Arc::new(AtomicBool::new(false)),
+            outcome: None,
+            error: None,
+        };
+        let summary = job.summary(id);
+        jobs.jobs.insert(id, job);
+        jobs.queue.push_back(id);
+        self.changed.notify_all();
+        (202, summary)
+    }
+
+    fn cancel(&self, id: u64) -> Response {
+        let mut guard = self.jobs.lock().unwrap();
+        let jobs = &mut *guard;
+        let Some(job) = jobs.jobs.get_mut(&id) else {
+            return error(404, "no such job");
+        };
+        match job.status {
+            Status::Queued => {
+                job.status = Status::Cancelled;
+                job.input = None;
+                jobs.queue.retain(|queued| *queued != id);
+                jobs.finish(id);
+            }
+            //The solver thread reports it cancelled once the solver gives up
+            Status::Running => job.cancel.store(true, Ordering::Relaxed),
+            _ => {}
+        }
+        let answer = jobs.jobs.get(&id).map_or(Value::Null, |job| job.to_json(id));
+        self.changed.notify_all();
+        (200, answer)
+    }
+
+    fn job<F: FnOnce(Value) -> Value>(&self, id: &str, f: F) -> Response {
+        let jobs = self.jobs.lock().unwrap();
+        match id.parse().ok().and_then(|id| jobs.jobs.get(&id).map(|job| job.to_json(id))) {
+            Some(job) => (200, f(job)),
+            None => error(404, "no such job"),
+        }
+    }
+
+    fn route(&self, request: &Request) -> Response {
+        let path: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+        match (request.method.as_str(), path.as_slice()) {
+            ("GET", ["jobs"]) => {
+                let jobs = self.jobs.lock().unwrap();
+                let mut ids: Vec<&u64> = jobs.jobs.keys().collect();
+                ids.sort();
+                (200, ids.into_iter().map(|id| jobs.jobs[id].summary(*id)).collect())
+            }
+            ("POST", ["jobs"]) => match request.input(&self.options) {
+                Ok(input) => self.submit(input),
+                Err(message) => error(400, &message),
+            },
+            ("GET", ["jobs", id]) => self.job(id, |job| job),
+            ("GET", ["jobs", id, field @ ("model" | "core" | "statistics")]) => {
+                self.job(id, |mut job| job[*field].take())
+            }
+            ("DELETE", ["jobs", id]) => match id.parse() {
+                Ok(id) => self.cancel(id),
+                Err(_) => error(404, "no such job"),
+            },
+            (_, ["jobs"] | ["jobs", _] | ["jobs", _, "model" | "core" | "statistics" | "events"]) => {
+                error(405, "method not allowed")
+            }
+            _ => error(404, "no such resource"),
+        }
+    }
+
+    //Writes the job whenever its status changes, until it is finished or forgotten
+    fn events<W: Write>(&self, out: &mut W, id: &str) -> io::Result<()> {
+        let Ok(id) = id.parse::<u64>() else {
+            return respond(out, error(404, "no such job"));
+        };
+        let mut last = None;
+        loop {
+            let (job, status) = {
+                let mut jobs = self.jobs.lock().unwrap();
+                loop {
+                    match jobs.jobs.get(&id) {
+                        None if last.is_none() => return respond(out, error(404, "no such job")),
+                        None => return Ok(()),
+                        Some(job) if Some(job.status) != last => break (job.to_json(id), job.status),
+                        Some(_) => jobs = self.changed.wait(jobs).unwrap(),
+                    }
+                }
+            };
+            if last.is_none() {
+                write!(out, "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nConnection: close\r\n\r\n")?;
+            }
+            writeln!(out, "{}", job)?;
+            out.flush()?;
+            if status.finished() {
+                return Ok(());
+            }
+            last = Some(status);
+        }
+    }
+
+    fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut out = stream.try_clone()?;
+        let request = match Request::read(&mut BufReader::new(stream), &mut out) {
+            Ok(request) => request,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => return respond(&mut out, error(400, &e.to_string())),
+            Err(e) => return Err(e),
+        };
+        debug!("{} {}", request.method, request.path);
+        match (request.method.as_str(), request.path.strip_prefix("/jobs/").and_then(|p| p.strip_suffix("/events"))) {
+            ("GET", Some(id)) => self.events(&mut out, id),
+            _ => respond(&mut out, self.route(&request)),
+        }
+    }
+}
+
+fn solve(input: Input, cancel: Arc<AtomicBool>) -> Outcome {
+    let clauses: Vec<ClauseRef> =
+        input.clauses.iter().map(|clause| clause.iter().map(|l| dimacs_literal(*l)).collect()).collect();
+    let formula = clauses.iter().fold(CNF::new(), |cnf, c| cnf.add_clause(c.clone()));
+    let formula =
+        input.xors.iter().fold(formula, |cnf, xor| cnf.add_xor(Xor::new(xor.iter().map(|l| dimacs_literal(*l)))));
+    let mut solver = input.config.limits(input.limits.terminate(cancel)).build(formula);
+    let answer = solver.solve();
+
+    //Unassigned variables are reported false
+    let model = solver.model().map(|model| {
+        let variables: BTreeSet<i32> = input.clauses.iter().chain(input.xors.iter()).flatten().map(|l| l.abs()).collect();
+        let value = |v: i32| model.get(&Literal::new(v.to_string()));
+        variables.into_iter().map(|v| if value(v) == LiteralValue::True { v } else { -v }).collect()
+    });
+    let core = solver.core().map(|core| {
+        let ids: HashSet<usize> = core.clauses().map(|c| c.id()).collect();
+        let indices = clauses.iter().enumerate().filter(|(_, c)| ids.contains(&c.id())).map(|(i, _)| i);
+        (indices.collect(), core.xors().next().is_some())
+    });
+    Outcome { answer, model, core, statistics: solver.statistics() }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+//%XX escapes and '+' of a query string
+fn decode(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok());
+        let escaped = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+//Answers 503 without reading the request, which is drained for a moment so that closing does not reset the
+//connection before the client reads the answer
+fn reject(stream: &mut TcpStream) -> io::Result<()> {
+    respond(stream, error(503, "too many connections"))?;
+    stream.shutdown(Shutdown::Write)?;
+    stream.set_read_timeout(Some(REJECT_LINGER))?;
+    io::copy(&mut stream.take(MAX_REJECTED), &mut io::sink())?;
+    Ok(())
+}
+
+fn respond<W: Write>(out: &mut W, (status, body): Response) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "",
+    };
+    let body = format!("{}\n", body);
+    write!(
+        out,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    out.flush()
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    json: bool,
+    body: Vec<u8>,
+}
+
+impl Request {
+    //Reads the request line, headers and body, answering "Expect: 100-continue" on out
+    fn read<R: BufRead, W: Write>(input: &mut R, out: &mut W) -> io::Result<Request> {
+        let mut line = String::new();
+        input.read_line(&mut line)?;
+        let mut parts = line.split_whitespace();
+        let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(target), Some(version)) if version.starts_with("HTTP/") => {
+                (method.to_string(), target.to_string())
+            }
+            _ => return Err(invalid("malformed request line")),
+        };
+
+        let (mut length, mut json, mut expect) = (0, false, false);
+        loop {
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            let Some((name, value)) = header.split_once(':') else {
+                return Err(invalid("malformed header"));
+            };
+            let value = value.trim();
+            match name.to_ascii_lowercase().as_str() {
+                "content-length" => length = value.parse().map_err(|_| invalid("invalid Content-Length"))?,
+                "content-type" => json = value.starts_with("application/json"),
+                "expect" => expect = value.eq_ignore_ascii_case("100-continue"),
+                "transfer-encoding" => return Err(invalid("chunked bodies are not supported, send a Content-Length")),
+                _ => {}
+            }
+        }
+        if length > MAX_BODY {
+            return Err(invalid("request body too large"));
+        }
+        if expect && length > 0 {
+            write!(out, "HTTP/1.1 100 Continue\r\n\r\n")?;
+            out.flush()?;
+        }
+        let mut body = Vec::new();
+        input.take(length as u64).read_to_end(&mut body)?;
+        if body.len() < length {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+        let query = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode(name), decode(value))
+            })
+            .collect();
+        Ok(Request { method, path: path.to_string(), query, json, body })
+    }
+
+    //The job in the body, with the options in the query for DIMACS bodies
+    fn input(&self, service: &ServiceOptions) -> Result<Input, String> {
+        let (formula, options) = match self.json {
+            true => {
+                let Value::Object(mut object) = serde_json::from_slice(&self.body).map_err(|e| e.to_string())?
+                else {
+                    return Err("expected a JSON object".to_string());
+                };
+                let formula = match object.remove("dimacs") {
+                    Some(Value::String(dimacs)) => parse_dimacs(dimacs.as_bytes())?,
+                    Some(_) => return Err("dimacs must be a string".to_string()),
+                    None => {
+                        let clauses = literal_lists(object.remove("clauses"), "clauses")?;
+                        let xors = literal_lists(object.remove("xors"), "xors")?;
+                        (clauses, xors)
+                    }
+                };
+                let options = object
+                    .into_iter()
+                    .map(|(name, value)| match value {
+                        Value::String(value) => (name, value),
+                        value => (name, value.to_string()),
+                    })
+                    .collect();
+                (formula, options)
+            }
+            false => (parse_dimacs(&self.body)?, self.query.clone()),
+        };
+        let (clauses, xors) = formula;
+
+        let mut config: SolverConfig = "dpll".parse()?;
+        let mut limits = Limits::new().time(service.time);
+        for (name, value) in options {
+            let invalid = || format!("invalid value for {}: {}", name, value);
+            match name.as_str() {
+                "solver" => config = value.parse()?,
+                "seed" => config = config.seed(value.parse().map_err(|_| invalid())?),
+                "time_limit" => {
+                    let seconds: f64 = value.parse().map_err(|_| invalid())?;
+                    let time = Duration::try_from_secs_f64(seconds).map_err(|_| invalid())?;
+                    limits = limits.time(time.min(service.time));
+                }
+                "conflict_limit" => limits = limits.conflicts(value.parse().map_err(|_| invalid())?),
+                _ => return Err(format!("unknown option: {}", name)),
+            }
+        }
+
+        check_parallelism(&config)?;
+        Ok(Input { config, limits, clauses, xors })
+    }
+}
+
+//Clauses and XORs
+type Formula = (Vec<Vec<i32>>, Vec<Vec<i32>>);
+
+fn parse_dimacs(text: &[u8]) -> Result<Formula, String> {
+    let dimacs = read_dimacs_cnf(text)?;
+    Ok((dimacs.clauses().cloned().collect(), dimacs.xors().cloned().collect()))
+}
+
+fn check_parallelism(config: &SolverConfig) -> Result<(), String> {
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    match config.kind() {
+        SolverKind::PDPLL(par_factor) if par_factor > MAX_PAR_FACTOR => {
+            Err(format!("parallel factor {} is above the limit of {}", par_factor, MAX_PAR_FACTOR))
+        }
+        SolverKind::Portfolio(threads) | SolverKind::CubeAndConquer(threads) if threads > cores => {
+            Err(format!("{} threads are more than the {} cores", threads, cores))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn literal_lists(value: Option<Value>, name: &str) -> Result<Vec<Vec<i32>>, String> {
+    let invalid = || format!("{} must be a list of lists of non-zero integers", name);
+    let Some(value) = value else {
+        return Ok(vec![]);
+    };
+    let lists = value.as_array().ok_or_else(invalid)?;
+    lists
+        .iter()
+        .map(|list| {
+            let literals = list.as_array().ok_or_else(invalid)?;
+            literals
+                .iter()
+                .map(|l| l.as_i64().and_then(|l| i32::try_from(l).ok()).filter(|l| *l != 0).ok_or_else(invalid))
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(options: ServiceOptions) -> SocketAddr {
+        let service = Service::bind("127.0.0.1:0", options).unwrap();
+        let address = service.local_addr().unwrap();
+        thread::spawn(move || service.serve());
+        address
+    }
+
+    //Status and body of the answer to a request
+    fn request(address: SocketAddr, method: &str, target: &str, content_type: &str, body: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            target,
+            content_type,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut answer = String::new();
+        stream.read_to_string(&mut answer).unwrap();
+        let (head, body) = answer.split_once("\r\n\r\n").unwrap();
+        (head.split_whitespace().nth(1).unwrap().parse().unwrap(), body.to_string())
+    }
+
+    fn get(address: SocketAddr, target: &str) -> Value {
+        let (status, body) = request(address, "GET", target, "text/plain", "");
+        assert_eq!(status, 200, "{}", body);
+        serde_json::from_str(&body).unwrap()
+    }
+
+    fn submit(address: SocketAddr, target: &str, content_type: &str, body: &str) -> u64 {
+        let (status, body) = request(address, "POST", target, content_type, body);
+        assert_eq!(status, 202, "{}", body);
+        serde_json::from_str::<Value>(&body).unwrap()["id"].as_u64().unwrap()
+    }
+
+    //The job once it is finished
+    fn wait(address: SocketAddr, id: u64) -> Value {
+        let (status, events) = request(address, "GET", &format!("/jobs/{}/events", id), "text/plain", "");
+        assert_eq!(status, 200);
+        let last = events.lines().last().unwrap();
+        serde_json::from_str(last).unwrap()
+    }
+
+    #[test]
+    fn solves_dimacs_and_json() {
+        let address = service(ServiceOptions::new().threads(2));
+
+        let uf20 = include_str!("../benchmarks/uf20-01.cnf");
+        let sat = submit(address, "/jobs?solver=pdpll%3A2&seed=3", "text/plain", uf20);
+        let job = wait(address, sat);
+        assert_eq!((job["status"].as_str(), job["answer"].as_str()), (Some("done"), Some("SAT")));
+        assert_eq!(job["solver"], "pdpll:2");
+        assert_eq!(job["statistics"]["seed"], 3);
+        let model: HashSet<i64> = job["model"].as_array().unwrap().iter().map(|l| l.as_i64().unwrap()).collect();
+        assert_eq!(model.len(), 20);
+        let dimacs = read_dimacs_cnf(uf20.as_bytes()).unwrap();
+        assert!(dimacs.clauses().all(|clause| clause.iter().any(|l| model.contains(&(*l as i64)))));
+        assert_eq!(get(address, &format!("/jobs/{}/model", sat)), job["model"]);
+
+        let clauses = "[[1, 2], [-1, 2], [3], [1, -2], [-1, -2]]";
+        let unsat = submit(address, "/jobs", "application/json", &format!(r#"{{"clauses": {}}}"#, clauses));
+        let job = wait(address, unsat);
+        assert_eq!(job["answer"], "UNSAT");
+        assert_eq!(job["model"], Value::Null);
+        assert_eq!(get(address, &format!("/jobs/{}/core", unsat)), json!({ "clauses": [0, 1, 3, 4], "xors": false }));
+
+        let xor = r#"{"clauses": [[1, 2]], "xors": [[1, 2]], "conflict_limit": 100}"#;
+        let job = wait(address, submit(address, "/jobs", "application/json", xor));
+        assert_eq!(job["answer"], "SAT");
+
+        //Models only cover the variables in the formula, whatever the header declares
+        let job = wait(address, submit(address, "/jobs", "text/plain", "p cnf 2000000000 1\n7 0\n"));
+        assert_eq!(job["model"], json!([7]));
+
+        let jobs = get(address, "/jobs");
+        assert_eq!(jobs.as_array().unwrap().len(), 4);
+        assert_eq!(jobs[0]["id"], sat);
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        let address = service(ServiceOptions::new().threads(1));
+        let bad = [
+            ("/jobs", "application/json", "[1, 2]"),
+            ("/jobs", "application/json", r#"{"clauses": [[1, 0]]}"#),
+            ("/jobs", "application/json", r#"{"clauses": [[1]], "time": 1}"#),
+            ("/jobs?solver=nope", "text/plain", "p cnf 1 1\n1 0\n"),
+            ("/jobs?time_limit=-1", "text/plain", "p cnf 1 1\n1 0\n"),
+            ("/jobs", "text/plain", "p cnf 1 2\n1 0\n"),
+            ("/jobs", "text/plain", "p cnf x 1\n1 0\n"),
+            ("/jobs?solver=pdpll:40:det", "text/plain", "p cnf 1 1\n1 0\n"),
+            ("/jobs?solver=portfolio:100000", "text/plain", "p cnf 1 1\n1 0\n"),
+        ];
+        for (target, content_type, body) in bad {
+            assert_eq!(request(address, "POST", target, content_type, body).0, 400, "{}", body);
+        }
+        assert_eq!(request(address, "GET", "/jobs/7", "text/plain", "").0, 404);
+        assert_eq!(request(address, "GET", "/jobs/7/events", "text/plain", "").0, 404);
+        assert_eq!(request(address, "PUT", "/jobs", "text/plain", "").0, 405);
+        assert_eq!(request(address, "GET", "/", "text/plain", "").0, 404);
+    }
+
+    //holes + 1 pigeons in holes, one per hole: UNSAT and out of reach of DPLL for 10 holes
+    fn pigeonhole(holes: i32) -> String {
+        let pigeon = |p: i32, h: i32| p * holes + h + 1;
+        let mut clauses: Vec<String> =
+            (0..=holes).map(|p| (0..holes).map(|h| format!("{} ", pigeon(p, h))).collect::<String>()).collect();
+        for h in 0..holes {
+            for p in 0..=holes {
+                clauses.extend((0..p).map(|q| format!("-{} -{} ", pigeon(q, h), pigeon(p, h))));
+            }
+        }
+        let header = format!("p cnf {} {}\n", (holes + 1) * holes, clauses.len());
+        clauses.iter().fold(header, |dimacs, clause| dimacs + clause + "0\n")
+    }
+
+    #[test]
+    fn cancels_and_bounds_the_queue() {
+        let address = service(ServiceOptions::new().threads(1).queue(1));
+        let hard = &pigeonhole(10);
+        let running = submit(address, "/jobs", "text/plain", hard);
+        while get(address, &format!("/jobs/{}", running))["status"] != "running" {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let queued = submit(address, "/jobs", "text/plain", hard);
+        assert_eq!(request(address, "POST", "/jobs", "text/plain", hard).0, 503);
+
+        let (status, body) = request(address, "DELETE", &format!("/jobs/{}", queued), "text/plain", "");
+        let job: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!((status, job["status"].as_str()), (200, Some("cancelled")));
+        request(address, "DELETE", &format!("/jobs/{}", running), "text/plain", "");
+        let job = wait(address, running);
+        assert_eq!((job["status"].as_str(), job["answer"].as_str()), (Some("cancelled"), Some("UNKNOWN")));
+
+        let limited = submit(address, "/jobs?time_limit=0.05", "text/plain", hard);
+        assert_eq!(wait(address, limited)["status"], "done");
+        assert_eq!(wait(address, limited)["answer"], "UNKNOWN");
+    }
+
+    #[test]
+    fn limits_connections() {
+        let address = service(ServiceOptions::new().threads(1).connections(1));
+        //Holds the only connection until it sends its request
+        let mut idle = TcpStream::connect(address).unwrap();
+        let (status, body) = request(address, "GET", "/jobs", "text/plain", "");
+        assert_eq!((status, body.trim()), (503, r#"{"error":"too many connections"}"#));
+
+        write!(idle, "GET /jobs HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut answer = String::new();
+        idle.read_to_string(&mut answer).unwrap();
+        assert!(answer.starts_with("HTTP/1.1 200 OK"));
+        //The connection is released after the answer
+        while request(address, "GET", "/jobs", "text/plain", "").0 != 200 {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//Learned clause sharing between the workers of a parallel search
+//Workers learn a clause at each conflict by resolving the conflicting clause with the reasons of the
+//propagated literals on their trail, until a single literal of the last decision level is left (first
+//UIP). The clause is implied by the formula, so it holds in every branch of the search. Short or low-LBD clauses
+//are published to an append-only buffer that other workers poll at every node of their search.
+//The buffer is lock-free: a writer reserves a slot with an atomic increment and fills it once, readers
+//keep their own cursor and stop at the first slot that is not filled yet.
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    OnceLock,
+};
+
+use crate::definitions::ClauseRef;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sharing {
+    max_size: usize,
+    max_lbd: usize,
+    max_import_size: usize,
+    capacity: usize,
+}
+
+impl Sharing {
+    pub fn new() -> Self {
+        Sharing { max_size: 8, max_lbd: 4, max_import_size: 8, capacity: 1 << 16 }
+    }
+
+    //Clauses with at most this many literals are published
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    //Clauses over at most this many decision levels are published, whatever their size
+    pub fn max_lbd(mut self, max_lbd: usize) -> Self {
+        self.max_lbd = max_lbd;
+        self
+    }
+
+    //A worker imports a published clause if at most this many of its literals are unassigned on its
+    //trail and none is true
+    pub fn max_import_size(mut self, max_import_size: usize) -> Self {
+        self.max_import_size = max_import_size;
+        self
+    }
+
+    //Clauses published per solve, later clauses are dropped
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub(crate) fn exports(&self, size: usize, lbd: usize) -> bool {
+        size <= self.max_size || lbd <= self.max_lbd
+    }
+
+    pub(crate) fn imports(&self, unassigned: usize) -> bool {
+        unassigned <= self.max_import_size
+    }
+
+    pub(crate) fn buffer(&self) -> ClauseBuffer {
+        ClauseBuffer { slots: (0..self.capacity).map(|_| OnceLock::new()).collect(), next: AtomicUsize::new(0) }
+    }
+}
+
+impl Default for Sharing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) struct SharedClause {
+    pub(crate) worker: usize,
+    pub(crate) clause: ClauseRef,
+}
+
+pub(crate) struct ClauseBuffer {
+    slots: Vec<OnceLock<SharedClause>>,
+    next: AtomicUsize,
+}
+
+impl ClauseBuffer {
+    //False if the buffer is full
+    pub(crate) fn publish(&self, worker: usize, clause: ClauseRef) -> bool {
+        let slot = self.next.fetch_add(1, Ordering::Relaxed);
+        match self.slots.get(slot) {
+            Some(slot) => slot.set(SharedClause { worker, clause }).is_ok(),
+            None => false,
+        }
+    }
+
+    //Clauses published after cursor, which is moved past them
+    pub(crate) fn read(&self, cursor: &mut usize) -> impl Iterator<Item = &SharedClause> {
+        let end = self.next.load(Ordering::Relaxed).min(self.slots.len());
+        let start = *cursor;
+        let published = self.slots[start.min(end)..end].iter().take_while(|slot| slot.get().is_some()).count();
+        *cursor = start + published;
+        self.slots[start..start + published].iter().filter_map(|slot| slot.get())
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.next.load(Ordering::Relaxed).min(self.slots.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::Literal;
+    use std::thread;
+
+    #[test]
+    fn concurrent_publish_and_read() {
+        let buffer = Sharing::new().capacity(1000).buffer();
+        thread::scope(|scope| {
+            for worker in 0..4 {
+                let buffer = &buffer;
+                scope.spawn(move || {
+                    for i in 0..300 {
+                        let l = Literal::new(format!("{}-{}", worker, i));
+                        buffer.publish(worker, [l.identity()].into_iter().collect());
+                    }
+                });
+            }
+        });
+        assert_eq!(buffer.len(), 1000);
+
+        let mut cursor = 0;
+        assert_eq!(buffer.read(&mut cursor).count(), 1000);
+        assert_eq!(buffer.read(&mut cursor).count(), 0);
+        assert!(!buffer.publish(0, ClauseRef::new()));
+    }
+}
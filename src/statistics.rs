@@ -0,0 +1,66 @@
+//Search statistics reported by solvers
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Statistics {
+    pub decisions: u64,
+    pub propagations: u64,
+    //Leaves of the search tree where the formula evaluated to UNSAT
+    pub conflicts: u64,
+    pub elapsed: Duration,
+    //Seed of the random choices, a solver built with it makes the same ones again. None for solvers
+    //without random choices
+    pub seed: Option<u64>,
+}
+
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "decisions: {}", self.decisions)?;
+        writeln!(f, "propagations: {}", self.propagations)?;
+        writeln!(f, "conflicts: {}", self.conflicts)?;
+        if let Some(seed) = self.seed {
+            writeln!(f, "seed: {}", seed)?;
+        }
+        write!(f, "time: {:.3}s", self.elapsed.as_secs_f64())
+    }
+}
+
+//Counters shared by the branches of a (possibly parallel) search
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    decisions: AtomicU64,
+    propagations: AtomicU64,
+    conflicts: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn decision(&self) {
+        self.decisions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn propagation(&self) {
+        self.propagations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn conflict(&self) {
+        self.conflicts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn conflicts(&self) -> u64 {
+        self.conflicts.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn statistics(&self, elapsed: Duration) -> Statistics {
+        Statistics {
+            decisions: self.decisions.load(Ordering::Relaxed),
+            propagations: self.propagations.load(Ordering::Relaxed),
+            conflicts: self.conflicts.load(Ordering::Relaxed),
+            elapsed,
+            seed: None,
+        }
+    }
+}
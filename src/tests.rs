@@ -1,12 +1,32 @@
 #[macro_export]
 macro_rules! sat_tests {
     ($builder: expr) => {
-    use crate::definitions::{ClauseBuilder, Literal, Satisfiability, CNF};
-    use crate::{SolverBuilder};
+    use $crate::definitions::{CNFValue, ClauseBuilder, Literal, Satisfiability, Xor, CNF};
+    use $crate::limits::Limits;
+    use $crate::{SolverBuilder};
+
+    //SAT answers must come with a model that satisfies the formula
+    fn assert_sat(formula: CNF) {
+        let mut solver = $builder.build(formula.clone());
+        assert_eq!(solver.solve(), Satisfiability::SAT);
+        let model = solver.model().expect("SAT without a model");
+        assert_eq!(formula.evaluate(&model), CNFValue::SAT);
+        assert_eq!(solver.core(), None);
+    }
+
+    //UNSAT answers must come with a core of input clauses that is UNSAT on its own
+    fn assert_unsat(formula: CNF) -> CNF {
+        let mut solver = $builder.build(formula.clone());
+        assert_eq!(solver.solve(), Satisfiability::UNSAT);
+        let core = solver.core().expect("UNSAT without a core");
+        assert!(core.clauses().all(|c| formula.clauses().any(|f| f == c)));
+        assert_eq!($builder.build(core.clone()).solve(), Satisfiability::UNSAT);
+        core
+    }
 
     #[test]
     fn case_1() {
-        pretty_env_logger::try_init();
+        let _ = pretty_env_logger::try_init();
         let a = Literal::new("a".to_string());
         let b = Literal::new("b".to_string());
         let c = Literal::new("c".to_string());
@@ -24,12 +44,12 @@ macro_rules! sat_tests {
                     .add_literal(c.identity()).build(),
             );
 
-        assert_eq!($builder.build(formula).solve(), Satisfiability::SAT);
+        assert_sat(formula);
     }
 
     #[test]
     fn case_2() {
-        pretty_env_logger::try_init();
+        let _ = pretty_env_logger::try_init();
         //CNF: (a v b) ^ (~a v c) ^ (~b v ~c)
         let a = Literal::new("a".to_string());
         let b = Literal::new("b".to_string());
@@ -52,12 +72,12 @@ macro_rules! sat_tests {
                     .add_literal(c.not()).build(),
             );
 
-        assert_eq!($builder.build(formula).solve(), Satisfiability::SAT);
+        assert_sat(formula);
     }
 
     #[test]
     fn case_3() {
-        pretty_env_logger::try_init();
+        let _ = pretty_env_logger::try_init();
         /*
            c1 = (¬p1 ∨ p2)
            c2 = (¬p1 ∨ p3 ∨ p5)
@@ -115,12 +135,12 @@ macro_rules! sat_tests {
             .add_clause(c7)
             .add_clause(c8);
 
-        assert_eq!($builder.build(formula).solve(), Satisfiability::SAT);
+        assert_sat(formula);
     }
 
     #[test]
     fn case_4() {
-        pretty_env_logger::try_init();
+        let _ = pretty_env_logger::try_init();
         /*
            (x∨y∨z)∧(x∨y∨¬z)∧(x∨¬y∨z)∧(x∨¬y∨¬z)∧(¬x∨y∨z)∧(¬x∨y∨¬z)∧(¬x∨¬y∨z)∧(¬x∨¬y∨¬z)
         */
@@ -179,7 +199,84 @@ macro_rules! sat_tests {
             .add_clause(c7)
             .add_clause(c8);
 
-        assert_eq!($builder.build(formula).solve(), Satisfiability::UNSAT);
+        assert_unsat(formula.clone());
+
+        let mut solver = $builder.limits(Limits::new().conflicts(1)).build(formula);
+        assert_eq!(solver.solve(), Satisfiability::Unknown);
+        assert_eq!(solver.model(), None);
+        assert_eq!(solver.core(), None);
+    }
+
+    #[test]
+    fn case_5() {
+        let _ = pretty_env_logger::try_init();
+        /*
+           (a v b) ^ (a ^ b ^ c) ^ (b ^ ~c) ^ (c ^ d)
+        */
+        let a = Literal::new("a".to_string());
+        let b = Literal::new("b".to_string());
+        let c = Literal::new("c".to_string());
+        let d = Literal::new("d".to_string());
+
+        let formula = CNF::new()
+            .add_clause(
+                ClauseBuilder::new()
+                    .add_literal(a.identity())
+                    .add_literal(b.identity()).build(),
+            )
+            .add_xor(Xor::new([a.identity(), b.identity(), c.identity()]))
+            .add_xor(Xor::new([b.identity(), c.not()]))
+            .add_xor(Xor::new([c.identity(), d.identity()]));
+
+        assert_sat(formula);
+    }
+
+    #[test]
+    fn case_6() {
+        let _ = pretty_env_logger::try_init();
+        /*
+           (a v b v c) ^ (a ^ b) ^ (b ^ c) ^ (a ^ c)
+        */
+        let a = Literal::new("a".to_string());
+        let b = Literal::new("b".to_string());
+        let c = Literal::new("c".to_string());
+
+        let formula = CNF::new()
+            .add_clause(
+                ClauseBuilder::new()
+                    .add_literal(a.identity())
+                    .add_literal(b.identity())
+                    .add_literal(c.identity()).build(),
+            )
+            .add_xor(Xor::new([a.identity(), b.identity()]))
+            .add_xor(Xor::new([b.identity(), c.identity()]))
+            .add_xor(Xor::new([a.identity(), c.identity()]));
+
+        assert_unsat(formula);
+    }
+
+    #[test]
+    fn case_7() {
+        let _ = pretty_env_logger::try_init();
+        /*
+           a ^ (~a v b) ^ ~b ^ (c v d) ^ (~c v e), only the first three clauses are needed
+        */
+        let a = Literal::new("a".to_string());
+        let b = Literal::new("b".to_string());
+        let c = Literal::new("c".to_string());
+        let d = Literal::new("d".to_string());
+        let e = Literal::new("e".to_string());
+
+        let formula = CNF::new()
+            .add_clause(ClauseBuilder::new().add_literal(a.identity()).build())
+            .add_clause(ClauseBuilder::new().add_literal(a.not()).add_literal(b.identity()).build())
+            .add_clause(ClauseBuilder::new().add_literal(b.not()).build())
+            .add_clause(ClauseBuilder::new().add_literal(c.identity()).add_literal(d.identity()).build())
+            .add_clause(ClauseBuilder::new().add_literal(c.not()).add_literal(e.identity()).build());
+
+        let core = assert_unsat(formula);
+        assert_eq!(core.clauses().count(), 3);
+        assert!(core.iter_literals().all(|l| l.literal() == a || l.literal() == b));
     }
     };
 }
@@ -0,0 +1,59 @@
+//Input constraints used to refute a formula, for unsatisfiable cores
+//Without clause learning every conflict and propagation of an UNSAT search is part of its refutation:
+//each leaf falsifies a traced clause and each propagation follows from one, so the traced clauses
+//are unsatisfiable on their own. Pure literals only satisfy clauses and never need tracing.
+//Learned clauses shared between workers stand for the input clauses they were derived from.
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use crate::definitions::{ClauseRef, Conflict, CNF};
+
+#[derive(Debug, Default)]
+pub(crate) struct Trace {
+    clauses: Mutex<HashSet<usize>>,
+    xors: AtomicBool,
+    //Input clauses each learned clause was derived from
+    derived: Mutex<HashMap<usize, Vec<usize>>>,
+}
+
+impl Trace {
+    //Reason for a unit propagation
+    pub(crate) fn clause(&self, clause: &ClauseRef) {
+        let antecedents = self.antecedents(clause);
+        self.clauses.lock().unwrap().extend(antecedents);
+    }
+
+    //Input clauses behind a clause: itself, or those a learned clause was derived from
+    pub(crate) fn antecedents(&self, clause: &ClauseRef) -> Vec<usize> {
+        match self.derived.lock().unwrap().get(&clause.id()) {
+            Some(antecedents) => antecedents.clone(),
+            None => vec![clause.id()],
+        }
+    }
+
+    //Must be called before the learned clause is used anywhere
+    pub(crate) fn derive(&self, learned: &ClauseRef, antecedents: Vec<usize>) {
+        self.derived.lock().unwrap().insert(learned.id(), antecedents);
+    }
+
+    pub(crate) fn conflict(&self, conflict: &Conflict) {
+        match conflict {
+            Conflict::Clause(clause) => self.clause(clause),
+            Conflict::Xor => self.xors(),
+        }
+    }
+
+    //Gauss-Jordan elimination combines the XOR constraints, so all of them are kept
+    pub(crate) fn xors(&self) {
+        self.xors.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn core(&self, formula: &CNF) -> CNF {
+        formula.subset(&self.clauses.lock().unwrap(), self.xors.load(Ordering::Relaxed))
+    }
+}
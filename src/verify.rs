@@ -0,0 +1,102 @@
+//Model checking, independent of how a solver simplified or preprocessed the formula
+//A constraint is only satisfied if the model decides it, unassigned variables never satisfy a literal
+use crate::{
+    definitions::{Assignments, ClauseRef, ClauseValue, Literal, LiteralValue, XorRef, XorValue, CNF},
+    dimacs::DimacsCnf,
+};
+
+//Constraints of the input that the model does not satisfy
+#[derive(Debug, PartialEq)]
+pub struct Violations<C, X> {
+    pub clauses: Vec<C>,
+    pub xors: Vec<X>,
+}
+
+impl<C, X> Violations<C, X> {
+    fn into_result(self) -> Result<(), Self> {
+        if self.clauses.is_empty() && self.xors.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+pub fn verify_model(cnf: &CNF, model: &Assignments) -> Result<(), Violations<ClauseRef, XorRef>> {
+    Violations {
+        clauses: cnf
+            .clauses()
+            .filter(|c| !matches!((*c).clone().evaluate(model), ClauseValue::True))
+            .cloned()
+            .collect(),
+        xors: cnf
+            .xors()
+            .filter(|x| !matches!((*x).clone().evaluate(model), XorValue::True))
+            .cloned()
+            .collect(),
+    }
+    .into_result()
+}
+
+//DIMACS variable n is the literal named n, as in CNF::from(DimacsCnf)
+fn dimacs_value(literal: i32, model: &Assignments) -> LiteralValue {
+    let value = model.get(&Literal::new(literal.unsigned_abs().to_string()));
+    if literal < 0 {
+        value.negate()
+    } else {
+        value
+    }
+}
+
+//Checks the model against the clauses as they were read, returning the offending ones in input order
+pub fn verify_dimacs_model<'a>(
+    dimacs: &'a DimacsCnf,
+    model: &Assignments,
+) -> Result<(), Violations<&'a Vec<i32>, &'a Vec<i32>>> {
+    Violations {
+        clauses: dimacs
+            .clauses()
+            .filter(|c| !c.iter().any(|l| dimacs_value(*l, model) == LiteralValue::True))
+            .collect(),
+        xors: dimacs
+            .xors()
+            .filter(|x| {
+                let values: Vec<LiteralValue> = x.iter().map(|l| dimacs_value(*l, model)).collect();
+                values.contains(&LiteralValue::Unassigned)
+                    || values.iter().filter(|v| **v == LiteralValue::True).count() % 2 == 0
+            })
+            .collect(),
+    }
+    .into_result()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dimacs::read_dimacs_cnf;
+
+    const INSTANCE: &str = "p cnf 3 4\n1 2 0\n-1 3 0\n-2 -3 0\nx1 2 3 0\n";
+
+    #[test]
+    fn report_violations() {
+        let dimacs = read_dimacs_cnf(INSTANCE.as_bytes()).unwrap();
+        let cnf = CNF::from(read_dimacs_cnf(INSTANCE.as_bytes()).unwrap());
+
+        let mut model = Assignments::new();
+        for (name, value) in [("1", LiteralValue::False), ("2", LiteralValue::True), ("3", LiteralValue::False)] {
+            model.assign(Literal::new(name.to_string()), value);
+        }
+        assert_eq!(verify_dimacs_model(&dimacs, &model), Ok(()));
+        assert!(verify_model(&cnf, &model).is_ok());
+
+        //x3 = 0 falsifies -1 3 and the XOR, x2 unassigned leaves 1 2 satisfied
+        let mut model = Assignments::new();
+        model.assign(Literal::new("1".to_string()), LiteralValue::True);
+        model.assign(Literal::new("3".to_string()), LiteralValue::False);
+        let violations = verify_dimacs_model(&dimacs, &model).unwrap_err();
+        assert_eq!(violations.clauses, vec![&vec![-1, 3]]);
+        assert_eq!(violations.xors, vec![&vec![1, 2, 3]]);
+        let violations = verify_model(&cnf, &model).unwrap_err();
+        assert_eq!((violations.clauses.len(), violations.xors.len()), (1, 1));
+    }
+}
@@ -0,0 +1,163 @@
+//WCNF (weighted partial MaxSAT) parser
+//Pre-2022 format: "p wcnf <vars> <clauses> [<top>]" and "<weight> <literals> 0", a weight >= top marks a hard clause
+//2022 format: no header, "h <literals> 0" for hard and "<weight> <literals> 0" for soft clauses
+//https://maxsat-evaluations.github.io/2022/rules.html#input
+use std::fs::File;
+use std::io::{self, BufRead};
+
+use crate::{
+    definitions::{ClauseRef, Literal, CNF},
+    maxsat::MaxSat,
+};
+
+pub struct Wcnf {
+    num_vars: usize,
+    hard: Vec<Vec<i32>>,
+    soft: Vec<(u64, Vec<i32>)>,
+}
+
+impl Wcnf {
+    pub fn num_vars(&self) -> usize {
+        self.num_vars
+    }
+
+    pub fn hard(&self) -> impl Iterator<Item = &Vec<i32>> {
+        self.hard.iter()
+    }
+
+    pub fn soft(&self) -> impl Iterator<Item = &(u64, Vec<i32>)> {
+        self.soft.iter()
+    }
+
+    //Variable n becomes the literal named n, as for DIMACS
+    pub fn to_maxsat(&self) -> MaxSat {
+        let hard = self.hard.iter().fold(CNF::new(), |cnf, c| cnf.add_clause(clause(c)));
+        self.soft
+            .iter()
+            .fold(MaxSat::new(hard), |maxsat, (weight, c)| maxsat.add_soft(*weight, clause(c)))
+    }
+}
+
+fn clause(literals: &[i32]) -> ClauseRef {
+    literals
+        .iter()
+        .map(|l| {
+            let literal = Literal::new(l.unsigned_abs().to_string());
+            if *l > 0 {
+                literal.identity()
+            } else {
+                literal.not()
+            }
+        })
+        .collect()
+}
+
+fn parse_literals(tokens: &[&str], line: &str) -> Result<Vec<i32>, String> {
+    let literals: Vec<i32> = tokens
+        .iter()
+        .map(|t| t.parse::<i32>().map_err(|_| format!("invalid WCNF literal: {}", line)))
+        .collect::<Result<_, _>>()?;
+    match literals.split_last() {
+        Some((0, literals)) if !literals.contains(&0) => Ok(literals.to_vec()),
+        _ => Err(format!("WCNF clause not terminated by a single 0: {}", line)),
+    }
+}
+
+//Errors on unreadable input, a malformed header, weight or literal, or a clause without a terminating 0
+pub fn read_wcnf<R: BufRead>(reader: R) -> Result<Wcnf, String> {
+    let mut wcnf = Wcnf { num_vars: 0, hard: vec![], soft: vec![] };
+    //Top weight of the pre-2022 format, None for the 2022 format or a header without top
+    let mut top: Option<u64> = None;
+
+    for line in reader.lines() {
+        let line = line.map_err(|error| error.to_string())?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('c') {
+            continue;
+        }
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens[0] == "p" {
+            let invalid = || format!("invalid WCNF header: {}", line);
+            let (num_vars, top_weight) = match tokens.as_slice() {
+                ["p", "wcnf", num_vars, _] => (num_vars, None),
+                ["p", "wcnf", num_vars, _, top_weight] => (num_vars, Some(top_weight)),
+                _ => return Err(invalid()),
+            };
+            wcnf.num_vars = num_vars.parse().map_err(|_| invalid())?;
+            top = top_weight.map(|t| t.parse().map_err(|_| invalid())).transpose()?;
+            continue;
+        }
+
+        let clause = parse_literals(&tokens[1..], line)?;
+        if tokens[0] == "h" {
+            wcnf.hard.push(clause);
+        } else {
+            let weight: u64 = tokens[0]
+                .parse()
+                .map_err(|_| format!("invalid WCNF weight: {}", line))?;
+            if top.is_some_and(|top| weight >= top) {
+                wcnf.hard.push(clause);
+            } else {
+                wcnf.soft.push((weight, clause));
+            }
+        }
+    }
+
+    let max_var = wcnf
+        .hard
+        .iter()
+        .chain(wcnf.soft.iter().map(|(_, c)| c))
+        .flat_map(|c| c.iter())
+        .map(|l| l.unsigned_abs() as usize)
+        .max()
+        .unwrap_or(0);
+    wcnf.num_vars = wcnf.num_vars.max(max_var);
+    Ok(wcnf)
+}
+
+pub fn parse_wcnf(file_path: &str) -> Result<Wcnf, String> {
+    let file = File::open(file_path).map_err(|error| format!("{}: {}", file_path, error))?;
+    read_wcnf(io::BufReader::new(file)).map_err(|error| format!("{}: {}", file_path, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_formats() {
+        let old = read_wcnf("c old format\np wcnf 3 4 10\n10 1 2 0\n10 -1 -2 0\n3 1 0\n5 -2 3 0\n".as_bytes()).unwrap();
+        let new = read_wcnf("c 2022 format\nh 1 2 0\nh -1 -2 0\n3 1 0\n5 -2 3 0\n".as_bytes()).unwrap();
+        for wcnf in [old, new] {
+            assert_eq!(wcnf.num_vars(), 3);
+            assert_eq!(wcnf.hard().collect::<Vec<_>>(), vec![&vec![1, 2], &vec![-1, -2]]);
+            assert_eq!(wcnf.soft().collect::<Vec<_>>(), vec![&(3, vec![1]), &(5, vec![-2, 3])]);
+        }
+
+        //Without top every clause is soft
+        let weighted = read_wcnf("p wcnf 2 2\n4 1 0\n100 -1 2 0\n".as_bytes()).unwrap();
+        assert_eq!(weighted.hard().count(), 0);
+        assert_eq!(weighted.soft().count(), 2);
+    }
+
+    #[test]
+    fn malformed_input() {
+        let malformed: [&[u8]; 9] = [
+            b"p cnf 2 1\n1 1 0\n",
+            b"p wcnf x 1\n1 1 0\n",
+            b"p wcnf 2 1 top\n1 1 0\n",
+            b"p wcnf 2 1\n1 foo 0\n",
+            b"h 1 -x 0\n",
+            b"w 1 2 0\n",
+            b"-3 1 2 0\n",
+            b"h 1 2\n",
+            b"3 1 0 2 0\n",
+        ];
+        for wcnf in malformed {
+            assert!(read_wcnf(wcnf).is_err(), "{:?}", wcnf);
+        }
+        assert!(read_wcnf(&b"h 1 0\n\xff\n"[..]).is_err());
+        assert!(matches!(parse_wcnf("benchmarks/missing.wcnf"), Err(error) if error.starts_with("benchmarks/missing.wcnf: ")));
+    }
+}